@@ -0,0 +1,236 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{
+    decode, decode_header,
+    errors::ErrorKind,
+    jwk::{JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use std::fs;
+use uuid::Uuid;
+
+use crate::config::AuthConfig;
+use crate::error::ApiError;
+use crate::routes::AppState;
+
+// ============ Verifier ============
+
+/// How bearer tokens are checked: Supabase's legacy HS256 shared secret,
+/// or the project's signing keys exported to a local JWKS file.
+enum KeySource {
+    Secret(DecodingKey),
+    Jwks(JwkSet),
+}
+
+pub struct JwtVerifier {
+    keys: Option<KeySource>,
+    audience: String,
+    admin_ids: Vec<Uuid>,
+}
+
+impl JwtVerifier {
+    /// Builds the verifier from `SUPABASE_JWT_SECRET` or the JWKS file at
+    /// `SUPABASE_JWKS_PATH`, as loaded into `Config`.
+    ///
+    /// Missing configuration is not fatal: the server still boots, but every
+    /// authenticated route answers with `AUTH_NOT_CONFIGURED`.
+    pub fn new(config: &AuthConfig) -> Result<Self, String> {
+        let keys = match (&config.jwt_secret, &config.jwks_path) {
+            (secret, Some(path)) => {
+                if secret.is_some() {
+                    tracing::warn!("Both SUPABASE_JWKS_PATH and SUPABASE_JWT_SECRET are set; ignoring SUPABASE_JWT_SECRET");
+                }
+                let raw = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read JWKS file {}: {}", path, e))?;
+                let set: JwkSet = serde_json::from_str(&raw)
+                    .map_err(|e| format!("Invalid JWKS file {}: {}", path, e))?;
+                if set.keys.is_empty() {
+                    return Err(format!("JWKS file {} contains no keys", path));
+                }
+                tracing::info!("Verifying Supabase JWTs against {} JWKS key(s)", set.keys.len());
+                Some(KeySource::Jwks(set))
+            }
+            (Some(secret), None) => {
+                tracing::info!("Verifying Supabase JWTs with HS256 shared secret");
+                Some(KeySource::Secret(DecodingKey::from_secret(secret.as_bytes())))
+            }
+            (None, None) => None,
+        };
+
+        Ok(Self {
+            keys,
+            audience: config.audience.clone(),
+            admin_ids: config.admin_ids.clone(),
+        })
+    }
+
+    fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let keys = self.keys.as_ref().ok_or(AuthError::NotConfigured)?;
+        let header = decode_header(token).map_err(|_| AuthError::InvalidToken)?;
+
+        let (key, algorithm) = match keys {
+            KeySource::Secret(key) => {
+                if header.alg != Algorithm::HS256 {
+                    return Err(AuthError::InvalidToken);
+                }
+                (key.clone(), Algorithm::HS256)
+            }
+            KeySource::Jwks(set) => {
+                let jwk = match (&header.kid, set.keys.as_slice()) {
+                    (Some(kid), _) => set.find(kid),
+                    (None, [only]) => Some(only),
+                    (None, _) => None,
+                }
+                .ok_or(AuthError::InvalidToken)?;
+
+                // Never let the token pick an algorithm the key wasn't issued for
+                if let Some(declared) = jwk.common.key_algorithm
+                    && signing_algorithm(declared) != Some(header.alg)
+                {
+                    return Err(AuthError::InvalidToken);
+                }
+
+                let key = DecodingKey::from_jwk(jwk).map_err(|_| AuthError::InvalidToken)?;
+                (key, header.alg)
+            }
+        };
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud"]);
+
+        decode::<Claims>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+                _ => AuthError::InvalidToken,
+            })
+    }
+}
+
+/// The JWS algorithm a JWK's `alg` names; `None` for encryption algorithms,
+/// which never sign a token.
+fn signing_algorithm(declared: KeyAlgorithm) -> Option<Algorithm> {
+    Some(match declared {
+        KeyAlgorithm::HS256 => Algorithm::HS256,
+        KeyAlgorithm::HS384 => Algorithm::HS384,
+        KeyAlgorithm::HS512 => Algorithm::HS512,
+        KeyAlgorithm::ES256 => Algorithm::ES256,
+        KeyAlgorithm::ES384 => Algorithm::ES384,
+        KeyAlgorithm::RS256 => Algorithm::RS256,
+        KeyAlgorithm::RS384 => Algorithm::RS384,
+        KeyAlgorithm::RS512 => Algorithm::RS512,
+        KeyAlgorithm::PS256 => Algorithm::PS256,
+        KeyAlgorithm::PS384 => Algorithm::PS384,
+        KeyAlgorithm::PS512 => Algorithm::PS512,
+        KeyAlgorithm::EdDSA => Algorithm::EdDSA,
+        KeyAlgorithm::RSA1_5 | KeyAlgorithm::RSA_OAEP | KeyAlgorithm::RSA_OAEP_256 => return None,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    app_metadata: Option<AppMetadata>,
+}
+
+/// The server-controlled part of a Supabase user, which clients can't edit.
+#[derive(Debug, Deserialize)]
+struct AppMetadata {
+    role: Option<String>,
+}
+
+// ============ Extractor ============
+
+/// The caller behind a verified Supabase access token.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: Uuid,
+    /// Listed in `ADMIN_USER_IDS`, or has `app_metadata.role = "admin"`.
+    pub is_admin: bool,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer ").or_else(|| value.strip_prefix("bearer ")))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or(AuthError::MissingToken)?;
+
+        let claims = state.auth.verify(token)?;
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AuthError::InvalidToken)?;
+
+        let is_admin = state.auth.admin_ids.contains(&user_id)
+            || claims
+                .app_metadata
+                .and_then(|meta| meta.role)
+                .is_some_and(|role| role == "admin");
+
+        Ok(AuthUser { user_id, is_admin })
+    }
+}
+
+/// An authenticated caller who is also an administrator.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthUser);
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        if !user.is_admin {
+            tracing::warn!("User {} attempted to use an admin endpoint", user.user_id);
+            return Err(AuthError::Forbidden);
+        }
+        Ok(AdminUser(user))
+    }
+}
+
+// ============ Errors ============
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    TokenExpired,
+    Forbidden,
+    NotConfigured,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let error = match self {
+            AuthError::MissingToken => ApiError::AuthMissingToken,
+            AuthError::InvalidToken => ApiError::AuthInvalidToken,
+            AuthError::TokenExpired => ApiError::AuthTokenExpired,
+            AuthError::Forbidden => ApiError::AuthForbidden,
+            AuthError::NotConfigured => {
+                tracing::error!("Rejected authenticated request: JWT verification is not configured");
+                ApiError::AuthNotConfigured
+            }
+        };
+
+        let mut response = error.into_response();
+        if error.status() == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert("WWW-Authenticate", "Bearer".parse().unwrap());
+        }
+
+        response
+    }
+}
@@ -0,0 +1,682 @@
+// Stand-in for the Perplexity, Gemini, OpenCage, Nominatim and Google Maps
+// APIs, for offline development and integration tests. Point the backend at
+// it with
+//
+//   PERPLEXITY_BASE_URL=http://127.0.0.1:4010
+//   GEMINI_BASE_URL=http://127.0.0.1:4010/v1beta
+//   OPENCAGE_BASE_URL=http://127.0.0.1:4010/geocode/v1
+//   NOMINATIM_BASE_URL=http://127.0.0.1:4010/nominatim
+//   GOOGLE_MAPS_BASE_URL=http://127.0.0.1:4010/maps/api
+//
+// and pick a scenario with MOCK_SCENARIO or at runtime through
+// PUT /_mock/scenario.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{Datelike, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    env,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:4010";
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+// ============ Scenarios ============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scenario {
+    Success,
+    Unauthorized,
+    RateLimited,
+    Timeout,
+    Malformed,
+}
+
+impl Scenario {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "success" | "ok" => Some(Scenario::Success),
+            "401" | "unauthorized" => Some(Scenario::Unauthorized),
+            "429" | "rate_limited" => Some(Scenario::RateLimited),
+            "timeout" => Some(Scenario::Timeout),
+            "malformed" => Some(Scenario::Malformed),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Scenario::Success => "success",
+            Scenario::Unauthorized => "401",
+            Scenario::RateLimited => "429",
+            Scenario::Timeout => "timeout",
+            Scenario::Malformed => "malformed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Service {
+    Chat,
+    Gemini,
+    Geocode,
+    Nominatim,
+    Maps,
+}
+
+impl Service {
+    const ALL: [Service; 5] = [Service::Chat, Service::Gemini, Service::Geocode, Service::Nominatim, Service::Maps];
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "chat" | "perplexity" | "openai" => Some(Service::Chat),
+            "gemini" => Some(Service::Gemini),
+            "geocode" | "opencage" => Some(Service::Geocode),
+            "nominatim" => Some(Service::Nominatim),
+            "maps" | "google_maps" => Some(Service::Maps),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Service::Chat => "chat",
+            Service::Gemini => "gemini",
+            Service::Geocode => "geocode",
+            Service::Nominatim => "nominatim",
+            Service::Maps => "maps",
+        }
+    }
+}
+
+/// The scenario every service follows, plus per-service overrides.
+struct Scenarios {
+    default: Scenario,
+    overrides: HashMap<Service, Scenario>,
+}
+
+#[derive(Clone)]
+struct MockState {
+    scenarios: Arc<Mutex<Scenarios>>,
+    timeout: Duration,
+}
+
+impl MockState {
+    fn scenario(&self, service: Service) -> Scenario {
+        let scenarios = self.scenarios.lock().unwrap();
+        scenarios.overrides.get(&service).copied().unwrap_or(scenarios.default)
+    }
+
+    /// Answers according to the service's current scenario, calling
+    /// `success` only when the request should succeed.
+    async fn respond(&self, service: Service, success: impl FnOnce() -> Response) -> Response {
+        let scenario = self.scenario(service);
+        tracing::info!("{} request answered with scenario {}", service.as_str(), scenario.as_str());
+
+        match scenario {
+            Scenario::Success => success(),
+            Scenario::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": { "message": "Invalid API key (mock)", "code": 401 } })),
+            )
+                .into_response(),
+            Scenario::RateLimited => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, "1")],
+                Json(json!({ "error": { "message": "Rate limit exceeded (mock)", "code": 429 } })),
+            )
+                .into_response(),
+            Scenario::Timeout => {
+                tokio::time::sleep(self.timeout).await;
+                success()
+            }
+            Scenario::Malformed => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/json")],
+                "{\"choices\": [{\"message\": ",
+            )
+                .into_response(),
+        }
+    }
+}
+
+// ============ Control ============
+
+#[derive(Debug, Deserialize)]
+struct ScenarioUpdate {
+    scenario: String,
+    /// `chat`, `gemini`, `geocode`, `nominatim` or `maps`; omitted to
+    /// change the default
+    service: Option<String>,
+}
+
+/// GET /_mock/scenario - Current default and per-service scenarios
+async fn get_scenarios(State(state): State<MockState>) -> impl IntoResponse {
+    let services: HashMap<&str, &str> = Service::ALL
+        .iter()
+        .map(|service| (service.as_str(), state.scenario(*service).as_str()))
+        .collect();
+    let default = state.scenarios.lock().unwrap().default.as_str();
+
+    Json(json!({ "default": default, "services": services }))
+}
+
+/// PUT /_mock/scenario - Switches the scenario for one service or all
+async fn set_scenario(State(state): State<MockState>, Json(update): Json<ScenarioUpdate>) -> Response {
+    let Some(scenario) = Scenario::parse(&update.scenario) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Unknown scenario. Use success, 401, 429, timeout or malformed" })),
+        )
+            .into_response();
+    };
+
+    {
+        let mut scenarios = state.scenarios.lock().unwrap();
+        match update.service.as_deref() {
+            None => {
+                scenarios.default = scenario;
+                scenarios.overrides.clear();
+            }
+            Some(name) => match Service::parse(name) {
+                Some(service) => {
+                    scenarios.overrides.insert(service, scenario);
+                }
+                None => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "error": "Unknown service. Use chat, gemini, geocode, nominatim or maps" })),
+                    )
+                        .into_response();
+                }
+            },
+        }
+    }
+
+    get_scenarios(State(state)).await.into_response()
+}
+
+// ============ Chat Completions (Perplexity / OpenAI) ============
+
+/// POST /chat/completions - Perplexity and OpenAI-compatible chat
+async fn chat_completions(State(state): State<MockState>, Json(body): Json<Value>) -> Response {
+    state
+        .respond(Service::Chat, || {
+            let model = body["model"].as_str().unwrap_or("mock-sonar").to_string();
+            let prompt = body["messages"].to_string();
+            let content = canned_reply(&prompt);
+            let citations = ["https://example.com/mock-source"];
+
+            if body["stream"].as_bool() == Some(true) {
+                let mut events: Vec<Value> = words(&content)
+                    .into_iter()
+                    .map(|word| {
+                        json!({
+                            "object": "chat.completion.chunk",
+                            "model": model,
+                            "choices": [{ "index": 0, "delta": { "content": word }, "finish_reason": null }],
+                            "citations": citations
+                        })
+                    })
+                    .collect();
+                events.push(json!({
+                    "object": "chat.completion.chunk",
+                    "model": model,
+                    "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }],
+                    "usage": usage(&prompt, &content),
+                    "citations": citations
+                }));
+                return event_stream(events, true);
+            }
+
+            Json(json!({
+                "id": "mock-completion",
+                "object": "chat.completion",
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": content },
+                    "finish_reason": "stop"
+                }],
+                "usage": usage(&prompt, &content),
+                "citations": citations
+            }))
+            .into_response()
+        })
+        .await
+}
+
+// ============ Gemini ============
+
+/// POST /v1beta/models/{model}:generateContent (or :streamGenerateContent)
+async fn gemini(
+    State(state): State<MockState>,
+    Path(model_action): Path<String>,
+    Json(body): Json<Value>,
+) -> Response {
+    let (model, action) = model_action.split_once(':').unwrap_or((&model_action, "generateContent"));
+    let model = model.to_string();
+    let streaming = action == "streamGenerateContent";
+
+    state
+        .respond(Service::Gemini, || {
+            let prompt = format!("{}{}", body["systemInstruction"], body["contents"]);
+            let content = canned_reply(&prompt);
+            let usage = usage(&prompt, &content);
+            let usage_metadata = json!({
+                "promptTokenCount": usage["prompt_tokens"],
+                "candidatesTokenCount": usage["completion_tokens"],
+                "totalTokenCount": usage["total_tokens"]
+            });
+
+            let candidate = |text: &str, finish: Option<&str>| {
+                json!({
+                    "candidates": [{
+                        "content": { "role": "model", "parts": [{ "text": text }] },
+                        "finishReason": finish
+                    }],
+                    "modelVersion": model
+                })
+            };
+
+            if streaming {
+                let mut events: Vec<Value> = words(&content).iter().map(|word| candidate(word, None)).collect();
+                if let Some(last) = events.last_mut() {
+                    last["candidates"][0]["finishReason"] = json!("STOP");
+                    last["usageMetadata"] = usage_metadata;
+                }
+                return event_stream(events, false);
+            }
+
+            let mut response = candidate(&content, Some("STOP"));
+            response["usageMetadata"] = usage_metadata;
+            Json(response).into_response()
+        })
+        .await
+}
+
+// ============ OpenCage ============
+
+#[derive(Debug, Deserialize)]
+struct GeocodeQuery {
+    q: String,
+}
+
+/// GET /geocode/v1/json - OpenCage forward and reverse geocoding
+async fn geocode(State(state): State<MockState>, Query(params): Query<GeocodeQuery>) -> Response {
+    state
+        .respond(Service::Geocode, || {
+            // "nowhere", and points within a degree of 0,0 (open sea),
+            // find nothing
+            let open_sea = parse_point(&params.q).is_some_and(|(lat, lng)| lat.abs() < 1.0 && lng.abs() < 1.0);
+            if open_sea || params.q.to_lowercase().contains("nowhere") {
+                return Json(json!({
+                    "results": [],
+                    "status": { "code": 200, "message": "OK" },
+                    "total_results": 0
+                }))
+                .into_response();
+            }
+
+            let (lat, lng) = coordinates(&params.q);
+            let street = match parse_point(&params.q) {
+                Some(_) => "1 Mock Road".to_string(),
+                None => params.q.trim().to_string(),
+            };
+            let result = json!({
+                "formatted": format!("{}, Mockville, Mock State, United States of America", street),
+                "geometry": { "lat": lat, "lng": lng },
+                "bounds": {
+                    "northeast": { "lat": lat + 0.01, "lng": lng + 0.01 },
+                    "southwest": { "lat": lat - 0.01, "lng": lng - 0.01 }
+                },
+                "confidence": 9,
+                "components": {
+                    "_type": "city",
+                    "city": "Mockville",
+                    "suburb": "Old Town",
+                    "county": "Mock County",
+                    "state": "Mock State",
+                    "postcode": "00000",
+                    "country": "United States of America",
+                    "country_code": "us"
+                },
+                "annotations": {
+                    "timezone": { "name": "America/New_York" },
+                    "currency": { "iso_code": "USD", "name": "United States Dollar" }
+                }
+            });
+
+            Json(json!({
+                "results": [result],
+                "status": { "code": 200, "message": "OK" },
+                "total_results": 1
+            }))
+            .into_response()
+        })
+        .await
+}
+
+/// A "lat,lng" reverse-geocoding query.
+fn parse_point(query: &str) -> Option<(f64, f64)> {
+    let (lat, lng) = query.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lng.trim().parse().ok()?))
+}
+
+/// Coordinates for a query: parsed when it is already "lat,lng",
+/// otherwise derived from a hash of the text so they are stable.
+fn coordinates(query: &str) -> (f64, f64) {
+    if let Some(point) = parse_point(query) {
+        return point;
+    }
+
+    // FNV-1a
+    let hash = query
+        .to_lowercase()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3));
+    let lat = (hash % 120_000) as f64 / 1000.0 - 60.0;
+    let lng = ((hash >> 20) % 360_000) as f64 / 1000.0 - 180.0;
+    (lat, lng)
+}
+
+// ============ Nominatim ============
+
+#[derive(Debug, Deserialize)]
+struct NominatimSearchQuery {
+    q: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NominatimReverseQuery {
+    lat: f64,
+    lon: f64,
+}
+
+/// A `jsonv2` place with address details
+fn nominatim_place(street: &str, lat: f64, lng: f64) -> Value {
+    json!({
+        "place_id": 1,
+        "lat": lat.to_string(),
+        "lon": lng.to_string(),
+        "category": "place",
+        "type": "city",
+        "display_name": format!("{}, Old Town, Mockville, Mock County, Mock State, 00000, United States", street),
+        "address": {
+            "road": street,
+            "suburb": "Old Town",
+            "city": "Mockville",
+            "county": "Mock County",
+            "state": "Mock State",
+            "postcode": "00000",
+            "country": "United States",
+            "country_code": "us"
+        },
+        "boundingbox": [
+            (lat - 0.01).to_string(),
+            (lat + 0.01).to_string(),
+            (lng - 0.01).to_string(),
+            (lng + 0.01).to_string()
+        ]
+    })
+}
+
+/// GET /nominatim/search - Nominatim forward geocoding
+async fn nominatim_search(State(state): State<MockState>, Query(params): Query<NominatimSearchQuery>) -> Response {
+    state
+        .respond(Service::Nominatim, || {
+            if params.q.to_lowercase().contains("nowhere") {
+                return Json(json!([])).into_response();
+            }
+            let (lat, lng) = coordinates(&params.q);
+            Json(json!([nominatim_place(params.q.trim(), lat, lng)])).into_response()
+        })
+        .await
+}
+
+/// GET /nominatim/reverse - Nominatim reverse geocoding
+async fn nominatim_reverse(State(state): State<MockState>, Query(params): Query<NominatimReverseQuery>) -> Response {
+    state
+        .respond(Service::Nominatim, || {
+            // Open sea, as for OpenCage
+            if params.lat.abs() < 1.0 && params.lon.abs() < 1.0 {
+                return Json(json!({ "error": "Unable to geocode" })).into_response();
+            }
+            Json(nominatim_place("1 Mock Road", params.lat, params.lon)).into_response()
+        })
+        .await
+}
+
+// ============ Google Maps ============
+
+/// A 1x1 transparent PNG
+const PIXEL_PNG: [u8; 67] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// GET /maps/api/staticmap - Google Static Maps; always a 1x1 image
+async fn static_map(State(state): State<MockState>) -> Response {
+    state
+        .respond(Service::Maps, || {
+            ([(header::CONTENT_TYPE, "image/png")], PIXEL_PNG.to_vec()).into_response()
+        })
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+struct FindPlaceQuery {
+    input: String,
+}
+
+/// GET /maps/api/place/findplacefromtext/json - Google Places "find place"
+async fn find_place(State(state): State<MockState>, Query(params): Query<FindPlaceQuery>) -> Response {
+    state
+        .respond(Service::Maps, || {
+            if params.input.to_lowercase().contains("nowhere") {
+                return Json(json!({ "candidates": [], "status": "ZERO_RESULTS" })).into_response();
+            }
+
+            let (lat, lng) = coordinates(&params.input);
+            Json(json!({
+                "candidates": [{
+                    "place_id": format!("mock-{:x}", (lat * 1000.0) as i64 ^ (lng * 1000.0) as i64),
+                    "name": params.input.trim(),
+                    "formatted_address": format!("{}, Mockville, Mock State, USA", params.input.trim()),
+                    "geometry": { "location": { "lat": lat, "lng": lng } }
+                }],
+                "status": "OK"
+            }))
+            .into_response()
+        })
+        .await
+}
+
+// ============ Canned Content ============
+
+/// Picks a reply that fits the prompt: a full report for analysis prompts,
+/// an address for OCR prompts and a fixed sentence for everything else.
+fn canned_reply(prompt: &str) -> String {
+    if prompt.contains("historical_trends") {
+        analysis_report().to_string()
+    } else if prompt.contains("OCR") {
+        "1600 Amphitheatre Parkway, Mountain View, CA 94043, USA".to_string()
+    } else {
+        "This is a canned reply from the mock upstream server.".to_string()
+    }
+}
+
+fn words(content: &str) -> Vec<String> {
+    content.split_inclusive(' ').map(str::to_string).collect()
+}
+
+fn usage(prompt: &str, content: &str) -> Value {
+    let prompt_tokens = prompt.len() / 4;
+    let completion_tokens = content.len() / 4;
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens
+    })
+}
+
+/// A `text/event-stream` body with one `data:` line per event.
+fn event_stream(events: Vec<Value>, done_marker: bool) -> Response {
+    let mut body: String = events.iter().map(|event| format!("data: {}\n\n", event)).collect();
+    if done_marker {
+        body.push_str("data: [DONE]\n\n");
+    }
+    ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response()
+}
+
+/// A complete report in the structure the analysis prompt asks for, with
+/// six years of history ending last year.
+fn analysis_report() -> Value {
+    let last = Utc::now().year() - 1;
+    let years: Vec<i32> = (last - 5..=last).collect();
+
+    let series = |base: f64, step: f64, key: &str| -> Vec<Value> {
+        years
+            .iter()
+            .enumerate()
+            .map(|(i, year)| {
+                let value = base + step * i as f64;
+                let change = if i == 0 { 0.0 } else { (step / (value - step) * 10000.0).round() / 100.0 };
+                json!({ "year": year, key: value, "change_pct": change })
+            })
+            .collect()
+    };
+
+    json!({
+        "location_info": {
+            "formatted_address": "Mock Address",
+            "coordinates": { "lat": 40.0, "lng": -75.0 },
+            "region": "Mock State",
+            "country": "United States of America",
+            "jurisdiction": "Mock County"
+        },
+        "risk_analysis": {
+            "overall_score": 42,
+            "buying_risk": { "score": 40, "status": "Medium", "factors": ["Stable prices"] },
+            "renting_risk": { "score": 35, "status": "Medium", "factors": ["Steady demand"] },
+            "flood_risk": { "score": 20, "level": "Low", "zones": [], "description": "Outside mapped flood zones." },
+            "crime_rate": { "score": 30, "rate_per_1000": 18.5, "trend": "Decreasing", "types": ["Property crime"] },
+            "air_quality": { "aqi": 42, "score": 80, "rating": "Good", "pollutants": ["PM2.5"] },
+            "amenities": { "score": 70, "walkability": 65, "nearby": [] },
+            "transportation": { "score": 60, "transit_options": ["Bus"], "commute_time": "25 min", "walkability_index": 60 },
+            "neighbourhood": {
+                "score": 68,
+                "rating": "Good",
+                "character": "Quiet residential",
+                "demographics": { "median_age": 38, "population_density": "Medium" }
+            },
+            "environmental_hazards": { "score": 15, "hazards": [], "severity": "Low" },
+            "growth_potential": { "score": 62, "forecast": "Moderate Growth", "drivers": ["New employers"], "outlook_5yr": "Steady" },
+            "political_stability": { "score": 75, "status": "Stable", "factors": [], "recent_events": [], "policy_environment": "Predictable" },
+            "trade_economy": {
+                "gdp_growth": 2.1,
+                "gdp_trend": "Growing",
+                "inflation_rate": 3.0,
+                "unemployment_rate": 4.0,
+                "trade_balance": "Deficit",
+                "economic_outlook": "Stable",
+                "major_industries": ["Services"],
+                "trade_relations": { "status": "Stable", "key_partners": ["Canada"], "impact_on_property": "Neutral" }
+            },
+            "soil_analysis": { "type": "Loam", "stability": "High", "liquefaction_risk": "Low", "foundation_concerns": "None" },
+            "noise_data": { "score": 30, "level": "Quiet", "db_avg": 45, "sources": ["Traffic"] },
+            "light_pollution": { "score": 40, "bortle_scale": 5, "brightness": "Moderate", "impact": "Suburban sky" },
+            "additional_info": {
+                "solar_potential": "Good",
+                "weather_summary": "Temperate",
+                "climate_risks": [],
+                "insurance_considerations": "Standard"
+            }
+        },
+        "historical_trends": {
+            "property_values": series(300000.0, 15000.0, "median_price"),
+            "crime_trends": series(22.0, -0.5, "incidents_per_1000"),
+            "population": series(50000.0, 800.0, "count"),
+            "development_timeline": [{ "year": last, "events": ["New park opened"] }]
+        },
+        "market_intelligence": {
+            "current_trend": "Up",
+            "prediction_6mo": "Modest gains",
+            "prediction_1yr": "Continued growth",
+            "ai_summary": "Canned report from the mock upstream server.",
+            "recent_listings": [],
+            "news": []
+        },
+        "legal_resources": {
+            "jurisdiction": "Mock County",
+            "property_law_system": "Common law",
+            "key_statutes": [],
+            "dispute_process": "Small claims court",
+            "typical_timeline": "3-6 months",
+            "resources": []
+        }
+    })
+}
+
+// ============ Server ============
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "mock_upstream=info".into()),
+        )
+        .init();
+
+    let default = env::var("MOCK_SCENARIO")
+        .ok()
+        .map(|value| Scenario::parse(&value).expect("MOCK_SCENARIO must be success, 401, 429, timeout or malformed"))
+        .unwrap_or(Scenario::Success);
+    let timeout = env::var("MOCK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(DEFAULT_TIMEOUT_SECS));
+    let addr: SocketAddr = env::var("MOCK_UPSTREAM_ADDR")
+        .unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+        .parse()
+        .expect("MOCK_UPSTREAM_ADDR must be host:port");
+
+    let state = MockState {
+        scenarios: Arc::new(Mutex::new(Scenarios {
+            default,
+            overrides: HashMap::new(),
+        })),
+        timeout,
+    };
+
+    let app = Router::new()
+        .route("/_mock/scenario", get(get_scenarios).put(set_scenario))
+        .route("/chat/completions", post(chat_completions))
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1beta/models/:model_action", post(gemini))
+        .route("/geocode/v1/json", get(geocode))
+        .route("/nominatim/search", get(nominatim_search))
+        .route("/nominatim/reverse", get(nominatim_reverse))
+        .route("/maps/api/staticmap", get(static_map))
+        .route("/maps/api/place/findplacefromtext/json", get(find_place))
+        .with_state(state);
+
+    tracing::info!("Mock upstream listening on {} (scenario: {})", addr, default.as_str());
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
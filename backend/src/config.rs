@@ -0,0 +1,886 @@
+use reqwest::Url;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt, fs,
+    net::IpAddr,
+    num::NonZeroUsize,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+use uuid::Uuid;
+
+// Application configuration, read once at startup. Every setting comes from
+// its environment variable or, failing that, from the optional TOML file;
+// the variable wins when both are set. All problems are collected so one
+// failed boot reports every one of them.
+
+/// Read when `CONFIG_FILE` is not set, if it exists
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+const DEFAULT_PERPLEXITY_BASE_URL: &str = "https://api.perplexity.ai";
+const DEFAULT_GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const DEFAULT_OPENCAGE_BASE_URL: &str = "https://api.opencagedata.com/geocode/v1";
+const DEFAULT_GOOGLE_MAPS_BASE_URL: &str = "https://maps.googleapis.com/maps/api";
+const DEFAULT_NOMINATIM_BASE_URL: &str = "https://nominatim.openstreetmap.org";
+
+/// Provider names accepted in the `LLM_ROUTE_*` lists
+const LLM_PROVIDERS: [&str; 3] = ["perplexity", "gemini", "openai"];
+/// Provider names accepted in `GEOCODE_PROVIDERS`
+const GEOCODE_PROVIDERS: [&str; 2] = ["opencage", "nominatim"];
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub cors: CorsConfig,
+    pub database: DatabaseConfig,
+    pub upstreams: UpstreamConfig,
+    pub keys: ApiKeys,
+    pub auth: AuthConfig,
+    pub http: HttpConfig,
+    pub circuit: CircuitConfig,
+    pub cache: CacheConfig,
+    pub llm: LlmConfig,
+    pub analysis: AnalysisConfig,
+    pub geocode: GeocodeConfig,
+    pub maps: MapsConfig,
+    pub limits: LimitsConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind_address: IpAddr,
+    pub port: u16,
+}
+
+/// Which browser origins may call the API. `*` in a list allows any origin.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed on authenticated and paid API routes; empty allows none
+    pub allowed_origins: Vec<String>,
+    /// Origins allowed on public routes such as `/health`
+    pub public_origins: Vec<String>,
+    /// Let API routes be called with cookies or HTTP auth
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight answer
+    pub max_age: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpstreamConfig {
+    pub perplexity_base_url: String,
+    pub perplexity_model: String,
+    pub gemini_base_url: String,
+    pub gemini_model: String,
+    pub opencage_base_url: String,
+    pub nominatim_base_url: String,
+    pub google_maps_base_url: String,
+    /// Set only when `OPENAI_COMPAT_BASE_URL` is
+    pub openai_compat_base_url: Option<String>,
+    pub openai_compat_model: String,
+}
+
+/// API keys exactly as configured. An empty key is kept so requests can
+/// still tell "empty" from "missing".
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    pub gemini: Option<String>,
+    pub opencage: Option<String>,
+    pub ai_service: Option<String>,
+    pub google_maps: Option<String>,
+    pub openai_compat: Option<String>,
+    /// Signs map access tokens; a random per-process secret when unset
+    pub maps_token_secret: Option<String>,
+}
+
+impl ApiKeys {
+    /// Required keys that are unset or empty, by variable name.
+    pub fn missing_required(&self) -> Vec<&'static str> {
+        [
+            ("GEMINI_API_KEY", &self.gemini),
+            ("OPENCAGE_API_KEY", &self.opencage),
+            ("AI_SERVICE_API_KEY", &self.ai_service),
+            ("GOOGLE_MAPS_API_KEY", &self.google_maps),
+        ]
+        .into_iter()
+        .filter(|(_, key)| key.as_deref().is_none_or(|key| key.trim().is_empty()))
+        .map(|(var, _)| var)
+        .collect()
+    }
+}
+
+// Keys never appear in logs, only whether they are set
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let set = |key: &Option<String>| key.as_ref().map(|_| "<set>");
+        f.debug_struct("ApiKeys")
+            .field("gemini", &set(&self.gemini))
+            .field("opencage", &set(&self.opencage))
+            .field("ai_service", &set(&self.ai_service))
+            .field("google_maps", &set(&self.google_maps))
+            .field("openai_compat", &set(&self.openai_compat))
+            .field("maps_token_secret", &set(&self.maps_token_secret))
+            .finish()
+    }
+}
+
+/// How bearer tokens from Supabase are verified.
+#[derive(Clone)]
+pub struct AuthConfig {
+    /// Legacy HS256 shared secret
+    pub jwt_secret: Option<String>,
+    /// JWKS file with the project's signing keys; wins over the secret
+    pub jwks_path: Option<String>,
+    /// Audience every token must carry
+    pub audience: String,
+    /// Users allowed on the admin routes regardless of their role claim
+    pub admin_ids: Vec<Uuid>,
+}
+
+// Same rule as ApiKeys: the secret never appears in logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &self.jwt_secret.as_ref().map(|_| "<set>"))
+            .field("jwks_path", &self.jwks_path)
+            .field("audience", &self.audience)
+            .field("admin_ids", &self.admin_ids)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub connect_timeout: Duration,
+    pub ai_timeout: Duration,
+    pub geocode_timeout: Duration,
+    pub maps_timeout: Duration,
+    pub max_retries: u32,
+    pub retry_budget: Duration,
+    pub pool_max_idle_per_host: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitConfig {
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub geocode_ttl: Duration,
+    pub gemini_ttl: Duration,
+    pub chat_ttl: Duration,
+    pub analysis_ttl: Duration,
+    pub memory_capacity: NonZeroUsize,
+    pub sweep_interval: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmConfig {
+    pub route_analysis: Vec<String>,
+    pub route_chat: Vec<String>,
+    pub route_ocr: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    pub fallback_radius_km: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeocodeConfig {
+    /// Geocoders to try, in order; the next one is used when one fails
+    pub providers: Vec<String>,
+    /// Decimal places reverse-geocoding coordinates are rounded to, so
+    /// nearby clicks share a cache entry (4 places is about 11 m)
+    pub reverse_precision: u32,
+    /// Most queries accepted by one batch request
+    pub batch_max: usize,
+    /// Batch misses sent to the provider at once
+    pub batch_concurrency: usize,
+    /// Batch and suggest misses sent to OpenCage per second, across all
+    /// requests
+    pub batch_rate_per_sec: u32,
+    /// Batch and suggest misses sent to Nominatim per second, including
+    /// failovers
+    pub nominatim_rate_per_sec: u32,
+    /// Suggestions returned by /api/geocode/suggest
+    pub suggest_limit: usize,
+    /// Shortest query sent to the geocoder for suggestions; shorter ones
+    /// only match search history
+    pub suggest_min_chars: usize,
+    /// Longest query accepted for suggestions
+    pub suggest_max_chars: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapsConfig {
+    /// How long a signed map token stays valid
+    pub token_ttl: Duration,
+    /// Origins that may fetch the raw Google Maps key; empty allows none
+    pub key_origins: Vec<String>,
+}
+
+/// Limits on the AI routes, per signed-in user or, for anonymous callers,
+/// per client IP.
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    /// Requests a caller may make back to back
+    pub burst: u32,
+    /// Requests per minute the bucket refills at
+    pub per_minute: u32,
+    /// Requests per caller per UTC day; 0 disables the daily quota
+    pub daily_requests: i64,
+    /// Upstream tokens per caller per UTC day; 0 disables the token quota
+    pub daily_tokens: i64,
+    /// Take the client IP from the last `X-Forwarded-For` entry (behind
+    /// exactly one reverse proxy)
+    pub trust_forwarded_for: bool,
+}
+
+/// Every problem found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration ({} problem(s)):", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads and validates the configuration from the environment and the
+    /// TOML file named by `CONFIG_FILE` (default `config.toml`, if present).
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::build(Loader::new())
+    }
+
+    /// Loads the configuration from TOML text alone, ignoring the
+    /// environment. Used by the integration tests.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Self::build(Loader::from_toml(text))
+    }
+
+    fn build(mut loader: Loader) -> Result<Self, ConfigError> {
+        let server = ServerConfig {
+            bind_address: loader.parse("BIND_ADDRESS", "server.bind_address", IpAddr::from([0, 0, 0, 0])),
+            port: loader.parse("PORT", "server.port", 3000),
+        };
+
+        let cors = CorsConfig {
+            allowed_origins: loader.list("CORS_ALLOWED_ORIGINS", "cors.allowed_origins", &[]),
+            public_origins: loader.list("CORS_PUBLIC_ORIGINS", "cors.public_origins", &["*"]),
+            allow_credentials: loader.parse("CORS_ALLOW_CREDENTIALS", "cors.allow_credentials", false),
+            max_age: loader.secs("CORS_MAX_AGE_SECS", "cors.max_age_secs", 10 * 60),
+        };
+        for (var, origins) in [
+            ("CORS_ALLOWED_ORIGINS / cors.allowed_origins", &cors.allowed_origins),
+            ("CORS_PUBLIC_ORIGINS / cors.public_origins", &cors.public_origins),
+        ] {
+            for origin in origins {
+                if origin != "*" && !is_origin(origin) {
+                    loader.problem(format!("{}: '{}' is not an origin like https://example.com", var, origin));
+                }
+            }
+        }
+        if cors.allow_credentials && cors.allowed_origins.iter().any(|origin| origin == "*") {
+            loader.problem("CORS_ALLOW_CREDENTIALS cannot be combined with '*' in CORS_ALLOWED_ORIGINS".to_string());
+        }
+
+        let database = DatabaseConfig {
+            url: loader.string("DATABASE_URL", "database.url").unwrap_or_else(|| {
+                loader.problem("DATABASE_URL / database.url is required".to_string());
+                String::new()
+            }),
+            max_connections: loader.parse("DATABASE_MAX_CONNECTIONS", "database.max_connections", 5),
+            min_connections: loader.parse("DATABASE_MIN_CONNECTIONS", "database.min_connections", 0),
+            acquire_timeout: loader.secs("DATABASE_ACQUIRE_TIMEOUT_SECS", "database.acquire_timeout_secs", 30),
+        };
+        if database.max_connections == 0 {
+            loader.problem("DATABASE_MAX_CONNECTIONS / database.max_connections must be at least 1".to_string());
+        }
+        if database.min_connections > database.max_connections {
+            loader.problem(format!(
+                "DATABASE_MIN_CONNECTIONS ({}) is larger than DATABASE_MAX_CONNECTIONS ({})",
+                database.min_connections, database.max_connections
+            ));
+        }
+
+        let openai_compat_base_url = loader
+            .string("OPENAI_COMPAT_BASE_URL", "upstreams.openai_compat_base_url")
+            .map(|url| loader.check_url("OPENAI_COMPAT_BASE_URL", url));
+        let upstreams = UpstreamConfig {
+            perplexity_base_url: loader.url("PERPLEXITY_BASE_URL", "upstreams.perplexity_base_url", DEFAULT_PERPLEXITY_BASE_URL),
+            perplexity_model: loader
+                .string("PERPLEXITY_MODEL", "upstreams.perplexity_model")
+                .unwrap_or_else(|| "sonar-pro".to_string()),
+            gemini_base_url: loader.url("GEMINI_BASE_URL", "upstreams.gemini_base_url", DEFAULT_GEMINI_BASE_URL),
+            gemini_model: loader
+                .string("GEMINI_MODEL", "upstreams.gemini_model")
+                .unwrap_or_else(|| "gemini-2.5-flash".to_string()),
+            opencage_base_url: loader.url("OPENCAGE_BASE_URL", "upstreams.opencage_base_url", DEFAULT_OPENCAGE_BASE_URL),
+            nominatim_base_url: loader.url("NOMINATIM_BASE_URL", "upstreams.nominatim_base_url", DEFAULT_NOMINATIM_BASE_URL),
+            google_maps_base_url: loader.url("GOOGLE_MAPS_BASE_URL", "upstreams.google_maps_base_url", DEFAULT_GOOGLE_MAPS_BASE_URL),
+            openai_compat_base_url,
+            openai_compat_model: loader
+                .string("OPENAI_COMPAT_MODEL", "upstreams.openai_compat_model")
+                .unwrap_or_else(|| "default".to_string()),
+        };
+
+        let keys = ApiKeys {
+            gemini: loader.key("GEMINI_API_KEY", "keys.gemini"),
+            opencage: loader.key("OPENCAGE_API_KEY", "keys.opencage"),
+            ai_service: loader.key("AI_SERVICE_API_KEY", "keys.ai_service"),
+            google_maps: loader.key("GOOGLE_MAPS_API_KEY", "keys.google_maps"),
+            openai_compat: loader.key("OPENAI_COMPAT_API_KEY", "keys.openai_compat"),
+            maps_token_secret: loader.string("MAPS_TOKEN_SECRET", "keys.maps_token_secret"),
+        };
+
+        let auth = AuthConfig {
+            jwt_secret: loader.string("SUPABASE_JWT_SECRET", "auth.jwt_secret"),
+            jwks_path: loader.string("SUPABASE_JWKS_PATH", "auth.jwks_path"),
+            audience: loader
+                .string("SUPABASE_JWT_AUDIENCE", "auth.audience")
+                .unwrap_or_else(|| "authenticated".to_string()),
+            admin_ids: loader
+                .list("ADMIN_USER_IDS", "auth.admin_user_ids", &[])
+                .iter()
+                .filter_map(|id| match Uuid::parse_str(id) {
+                    Ok(id) => Some(id),
+                    Err(_) => {
+                        loader.problem(format!("ADMIN_USER_IDS / auth.admin_user_ids: '{}' is not a user id", id));
+                        None
+                    }
+                })
+                .collect(),
+        };
+        if let Some(path) = &auth.jwks_path
+            && !Path::new(path).is_file()
+        {
+            loader.problem(format!("SUPABASE_JWKS_PATH / auth.jwks_path: {} does not exist", path));
+        }
+
+        let http = HttpConfig {
+            connect_timeout: loader.secs("HTTP_CONNECT_TIMEOUT_SECS", "http.connect_timeout_secs", 5),
+            ai_timeout: loader.secs("AI_TIMEOUT_SECS", "http.ai_timeout_secs", 60),
+            geocode_timeout: loader.secs("GEOCODE_TIMEOUT_SECS", "http.geocode_timeout_secs", 10),
+            maps_timeout: loader.secs("MAPS_TIMEOUT_SECS", "http.maps_timeout_secs", 10),
+            max_retries: loader.parse("HTTP_MAX_RETRIES", "http.max_retries", 2),
+            retry_budget: loader.secs("HTTP_RETRY_BUDGET_SECS", "http.retry_budget_secs", 10),
+            pool_max_idle_per_host: loader.parse("HTTP_POOL_MAX_IDLE_PER_HOST", "http.pool_max_idle_per_host", 16),
+        };
+
+        let circuit = CircuitConfig {
+            failure_threshold: loader.parse("CIRCUIT_FAILURE_THRESHOLD", "circuit.failure_threshold", 5),
+            open_for: loader.secs("CIRCUIT_OPEN_SECS", "circuit.open_secs", 30),
+            window: loader.secs("CIRCUIT_WINDOW_SECS", "circuit.window_secs", 300),
+        };
+        if circuit.failure_threshold == 0 {
+            loader.problem("CIRCUIT_FAILURE_THRESHOLD / circuit.failure_threshold must be at least 1".to_string());
+        }
+
+        let cache = CacheConfig {
+            geocode_ttl: loader.secs("CACHE_TTL_GEOCODE_SECS", "cache.ttl_geocode_secs", 7 * 24 * 60 * 60),
+            gemini_ttl: loader.secs("CACHE_TTL_GEMINI_SECS", "cache.ttl_gemini_secs", 24 * 60 * 60),
+            chat_ttl: loader.secs("CACHE_TTL_CHAT_SECS", "cache.ttl_chat_secs", 24 * 60 * 60),
+            analysis_ttl: loader.secs("CACHE_TTL_ANALYSIS_SECS", "cache.ttl_analysis_secs", 24 * 60 * 60),
+            memory_capacity: loader.parse("CACHE_MEMORY_CAPACITY", "cache.memory_capacity", NonZeroUsize::new(1000).unwrap()),
+            sweep_interval: loader.secs("CACHE_SWEEP_INTERVAL_SECS", "cache.sweep_interval_secs", 60 * 60),
+        };
+
+        let llm = LlmConfig {
+            route_analysis: loader.list("LLM_ROUTE_ANALYSIS", "llm.route_analysis", &["perplexity", "gemini"]),
+            route_chat: loader.list("LLM_ROUTE_CHAT", "llm.route_chat", &["perplexity", "gemini"]),
+            route_ocr: loader.list("LLM_ROUTE_OCR", "llm.route_ocr", &["gemini", "perplexity"]),
+        };
+        for (var, route) in [
+            ("LLM_ROUTE_ANALYSIS", &llm.route_analysis),
+            ("LLM_ROUTE_CHAT", &llm.route_chat),
+            ("LLM_ROUTE_OCR", &llm.route_ocr),
+        ] {
+            for name in route {
+                if !LLM_PROVIDERS.contains(&name.as_str()) {
+                    loader.problem(format!(
+                        "{}: unknown provider '{}'; use perplexity, gemini or openai",
+                        var, name
+                    ));
+                } else if name == "openai" && upstreams.openai_compat_base_url.is_none() {
+                    loader.problem(format!("{} lists openai but OPENAI_COMPAT_BASE_URL is not set", var));
+                }
+            }
+        }
+
+        let analysis = AnalysisConfig {
+            fallback_radius_km: loader.parse("FALLBACK_RADIUS_KM", "analysis.fallback_radius_km", 25.0),
+        };
+        if analysis.fallback_radius_km <= 0.0 {
+            loader.problem("FALLBACK_RADIUS_KM / analysis.fallback_radius_km must be positive".to_string());
+        }
+
+        let geocode = GeocodeConfig {
+            providers: loader.list("GEOCODE_PROVIDERS", "geocode.providers", &["opencage", "nominatim"]),
+            reverse_precision: loader.parse("GEOCODE_REVERSE_PRECISION", "geocode.reverse_precision", 4),
+            batch_max: loader.parse("GEOCODE_BATCH_MAX", "geocode.batch_max", 500),
+            batch_concurrency: loader.parse("GEOCODE_BATCH_CONCURRENCY", "geocode.batch_concurrency", 4),
+            batch_rate_per_sec: loader.parse("GEOCODE_BATCH_RATE_PER_SEC", "geocode.batch_rate_per_sec", 10),
+            nominatim_rate_per_sec: loader.parse("GEOCODE_NOMINATIM_RATE_PER_SEC", "geocode.nominatim_rate_per_sec", 1),
+            suggest_limit: loader.parse("GEOCODE_SUGGEST_LIMIT", "geocode.suggest_limit", 5),
+            suggest_min_chars: loader.parse("GEOCODE_SUGGEST_MIN_CHARS", "geocode.suggest_min_chars", 3),
+            suggest_max_chars: loader.parse("GEOCODE_SUGGEST_MAX_CHARS", "geocode.suggest_max_chars", 100),
+        };
+        if geocode.batch_max == 0
+            || geocode.batch_concurrency == 0
+            || geocode.batch_rate_per_sec == 0
+            || geocode.nominatim_rate_per_sec == 0
+        {
+            loader.problem(
+                "GEOCODE_BATCH_MAX, GEOCODE_BATCH_CONCURRENCY, GEOCODE_BATCH_RATE_PER_SEC and GEOCODE_NOMINATIM_RATE_PER_SEC must be at least 1".to_string(),
+            );
+        }
+        for name in &geocode.providers {
+            if !GEOCODE_PROVIDERS.contains(&name.as_str()) {
+                loader.problem(format!(
+                    "GEOCODE_PROVIDERS: unknown provider '{}'; use opencage or nominatim",
+                    name
+                ));
+            }
+        }
+        if geocode.suggest_limit == 0 || geocode.suggest_min_chars == 0 {
+            loader.problem("GEOCODE_SUGGEST_LIMIT and GEOCODE_SUGGEST_MIN_CHARS must be at least 1".to_string());
+        }
+        if geocode.suggest_max_chars < geocode.suggest_min_chars {
+            loader.problem("GEOCODE_SUGGEST_MAX_CHARS must be at least GEOCODE_SUGGEST_MIN_CHARS".to_string());
+        }
+        if geocode.providers.is_empty() {
+            loader.problem("GEOCODE_PROVIDERS must list at least one provider".to_string());
+        }
+        if geocode.reverse_precision > 7 {
+            loader.problem("GEOCODE_REVERSE_PRECISION / geocode.reverse_precision must be 0 to 7".to_string());
+        }
+
+        let maps = MapsConfig {
+            token_ttl: loader.secs("MAPS_TOKEN_TTL_SECS", "maps.token_ttl_secs", 5 * 60),
+            key_origins: loader.list("MAPS_KEY_ORIGINS", "maps.key_origins", &[]),
+        };
+        for origin in &maps.key_origins {
+            if !is_origin(origin) {
+                loader.problem(format!(
+                    "MAPS_KEY_ORIGINS / maps.key_origins: '{}' is not an origin like https://example.com",
+                    origin
+                ));
+            }
+        }
+        if let Some(secret) = &keys.maps_token_secret
+            && secret.len() < 32
+        {
+            loader.problem("MAPS_TOKEN_SECRET / keys.maps_token_secret must be at least 32 characters".to_string());
+        }
+
+        let limits = LimitsConfig {
+            burst: loader.parse("RATE_LIMIT_BURST", "limits.burst", 10),
+            per_minute: loader.parse("RATE_LIMIT_PER_MINUTE", "limits.per_minute", 20),
+            daily_requests: loader.parse("QUOTA_DAILY_REQUESTS", "limits.daily_requests", 500),
+            daily_tokens: loader.parse("QUOTA_DAILY_TOKENS", "limits.daily_tokens", 500_000),
+            trust_forwarded_for: loader.parse("TRUST_FORWARDED_FOR", "limits.trust_forwarded_for", false),
+        };
+        if limits.burst == 0 || limits.per_minute == 0 {
+            loader.problem("RATE_LIMIT_BURST and RATE_LIMIT_PER_MINUTE must be at least 1".to_string());
+        }
+        if limits.daily_requests < 0 || limits.daily_tokens < 0 {
+            loader.problem("QUOTA_DAILY_REQUESTS and QUOTA_DAILY_TOKENS cannot be negative".to_string());
+        }
+
+        loader.finish()?;
+
+        let config = Self {
+            server,
+            cors,
+            database,
+            upstreams,
+            keys,
+            auth,
+            http,
+            circuit,
+            cache,
+            llm,
+            analysis,
+            geocode,
+            maps,
+            limits,
+        };
+
+        let missing = config.keys.missing_required();
+        if !missing.is_empty() {
+            tracing::warn!(
+                "Missing API keys: {}. Routes that need them will fail until they are set.",
+                missing.join(", ")
+            );
+        }
+        if config.auth.jwt_secret.is_none() && config.auth.jwks_path.is_none() {
+            tracing::warn!(
+                "Neither SUPABASE_JWT_SECRET nor SUPABASE_JWKS_PATH is set; authenticated routes will reject every request"
+            );
+        }
+        if config.cors.allowed_origins.is_empty() {
+            tracing::warn!("CORS_ALLOWED_ORIGINS is empty; browsers on other origins cannot use the API");
+        }
+        Ok(config)
+    }
+}
+
+/// A value found for a setting, and where it came from.
+enum Raw {
+    Env(String),
+    File(toml::Value),
+}
+
+struct Loader {
+    /// The environment, read once; empty for `Config::from_toml`, so only
+    /// the file counts
+    env: HashMap<String, String>,
+    file: toml::Table,
+    file_name: Option<String>,
+    /// File paths that some setting asked for, to spot unknown keys
+    known: HashSet<&'static str>,
+    problems: Vec<String>,
+}
+
+impl Loader {
+    fn new() -> Self {
+        let mut loader = Self {
+            env: env::vars_os()
+                .filter_map(|(var, value)| Some((var.into_string().ok()?, value.into_string().ok()?)))
+                .collect(),
+            file: toml::Table::new(),
+            file_name: None,
+            known: HashSet::new(),
+            problems: Vec::new(),
+        };
+
+        let (path, required) = match loader.env("CONFIG_FILE") {
+            Some(path) if !path.is_empty() => (path, true),
+            _ => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        if !required && !Path::new(&path).exists() {
+            return loader;
+        }
+
+        match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|raw| {
+            raw.parse::<toml::Table>().map_err(|e| e.to_string())
+        }) {
+            Ok(table) => {
+                tracing::info!("Loading configuration from {}", path);
+                loader.file = table;
+                loader.file_name = Some(path);
+            }
+            Err(e) => loader.problem(format!("Config file {}: {}", path, e)),
+        }
+        loader
+    }
+
+    fn from_toml(text: &str) -> Self {
+        let mut loader = Self {
+            env: HashMap::new(),
+            file: toml::Table::new(),
+            file_name: Some("inline config".to_string()),
+            known: HashSet::new(),
+            problems: Vec::new(),
+        };
+        match text.parse::<toml::Table>() {
+            Ok(table) => loader.file = table,
+            Err(e) => loader.problem(format!("Config: {}", e)),
+        }
+        loader
+    }
+
+    fn env(&self, var: &str) -> Option<String> {
+        self.env.get(var).cloned()
+    }
+
+    fn problem(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+
+    /// The environment variable if set and non-empty, else the file value.
+    fn raw(&mut self, var: &str, path: &'static str) -> Option<Raw> {
+        self.known.insert(path);
+        if let Some(value) = self.env(var)
+            && !value.trim().is_empty()
+        {
+            return Some(Raw::Env(value.trim().to_string()));
+        }
+        let (section, key) = path.split_once('.')?;
+        self.file.get(section)?.get(key).cloned().map(Raw::File)
+    }
+
+    fn describe(&self, var: &str, path: &str, raw: &Raw) -> String {
+        match raw {
+            Raw::Env(_) => var.to_string(),
+            Raw::File(_) => format!("{} in {}", path, self.file_name.as_deref().unwrap_or("config file")),
+        }
+    }
+
+    fn string(&mut self, var: &str, path: &'static str) -> Option<String> {
+        match self.raw(var, path)? {
+            Raw::Env(value) => Some(value),
+            Raw::File(toml::Value::String(value)) if !value.trim().is_empty() => Some(value.trim().to_string()),
+            Raw::File(toml::Value::String(_)) => None,
+            raw => {
+                let source = self.describe(var, path, &raw);
+                self.problem(format!("{}: expected a string", source));
+                None
+            }
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, var: &str, path: &'static str, default: T) -> T {
+        let Some(raw) = self.raw(var, path) else {
+            return default;
+        };
+        let text = match &raw {
+            Raw::Env(value) => value.clone(),
+            Raw::File(toml::Value::String(value)) => value.clone(),
+            Raw::File(value) => value.to_string(),
+        };
+        match text.parse::<T>() {
+            Ok(value) => value,
+            Err(_) => {
+                let source = self.describe(var, path, &raw);
+                self.problem(format!("{}: invalid value '{}'", source, text));
+                default
+            }
+        }
+    }
+
+    /// A positive number of seconds.
+    fn secs(&mut self, var: &str, path: &'static str, default: u64) -> Duration {
+        let secs = self.parse(var, path, default);
+        if secs == 0 {
+            self.problem(format!("{} / {} must be at least 1 second", var, path));
+            return Duration::from_secs(default);
+        }
+        Duration::from_secs(secs)
+    }
+
+    /// A comma-separated variable or a TOML array of strings.
+    fn list(&mut self, var: &str, path: &'static str, default: &[&str]) -> Vec<String> {
+        let items: Vec<String> = match self.raw(var, path) {
+            None => return default.iter().map(|item| item.to_string()).collect(),
+            Some(Raw::Env(value)) | Some(Raw::File(toml::Value::String(value))) => {
+                value.split(',').map(str::to_string).collect()
+            }
+            Some(Raw::File(toml::Value::Array(values))) => {
+                let mut items = Vec::new();
+                for value in values {
+                    match value {
+                        toml::Value::String(item) => items.push(item),
+                        other => self.problem(format!("{}: expected strings, found {}", path, other)),
+                    }
+                }
+                items
+            }
+            Some(raw) => {
+                let source = self.describe(var, path, &raw);
+                self.problem(format!("{}: expected a list of strings", source));
+                return default.iter().map(|item| item.to_string()).collect();
+            }
+        };
+        items
+            .iter()
+            .map(|item| item.trim().to_lowercase())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
+    /// An API key, kept even when empty.
+    fn key(&mut self, var: &str, path: &'static str) -> Option<String> {
+        self.known.insert(path);
+        if let Some(value) = self.env(var) {
+            return Some(value);
+        }
+        let (section, key) = path.split_once('.')?;
+        match self.file.get(section)?.get(key)? {
+            toml::Value::String(value) => Some(value.clone()),
+            _ => {
+                self.problem(format!("{}: expected a string", path));
+                None
+            }
+        }
+    }
+
+    fn url(&mut self, var: &str, path: &'static str, default: &str) -> String {
+        match self.string(var, path) {
+            Some(url) => self.check_url(var, url),
+            None => default.to_string(),
+        }
+    }
+
+    /// Checks an http(s) URL and drops trailing slashes.
+    fn check_url(&mut self, var: &str, url: String) -> String {
+        match Url::parse(&url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            Ok(_) => self.problem(format!("{}: '{}' must be an http or https URL", var, url)),
+            Err(e) => self.problem(format!("{}: '{}' is not a valid URL ({})", var, url, e)),
+        }
+        url.trim_end_matches('/').to_string()
+    }
+
+    fn finish(mut self) -> Result<(), ConfigError> {
+        let mut unknown = Vec::new();
+        for (section, value) in &self.file {
+            match value.as_table() {
+                Some(table) => {
+                    for key in table.keys() {
+                        let path = format!("{}.{}", section, key);
+                        if !self.known.contains(path.as_str()) {
+                            unknown.push(path);
+                        }
+                    }
+                }
+                None => unknown.push(section.clone()),
+            }
+        }
+        for path in unknown {
+            self.problem(format!("Unknown setting '{}' in config file", path));
+        }
+
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems: self.problems })
+        }
+    }
+}
+
+/// `scheme://host[:port]` with nothing after it.
+fn is_origin(origin: &str) -> bool {
+    match Url::parse(origin) {
+        Ok(url) => {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some()
+                && url.path() == "/"
+                && !origin.ends_with('/')
+                && url.query().is_none()
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = "[database]\nurl = \"postgres://localhost/terratruce\"\n";
+
+    /// Loads `toml` with `vars` standing in for the environment.
+    fn load(vars: &[(&str, &str)], toml: &str) -> Result<Config, ConfigError> {
+        let mut loader = Loader::from_toml(toml);
+        loader.env = vars.iter().map(|(var, value)| (var.to_string(), value.to_string())).collect();
+        Config::build(loader)
+    }
+
+    fn problems(vars: &[(&str, &str)], toml: &str) -> Vec<String> {
+        load(vars, toml).expect_err("config should be rejected").problems
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() {
+        let toml = format!("{}[server]\nport = 4000\n", DATABASE);
+
+        assert_eq!(load(&[], DATABASE).unwrap().server.port, 3000);
+        assert_eq!(load(&[], &toml).unwrap().server.port, 4000);
+        assert_eq!(load(&[("PORT", "5000")], &toml).unwrap().server.port, 5000);
+        // A blank variable does not hide the file value
+        assert_eq!(load(&[("PORT", " ")], &toml).unwrap().server.port, 4000);
+    }
+
+    #[test]
+    fn reads_lists_from_either_source() {
+        let toml = format!("{}[geocode]\nproviders = [\"nominatim\"]\n", DATABASE);
+        assert_eq!(load(&[], &toml).unwrap().geocode.providers, ["nominatim"]);
+
+        let vars = [("GEOCODE_PROVIDERS", " Nominatim, opencage ,")];
+        assert_eq!(load(&vars, &toml).unwrap().geocode.providers, ["nominatim", "opencage"]);
+    }
+
+    #[test]
+    fn reads_auth_settings() {
+        let admin = "6f1f7c4e-3c2a-4a3e-9d1b-2f8e5a7c9b10";
+        let vars = [("SUPABASE_JWT_SECRET", "secret"), ("ADMIN_USER_IDS", admin)];
+        let toml = format!("{}[auth]\naudience = \"staff\"\n", DATABASE);
+
+        let auth = load(&vars, &toml).unwrap().auth;
+        assert_eq!(auth.jwt_secret.as_deref(), Some("secret"));
+        assert_eq!(auth.audience, "staff");
+        assert_eq!(auth.admin_ids, [Uuid::parse_str(admin).unwrap()]);
+        assert!(!format!("{:?}", auth).contains("\"secret\""));
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let vars = [
+            ("PORT", "eighty"),
+            ("RATE_LIMIT_BURST", "0"),
+            ("ADMIN_USER_IDS", "not-a-uuid"),
+            ("SUPABASE_JWKS_PATH", "/nonexistent/jwks.json"),
+        ];
+        let toml = "[cors]\nallowed_origins = [\"https://example.com/app\"]\n[server]\ncolour = \"blue\"\n";
+
+        let problems = problems(&vars, toml);
+        let expected = [
+            "DATABASE_URL / database.url is required",
+            "PORT: invalid value 'eighty'",
+            "RATE_LIMIT_BURST and RATE_LIMIT_PER_MINUTE must be at least 1",
+            "ADMIN_USER_IDS / auth.admin_user_ids: 'not-a-uuid' is not a user id",
+            "SUPABASE_JWKS_PATH / auth.jwks_path: /nonexistent/jwks.json does not exist",
+            "CORS_ALLOWED_ORIGINS / cors.allowed_origins: 'https://example.com/app' is not an origin like https://example.com",
+            "Unknown setting 'server.colour' in config file",
+        ];
+        for problem in expected {
+            assert!(problems.iter().any(|p| p == problem), "missing '{}' in {:#?}", problem, problems);
+        }
+        assert_eq!(problems.len(), expected.len(), "{:#?}", problems);
+    }
+
+    #[test]
+    fn names_the_file_setting_that_is_wrong() {
+        let toml = format!("{}[limits]\nper_minute = \"often\"\n", DATABASE);
+
+        assert_eq!(problems(&[], &toml), ["limits.per_minute in inline config: invalid value 'often'"]);
+    }
+
+    #[test]
+    fn rejects_settings_that_contradict_each_other() {
+        let vars = [
+            ("CORS_ALLOWED_ORIGINS", "*"),
+            ("CORS_ALLOW_CREDENTIALS", "true"),
+            ("DATABASE_MIN_CONNECTIONS", "10"),
+            ("LLM_ROUTE_CHAT", "openai"),
+            ("GEOCODE_SUGGEST_MIN_CHARS", "5"),
+            ("GEOCODE_SUGGEST_MAX_CHARS", "4"),
+        ];
+
+        let problems = problems(&vars, DATABASE);
+        assert_eq!(problems.len(), 4, "{:#?}", problems);
+        assert!(problems.contains(&"CORS_ALLOW_CREDENTIALS cannot be combined with '*' in CORS_ALLOWED_ORIGINS".to_string()));
+        assert!(problems.contains(&"LLM_ROUTE_CHAT lists openai but OPENAI_COMPAT_BASE_URL is not set".to_string()));
+    }
+}
@@ -0,0 +1,447 @@
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::services::upstream::UpstreamError;
+
+// Every error the API can return, with its HTTP status and a stable code.
+// Handlers return an `ApiError` (or one built up with `with_message`) and
+// the body always has the same shape:
+// `{error, message, code, status, request_id}` plus any extra details.
+// `GET /api/errors` serves this catalog so clients can localize by code.
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Largest non-JSON error body read back when normalizing responses
+const MAX_NORMALIZED_BODY: usize = 64 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+macro_rules! catalog {
+    ($(
+        $group:literal {
+            $( $variant:ident => ($code:literal, $status:ident, $title:literal, $description:literal), )*
+        }
+    )*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum ApiError {
+            $( $( $variant, )* )*
+        }
+
+        impl ApiError {
+            pub const ALL: &'static [ApiError] = &[$( $( ApiError::$variant, )* )*];
+
+            pub fn code(self) -> &'static str {
+                match self {
+                    $( $( ApiError::$variant => $code, )* )*
+                }
+            }
+
+            /// The status this error is returned with unless the call site
+            /// overrides it (upstream statuses are passed through).
+            pub fn status(self) -> StatusCode {
+                match self {
+                    $( $( ApiError::$variant => StatusCode::$status, )* )*
+                }
+            }
+
+            /// Short summary, sent as the `error` field.
+            pub fn title(self) -> &'static str {
+                match self {
+                    $( $( ApiError::$variant => $title, )* )*
+                }
+            }
+
+            /// Default `message` when the call site has nothing more specific.
+            pub fn description(self) -> &'static str {
+                match self {
+                    $( $( ApiError::$variant => $description, )* )*
+                }
+            }
+
+            pub fn group(self) -> &'static str {
+                match self {
+                    $( $( ApiError::$variant => $group, )* )*
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    "request" {
+        InvalidRequestBody => ("INVALID_REQUEST_BODY", BAD_REQUEST, "Invalid request body", "The request body could not be read as the expected JSON"),
+        RouteNotFound => ("ROUTE_NOT_FOUND", NOT_FOUND, "Route not found", "No endpoint matches this path"),
+        MethodNotAllowed => ("METHOD_NOT_ALLOWED", METHOD_NOT_ALLOWED, "Method not allowed", "This endpoint does not accept this HTTP method"),
+        InternalError => ("INTERNAL_ERROR", INTERNAL_SERVER_ERROR, "Internal server error", "The server failed to handle the request"),
+        CorsOriginNotAllowed => ("CORS_ORIGIN_NOT_ALLOWED", FORBIDDEN, "Origin not allowed", "Browser requests from this origin are not allowed. Add it to CORS_ALLOWED_ORIGINS"),
+    }
+    "auth" {
+        AuthMissingToken => ("AUTH_MISSING_TOKEN", UNAUTHORIZED, "Authentication required", "Send a Supabase access token in the 'Authorization: Bearer <token>' header"),
+        AuthInvalidToken => ("AUTH_INVALID_TOKEN", UNAUTHORIZED, "Invalid access token", "The access token could not be verified"),
+        AuthTokenExpired => ("AUTH_TOKEN_EXPIRED", UNAUTHORIZED, "Access token expired", "The access token has expired. Refresh the session and try again"),
+        AuthForbidden => ("AUTH_FORBIDDEN", FORBIDDEN, "Admin access required", "This endpoint is only available to administrators"),
+        AuthNotConfigured => ("AUTH_NOT_CONFIGURED", INTERNAL_SERVER_ERROR, "Authentication is not configured", "Set SUPABASE_JWT_SECRET or SUPABASE_JWKS_PATH in the backend .env file."),
+    }
+    "search" {
+        InvalidSort => ("INVALID_SORT", BAD_REQUEST, "Invalid sort order", "The 'sort' parameter must be one of: newest, oldest, risk_desc, risk_asc"),
+        InvalidCursor => ("INVALID_CURSOR", BAD_REQUEST, "Invalid cursor", "The 'cursor' parameter is malformed or was issued for a different sort order"),
+        InvalidRange => ("INVALID_RANGE", BAD_REQUEST, "Invalid range", "'min_risk' cannot be greater than 'max_risk', and 'from' cannot be later than 'to'"),
+        SearchNotFound => ("SEARCH_NOT_FOUND", NOT_FOUND, "Search not found", "No search history entry with that id"),
+        SearchForbidden => ("SEARCH_FORBIDDEN", FORBIDDEN, "Access denied", "This search history entry belongs to another user"),
+        EmptyUpdate => ("EMPTY_UPDATE", BAD_REQUEST, "Invalid request", "Provide at least one of: location_name, city, state, notes, search_data"),
+        InvalidLocationName => ("INVALID_LOCATION_NAME", BAD_REQUEST, "Invalid request", "'location_name' cannot be empty"),
+        InvalidSearchData => ("INVALID_SEARCH_DATA", BAD_REQUEST, "Invalid request", "'search_data' must be a JSON object"),
+        SearchDbError => ("SEARCH_DB_ERROR", INTERNAL_SERVER_ERROR, "Search history database error", "Failed to query the search_history table"),
+    }
+    "maps" {
+        MapsKeyMissing => ("MAPS_KEY_MISSING", INTERNAL_SERVER_ERROR, "Google Maps API key is missing", "The GOOGLE_MAPS_API_KEY environment variable is not set. Please add it to the backend .env file."),
+        MapsKeyEmpty => ("MAPS_KEY_EMPTY", INTERNAL_SERVER_ERROR, "Google Maps API key is not configured", "The GOOGLE_MAPS_API_KEY environment variable is empty. Please add a valid API key to the backend .env file."),
+        MapsOriginRequired => ("MAPS_ORIGIN_REQUIRED", BAD_REQUEST, "Request origin is required", "Map access is tied to the page's origin; send the request from a browser page (Origin or Referer header)"),
+        MapsOriginNotAllowed => ("MAPS_ORIGIN_NOT_ALLOWED", FORBIDDEN, "Origin not allowed", "This origin may not use the map endpoints"),
+        MapsTokenMissing => ("MAPS_TOKEN_MISSING", UNAUTHORIZED, "Map token required", "Send a token from POST /api/maps/token in the 'X-Maps-Token' header or the 'token' query parameter"),
+        MapsTokenInvalid => ("MAPS_TOKEN_INVALID", UNAUTHORIZED, "Invalid map token", "The map token could not be verified"),
+        MapsTokenExpired => ("MAPS_TOKEN_EXPIRED", UNAUTHORIZED, "Map token expired", "The map token has expired. Request a new one from POST /api/maps/token"),
+        MapsTokenOriginMismatch => ("MAPS_TOKEN_ORIGIN_MISMATCH", FORBIDDEN, "Map token used from another origin", "The map token was issued to a different origin"),
+        MapsInvalidParams => ("MAPS_INVALID_PARAMS", BAD_REQUEST, "Invalid request", "The map request parameters are invalid"),
+        MapsRequestDenied => ("MAPS_REQUEST_DENIED", FORBIDDEN, "Google Maps request denied", "Google Maps refused the request; check the key and the APIs enabled for it"),
+        MapsRateLimit => ("MAPS_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many map requests. Please try again later", "Google Maps is rate limiting requests"),
+        MapsTimeout => ("MAPS_TIMEOUT", SERVICE_UNAVAILABLE, "Google Maps request timed out", "Google Maps did not answer within MAPS_TIMEOUT_SECS"),
+        MapsConnectionError => ("MAPS_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to Google Maps", "No connection to Google Maps could be opened"),
+        MapsServiceError => ("MAPS_SERVICE_ERROR", SERVICE_UNAVAILABLE, "Google Maps unavailable", "The request to Google Maps failed"),
+        MapsParseError => ("MAPS_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse Google Maps response", "Google Maps returned an invalid response format"),
+        MapsError => ("MAPS_ERROR", BAD_GATEWAY, "Google Maps error", "Google Maps returned an unexpected error"),
+        MapsCircuitOpen => ("MAPS_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "Google Maps is temporarily disabled after repeated failures", "Google Maps was not called because its circuit is open; see retry_after"),
+    }
+    "geocode" {
+        InvalidQuery => ("INVALID_QUERY", BAD_REQUEST, "Invalid request", "The 'q' parameter (location query) cannot be empty"),
+        InvalidCoordinates => ("INVALID_COORDINATES", BAD_REQUEST, "Invalid coordinates", "'lat' must be a number from -90 to 90 and 'lng' a number from -180 to 180"),
+        GeocodeNoProvider => ("GEOCODE_NO_PROVIDER", INTERNAL_SERVER_ERROR, "No geocoding provider configured", "GEOCODE_PROVIDERS lists no usable provider"),
+        GeocodeBatchEmpty => ("GEOCODE_BATCH_EMPTY", BAD_REQUEST, "Invalid request", "The 'queries' array cannot be empty"),
+        GeocodeBatchTooLarge => ("GEOCODE_BATCH_TOO_LARGE", PAYLOAD_TOO_LARGE, "Too many queries", "A batch may hold at most GEOCODE_BATCH_MAX queries; split it up"),
+        NoResults => ("NO_RESULTS", NOT_FOUND, "No address found", "Nothing addressable at these coordinates, e.g. open water"),
+        GeocodeKeyMissing => ("GEOCODE_KEY_MISSING", INTERNAL_SERVER_ERROR, "Geocoding API key is missing", "The OPENCAGE_API_KEY environment variable is not set. Please add it to the backend .env file."),
+        GeocodeKeyEmpty => ("GEOCODE_KEY_EMPTY", INTERNAL_SERVER_ERROR, "Geocoding API key is not configured", "The OPENCAGE_API_KEY environment variable is empty. Please add a valid API key to the backend .env file."),
+        GeocodeUnauthorized => ("GEOCODE_UNAUTHORIZED", UNAUTHORIZED, "Invalid or expired OpenCage API key", "OpenCage rejected the API key"),
+        GeocodeQuotaExceeded => ("GEOCODE_QUOTA_EXCEEDED", PAYMENT_REQUIRED, "OpenCage API quota exceeded", "The OpenCage account has used up its quota"),
+        GeocodeForbidden => ("GEOCODE_FORBIDDEN", FORBIDDEN, "OpenCage API access forbidden", "OpenCage refused access for this API key"),
+        GeocodeRateLimit => ("GEOCODE_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many geocoding requests. Please try again later", "OpenCage is rate limiting requests"),
+        GeocodeTimeout => ("GEOCODE_TIMEOUT", SERVICE_UNAVAILABLE, "Geocoding request timed out", "OpenCage did not answer within GEOCODE_TIMEOUT_SECS"),
+        GeocodeConnectionError => ("GEOCODE_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to geocoding service", "No connection to OpenCage could be opened"),
+        GeocodeServiceError => ("GEOCODE_SERVICE_ERROR", SERVICE_UNAVAILABLE, "Geocoding service unavailable", "The request to OpenCage failed"),
+        GeocodeParseError => ("GEOCODE_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse geocoding response", "The geocoding service returned an invalid response format"),
+        GeocodeError => ("GEOCODE_ERROR", BAD_GATEWAY, "Geocoding service error", "OpenCage returned an unexpected error"),
+        GeocodeCircuitOpen => ("GEOCODE_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "Geocoding service is temporarily disabled after repeated failures", "OpenCage was not called because its circuit is open; see retry_after"),
+    }
+    "nominatim" {
+        NominatimForbidden => ("NOMINATIM_FORBIDDEN", FORBIDDEN, "Nominatim access forbidden", "Nominatim refused the request; public servers block clients that break their usage policy"),
+        NominatimRateLimit => ("NOMINATIM_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many geocoding requests. Please try again later", "Nominatim is rate limiting requests"),
+        NominatimTimeout => ("NOMINATIM_TIMEOUT", SERVICE_UNAVAILABLE, "Geocoding request timed out", "Nominatim did not answer within GEOCODE_TIMEOUT_SECS"),
+        NominatimConnectionError => ("NOMINATIM_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to geocoding service", "No connection to Nominatim could be opened"),
+        NominatimServiceError => ("NOMINATIM_SERVICE_ERROR", SERVICE_UNAVAILABLE, "Geocoding service unavailable", "The request to Nominatim failed"),
+        NominatimParseError => ("NOMINATIM_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse geocoding response", "Nominatim returned an invalid response format"),
+        NominatimError => ("NOMINATIM_ERROR", BAD_GATEWAY, "Geocoding service error", "Nominatim returned an unexpected error"),
+        NominatimCircuitOpen => ("NOMINATIM_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "Geocoding service is temporarily disabled after repeated failures", "Nominatim was not called because its circuit is open; see retry_after"),
+    }
+    "gemini" {
+        GeminiEmptyContents => ("GEMINI_EMPTY_CONTENTS", BAD_REQUEST, "Invalid request", "The 'contents' array cannot be empty"),
+        GeminiKeyMissing => ("GEMINI_KEY_MISSING", INTERNAL_SERVER_ERROR, "Gemini API key is missing", "The GEMINI_API_KEY environment variable is not set. Please add it to the backend .env file."),
+        GeminiKeyEmpty => ("GEMINI_KEY_EMPTY", INTERNAL_SERVER_ERROR, "Gemini API key is not configured", "The GEMINI_API_KEY environment variable is empty. Please add a valid API key to the backend .env file."),
+        GeminiBadRequest => ("GEMINI_BAD_REQUEST", BAD_REQUEST, "Invalid Gemini API request", "Gemini rejected the request"),
+        GeminiUnauthorized => ("GEMINI_UNAUTHORIZED", UNAUTHORIZED, "Invalid or expired Gemini API key", "Gemini rejected the API key"),
+        GeminiForbidden => ("GEMINI_FORBIDDEN", FORBIDDEN, "Gemini API access forbidden", "Gemini refused access for this API key"),
+        GeminiRateLimit => ("GEMINI_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many AI requests. Please try again later", "Gemini is rate limiting requests"),
+        GeminiServerError => ("GEMINI_SERVER_ERROR", INTERNAL_SERVER_ERROR, "Gemini API internal error", "Gemini failed to handle the request"),
+        GeminiUnavailable => ("GEMINI_UNAVAILABLE", SERVICE_UNAVAILABLE, "Gemini API temporarily unavailable", "Gemini is overloaded or down"),
+        GeminiError => ("GEMINI_ERROR", BAD_GATEWAY, "Gemini API error", "Gemini returned an unexpected error"),
+        GeminiTimeout => ("GEMINI_TIMEOUT", SERVICE_UNAVAILABLE, "Gemini API request timed out", "Gemini did not answer within AI_TIMEOUT_SECS"),
+        GeminiConnectionError => ("GEMINI_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to Gemini API", "No connection to Gemini could be opened"),
+        GeminiServiceError => ("GEMINI_SERVICE_ERROR", SERVICE_UNAVAILABLE, "Gemini API unavailable", "The request to Gemini failed"),
+        GeminiParseError => ("GEMINI_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse Gemini API response", "The Gemini API returned an invalid response format"),
+        GeminiCircuitOpen => ("GEMINI_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "Gemini API is temporarily disabled after repeated failures", "Gemini was not called because its circuit is open; see retry_after"),
+    }
+    "ai" {
+        AiEmptyMessages => ("AI_EMPTY_MESSAGES", BAD_REQUEST, "Invalid request", "The 'messages' array cannot be empty"),
+        AiKeyMissing => ("AI_KEY_MISSING", INTERNAL_SERVER_ERROR, "AI service API key is missing", "The AI_SERVICE_API_KEY environment variable is not set. Please add it to the backend .env file."),
+        AiKeyEmpty => ("AI_KEY_EMPTY", INTERNAL_SERVER_ERROR, "AI service API key is not configured", "The AI_SERVICE_API_KEY environment variable is empty. Please add a valid API key to the backend .env file."),
+        AiBadRequest => ("AI_BAD_REQUEST", BAD_REQUEST, "Invalid AI service request", "Perplexity rejected the request"),
+        AiUnauthorized => ("AI_UNAUTHORIZED", UNAUTHORIZED, "Invalid or expired AI service API key", "Perplexity rejected the API key"),
+        AiForbidden => ("AI_FORBIDDEN", FORBIDDEN, "AI service access forbidden", "Perplexity refused access for this API key"),
+        AiRateLimit => ("AI_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many AI requests. Please try again later", "Perplexity is rate limiting requests"),
+        AiServerError => ("AI_SERVER_ERROR", INTERNAL_SERVER_ERROR, "AI service internal error", "Perplexity failed to handle the request"),
+        AiUnavailable => ("AI_UNAVAILABLE", SERVICE_UNAVAILABLE, "AI service temporarily unavailable", "Perplexity is overloaded or down"),
+        AiError => ("AI_ERROR", BAD_GATEWAY, "AI service error", "Perplexity returned an unexpected error"),
+        AiTimeout => ("AI_TIMEOUT", SERVICE_UNAVAILABLE, "AI service request timed out", "Perplexity did not answer within AI_TIMEOUT_SECS"),
+        AiConnectionError => ("AI_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to AI service", "No connection to Perplexity could be opened"),
+        AiServiceError => ("AI_SERVICE_ERROR", SERVICE_UNAVAILABLE, "AI service unavailable", "The request to Perplexity failed"),
+        AiParseError => ("AI_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse AI service response", "The AI service returned an invalid response format"),
+        AiCircuitOpen => ("AI_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "AI service is temporarily disabled after repeated failures", "Perplexity was not called because its circuit is open; see retry_after"),
+    }
+    "llm" {
+        LlmNoProvider => ("LLM_NO_PROVIDER", INTERNAL_SERVER_ERROR, "No AI provider configured", "The route for this use case names no usable provider. Use perplexity, gemini or openai."),
+        LlmKeyMissing => ("LLM_KEY_MISSING", INTERNAL_SERVER_ERROR, "OpenAI-compatible service API key is missing", "The OPENAI_COMPAT_API_KEY environment variable is not set. Please add it to the backend .env file."),
+        LlmKeyEmpty => ("LLM_KEY_EMPTY", INTERNAL_SERVER_ERROR, "OpenAI-compatible service API key is not configured", "The OPENAI_COMPAT_API_KEY environment variable is empty. Please add a valid API key to the backend .env file."),
+        LlmBadRequest => ("LLM_BAD_REQUEST", BAD_REQUEST, "Invalid OpenAI-compatible service request", "The OpenAI-compatible server rejected the request"),
+        LlmUnauthorized => ("LLM_UNAUTHORIZED", UNAUTHORIZED, "Invalid or expired OpenAI-compatible service API key", "The OpenAI-compatible server rejected the API key"),
+        LlmForbidden => ("LLM_FORBIDDEN", FORBIDDEN, "OpenAI-compatible service access forbidden", "The OpenAI-compatible server refused access"),
+        LlmRateLimit => ("LLM_RATE_LIMIT", TOO_MANY_REQUESTS, "Too many AI requests. Please try again later", "The OpenAI-compatible server is rate limiting requests"),
+        LlmServerError => ("LLM_SERVER_ERROR", INTERNAL_SERVER_ERROR, "OpenAI-compatible service internal error", "The OpenAI-compatible server failed to handle the request"),
+        LlmUnavailable => ("LLM_UNAVAILABLE", SERVICE_UNAVAILABLE, "OpenAI-compatible service temporarily unavailable", "The OpenAI-compatible server is overloaded or down"),
+        LlmError => ("LLM_ERROR", BAD_GATEWAY, "OpenAI-compatible service error", "The OpenAI-compatible server returned an unexpected error"),
+        LlmTimeout => ("LLM_TIMEOUT", SERVICE_UNAVAILABLE, "OpenAI-compatible service request timed out", "The OpenAI-compatible server did not answer within AI_TIMEOUT_SECS"),
+        LlmConnectionError => ("LLM_CONNECTION_ERROR", SERVICE_UNAVAILABLE, "Cannot connect to OpenAI-compatible service", "No connection to the OpenAI-compatible server could be opened"),
+        LlmServiceError => ("LLM_SERVICE_ERROR", SERVICE_UNAVAILABLE, "OpenAI-compatible service unavailable", "The request to the OpenAI-compatible server failed"),
+        LlmParseError => ("LLM_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse OpenAI-compatible service response", "The OpenAI-compatible service returned an invalid response format"),
+        LlmCircuitOpen => ("LLM_CIRCUIT_OPEN", SERVICE_UNAVAILABLE, "OpenAI-compatible service is temporarily disabled after repeated failures", "The OpenAI-compatible server was not called because its circuit is open; see retry_after"),
+    }
+    "ocr" {
+        OcrEmptyText => ("OCR_EMPTY_TEXT", BAD_REQUEST, "Invalid request", "The 'text' field cannot be empty"),
+    }
+    "analysis" {
+        AnalyzeEmptyLocation => ("ANALYZE_EMPTY_LOCATION", BAD_REQUEST, "Invalid request", "The 'location' field cannot be empty"),
+        AnalysisParseError => ("ANALYSIS_PARSE_ERROR", INTERNAL_SERVER_ERROR, "Failed to parse analysis report", "The AI reply contained no usable report"),
+        AnalysisValidationError => ("ANALYSIS_VALIDATION_ERROR", INTERNAL_SERVER_ERROR, "Invalid analysis report", "The AI service returned a report that failed validation twice"),
+    }
+    "cache" {
+        CachePurgeFilterRequired => ("CACHE_PURGE_FILTER_REQUIRED", BAD_REQUEST, "Invalid request", "Provide a 'type' and/or 'prefix' to purge; refusing to clear the whole cache"),
+        CacheKeyNotFound => ("CACHE_KEY_NOT_FOUND", NOT_FOUND, "Cache entry not found", "No cache entry with that key"),
+        CacheNotRefreshable => ("CACHE_NOT_REFRESHABLE", UNPROCESSABLE_ENTITY, "Cache entry cannot be refreshed", "The entry was not written by the backend or has no recorded request. Purge it instead."),
+        CacheDbError => ("CACHE_DB_ERROR", INTERNAL_SERVER_ERROR, "Cache database error", "Failed to query the cache_entries table"),
+    }
+    "limits" {
+        RateLimited => ("RATE_LIMITED", TOO_MANY_REQUESTS, "Too many requests", "Slow down; see Retry-After for when the next request is allowed"),
+        QuotaRequestsExceeded => ("QUOTA_REQUESTS_EXCEEDED", TOO_MANY_REQUESTS, "Daily request quota exceeded", "The daily request quota is used up; it resets at midnight UTC"),
+        QuotaTokensExceeded => ("QUOTA_TOKENS_EXCEEDED", TOO_MANY_REQUESTS, "Daily token quota exceeded", "The daily upstream token quota is used up; it resets at midnight UTC"),
+        QuotaSubjectInvalid => ("QUOTA_SUBJECT_INVALID", BAD_REQUEST, "Invalid quota subject", "Use 'user:<uuid>' or 'ip:<address>'"),
+        QuotaDbError => ("QUOTA_DB_ERROR", INTERNAL_SERVER_ERROR, "Quota database error", "Failed to query the usage_quotas table"),
+    }
+}
+
+impl ApiError {
+    pub fn from_code(code: &str) -> Option<ApiError> {
+        Self::ALL.iter().copied().find(|error| error.code() == code)
+    }
+
+    /// Starts a response for this error with a message specific to the
+    /// occurrence.
+    pub fn with_message(self, message: impl Into<String>) -> ErrorResponse {
+        ErrorResponse {
+            error: self,
+            status: self.status(),
+            message: message.into(),
+            details: Map::new(),
+        }
+    }
+
+    /// Starts a response with the default message and one extra field.
+    pub fn detail(self, key: &str, value: impl Serialize) -> ErrorResponse {
+        ErrorResponse::from(self).detail(key, value)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        self.with_message(self.description()).into_response()
+    }
+}
+
+/// One occurrence of an `ApiError`: its message, the status actually sent
+/// and any extra fields (`retry_after`, `problems`, ...).
+#[derive(Debug, Clone)]
+pub struct ErrorResponse {
+    error: ApiError,
+    status: StatusCode,
+    message: String,
+    details: Map<String, Value>,
+}
+
+impl ErrorResponse {
+    /// Sends a different status than the catalog default, e.g. the
+    /// upstream's own status.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn detail(mut self, key: &str, value: impl Serialize) -> Self {
+        self.details.insert(key.to_string(), json!(value));
+        self
+    }
+
+    /// The body without a request id, for errors that may be shared
+    /// between requests.
+    fn body(&self) -> Value {
+        let mut body = Map::new();
+        body.insert("error".to_string(), json!(self.error.title()));
+        body.insert("message".to_string(), json!(self.message));
+        body.insert("code".to_string(), json!(self.error.code()));
+        body.insert("status".to_string(), json!(self.status.as_u16()));
+        body.extend(self.details.clone());
+        Value::Object(body)
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        UpstreamError::from(self).into_response()
+    }
+}
+
+impl From<ApiError> for ErrorResponse {
+    fn from(error: ApiError) -> Self {
+        error.with_message(error.description())
+    }
+}
+
+impl From<ErrorResponse> for UpstreamError {
+    fn from(error: ErrorResponse) -> Self {
+        UpstreamError::new(error.status, error.body())
+    }
+}
+
+impl From<ApiError> for UpstreamError {
+    fn from(error: ApiError) -> Self {
+        ErrorResponse::from(error).into()
+    }
+}
+
+/// Adds `request_id` to an error body. Bodies are built without one so a
+/// failure shared between requests can be tagged for each of them.
+pub fn attach_request_id(body: &mut Value, request_id: Option<&str>) {
+    if let (Some(object), Some(id)) = (body.as_object_mut(), request_id) {
+        object.insert("request_id".to_string(), json!(id));
+    }
+}
+
+/// The id of the request being handled, if called inside one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Gives every request an id (the caller's `X-Request-Id` when it looks
+/// sane, else a new UUID), echoes it in the `X-Request-Id` response header,
+/// and turns the plain-text errors axum produces itself (unknown route,
+/// wrong method, unreadable JSON body) into catalog errors.
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut response = REQUEST_ID
+        .scope(id.clone(), async move { normalize(next.run(request).await).await })
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+async fn normalize(response: Response) -> Response {
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !(status.is_client_error() || status.is_server_error()) || is_json {
+        return response;
+    }
+
+    let error = match status {
+        StatusCode::NOT_FOUND => ApiError::RouteNotFound,
+        StatusCode::METHOD_NOT_ALLOWED => ApiError::MethodNotAllowed,
+        s if s.is_server_error() => ApiError::InternalError,
+        _ => ApiError::InvalidRequestBody,
+    };
+
+    let (parts, body) = response.into_parts();
+    let text = to_bytes(body, MAX_NORMALIZED_BODY)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+    let message = if text.is_empty() || status.is_server_error() {
+        error.description().to_string()
+    } else {
+        text
+    };
+
+    let mut normalized = error.with_message(message).status(status).into_response();
+    // Keep headers such as `Allow` and `WWW-Authenticate`, every value of
+    // repeated ones like `Set-Cookie` included
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            normalized.headers_mut().append(name, value.clone());
+        }
+    }
+    normalized
+}
+
+/// The catalog entry served by `GET /api/errors`.
+#[derive(Debug, Serialize)]
+pub struct CatalogEntry {
+    pub code: &'static str,
+    pub status: u16,
+    pub error: &'static str,
+    pub description: &'static str,
+    pub group: &'static str,
+}
+
+pub fn catalog() -> Vec<CatalogEntry> {
+    ApiError::ALL
+        .iter()
+        .map(|&error| CatalogEntry {
+            code: error.code(),
+            status: error.status().as_u16(),
+            error: error.title(),
+            description: error.description(),
+            group: error.group(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn normalize_keeps_every_value_of_repeated_headers() {
+        let response = Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(header::ALLOW, "GET")
+            .header(header::SET_COOKIE, "a=1")
+            .header(header::SET_COOKIE, "b=2")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(axum::body::Body::from("nope"))
+            .unwrap();
+
+        let normalized = normalize(response).await;
+
+        let headers = normalized.headers();
+        let cookies: Vec<_> = headers.get_all(header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(headers[header::ALLOW], "GET");
+        assert_eq!(headers.get_all(header::CONTENT_TYPE).iter().count(), 1);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    }
+}
@@ -0,0 +1,6 @@
+pub mod auth;
+pub mod config;
+pub mod error;
+pub mod models;
+pub mod routes;
+pub mod services;
@@ -1,9 +1,6 @@
-mod models;
-mod routes;
-
+use backend::{auth, config, routes};
 use dotenvy::dotenv;
 use sqlx::postgres::PgPoolOptions;
-use std::env;
 use std::net::SocketAddr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -21,28 +18,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set in .env file");
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     tracing::info!("Connecting to database...");
 
     let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(std::time::Duration::from_secs(30))
-        .connect(&database_url)
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(config.database.acquire_timeout)
+        .connect(&config.database.url)
         .await
         .expect("Failed to create pool.");
 
     tracing::info!("✅ Connection to Supabase successful!");
 
-    let app = routes::create_router(pool);
+    let auth = auth::JwtVerifier::new(&config.auth)
+        .expect("Invalid Supabase JWT configuration");
+
+    let addr = SocketAddr::new(config.server.bind_address, config.server.port);
+    let app = routes::create_router(config, pool, auth);
 
-    let port = 3000;
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     tracing::info!("Listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses key the rate limits of anonymous callers
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 
     Ok(())
 }
@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+
+// Typed shape of the property risk report the dashboard renders. Field
+// names match the JSON contract the client already consumes.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyReport {
+    pub location_info: LocationInfo,
+    pub risk_analysis: RiskAnalysis,
+    pub historical_trends: HistoricalTrends,
+    pub market_intelligence: MarketIntelligence,
+    pub legal_resources: LegalResources,
+    #[serde(default)]
+    pub validation: ValidationReport,
+    #[serde(default)]
+    pub data_quality: DataQuality,
+    /// Sections that were estimated rather than researched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub estimated_sections: Vec<String>,
+}
+
+/// Whether the report came from the AI service or was estimated by the
+/// backend because the AI service was unavailable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataQuality {
+    #[default]
+    Researched,
+    Estimated,
+}
+
+/// What the backend had to fix in the model's answer before returning it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Values that were present but coerced, clamped or normalized
+    pub repaired: Vec<FieldIssue>,
+    /// Values that were missing and filled with a default
+    pub defaulted: Vec<FieldIssue>,
+    /// Whether the first answer failed validation and the model was asked again
+    pub reprompted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldIssue {
+    pub field: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocationInfo {
+    pub formatted_address: String,
+    pub coordinates: Option<Coordinates>,
+    pub region: Option<String>,
+    pub country: Option<String>,
+    pub jurisdiction: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiskStatus {
+    High,
+    Medium,
+    Low,
+}
+
+impl RiskStatus {
+    pub fn from_score(score: f64) -> Self {
+        if score >= 67.0 {
+            RiskStatus::High
+        } else if score >= 34.0 {
+            RiskStatus::Medium
+        } else {
+            RiskStatus::Low
+        }
+    }
+}
+
+// ============ Risk Analysis ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskAnalysis {
+    pub overall_score: f64,
+    pub buying_risk: ScoredRisk,
+    pub renting_risk: ScoredRisk,
+    pub flood_risk: FloodRisk,
+    pub crime_rate: CrimeRate,
+    pub air_quality: AirQuality,
+    pub amenities: Amenities,
+    pub transportation: Transportation,
+    pub neighbourhood: Neighbourhood,
+    pub environmental_hazards: EnvironmentalHazards,
+    pub growth_potential: GrowthPotential,
+    pub political_stability: PoliticalStability,
+    pub trade_economy: TradeEconomy,
+    pub soil_analysis: SoilAnalysis,
+    pub noise_data: NoiseData,
+    pub light_pollution: LightPollution,
+    pub additional_info: AdditionalInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredRisk {
+    pub score: f64,
+    pub status: RiskStatus,
+    #[serde(default)]
+    pub factors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloodRisk {
+    pub score: f64,
+    pub level: String,
+    #[serde(default)]
+    pub zones: Vec<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrimeRate {
+    pub score: f64,
+    pub rate_per_1000: Option<f64>,
+    pub trend: String,
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AirQuality {
+    pub aqi: f64,
+    pub score: f64,
+    pub rating: String,
+    #[serde(default)]
+    pub pollutants: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Amenities {
+    pub score: f64,
+    pub walkability: Option<f64>,
+    #[serde(default)]
+    pub nearby: Vec<AmenityGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmenityGroup {
+    pub r#type: String,
+    pub count: Option<u32>,
+    pub closest_distance: Option<String>,
+    #[serde(default)]
+    pub facilities: Vec<Facility>,
+}
+
+/// One nearby school, hospital, shop or park. Which optional fields are set
+/// depends on the group it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Facility {
+    pub name: String,
+    pub distance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub specialty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transportation {
+    pub score: f64,
+    #[serde(default)]
+    pub transit_options: Vec<String>,
+    pub commute_time: Option<String>,
+    pub walkability_index: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Neighbourhood {
+    pub score: f64,
+    pub rating: String,
+    pub character: Option<String>,
+    pub demographics: Option<Demographics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Demographics {
+    pub median_age: Option<f64>,
+    pub population_density: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentalHazards {
+    pub score: f64,
+    #[serde(default)]
+    pub hazards: Vec<String>,
+    pub severity: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthPotential {
+    pub score: f64,
+    pub forecast: String,
+    #[serde(default)]
+    pub drivers: Vec<String>,
+    pub outlook_5yr: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoliticalStability {
+    pub score: f64,
+    pub status: String,
+    #[serde(default)]
+    pub factors: Vec<String>,
+    #[serde(default)]
+    pub recent_events: Vec<String>,
+    pub policy_environment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeEconomy {
+    pub gdp_growth: Option<f64>,
+    pub gdp_trend: Option<String>,
+    pub inflation_rate: Option<f64>,
+    pub unemployment_rate: Option<f64>,
+    pub trade_balance: Option<String>,
+    pub economic_outlook: Option<String>,
+    #[serde(default)]
+    pub major_industries: Vec<String>,
+    pub trade_relations: Option<TradeRelations>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeRelations {
+    pub status: Option<String>,
+    #[serde(default)]
+    pub key_partners: Vec<String>,
+    pub impact_on_property: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoilAnalysis {
+    pub r#type: Option<String>,
+    pub stability: Option<String>,
+    pub liquefaction_risk: Option<String>,
+    pub foundation_concerns: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseData {
+    pub score: f64,
+    pub level: String,
+    pub db_avg: Option<f64>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightPollution {
+    pub score: f64,
+    pub bortle_scale: Option<f64>,
+    pub brightness: String,
+    pub impact: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdditionalInfo {
+    pub solar_potential: Option<String>,
+    pub weather_summary: Option<String>,
+    #[serde(default)]
+    pub climate_risks: Vec<String>,
+    pub insurance_considerations: Option<String>,
+}
+
+// ============ Historical Trends ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalTrends {
+    pub property_values: Vec<PropertyValuePoint>,
+    pub crime_trends: Vec<CrimeTrendPoint>,
+    pub population: Vec<PopulationPoint>,
+    #[serde(default)]
+    pub development_timeline: Vec<DevelopmentYear>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyValuePoint {
+    pub year: i32,
+    pub median_price: f64,
+    pub change_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrimeTrendPoint {
+    pub year: i32,
+    pub incidents_per_1000: f64,
+    pub change_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PopulationPoint {
+    pub year: i32,
+    pub count: f64,
+    pub change_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevelopmentYear {
+    pub year: i32,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+// ============ Market Intelligence ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketIntelligence {
+    pub current_trend: String,
+    pub prediction_6mo: Option<String>,
+    pub prediction_1yr: Option<String>,
+    pub ai_summary: String,
+    #[serde(default)]
+    pub recent_listings: Vec<Listing>,
+    #[serde(default)]
+    pub news: Vec<NewsItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listing {
+    pub address: String,
+    pub price: Option<String>,
+    pub r#type: Option<String>,
+    pub bedrooms: Option<u32>,
+    pub sqft: Option<f64>,
+    pub date: Option<String>,
+    pub coordinates: Option<Coordinates>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewsItem {
+    pub headline: String,
+    pub summary: Option<String>,
+    pub date: Option<String>,
+    pub source: Option<String>,
+    pub relevance: Option<String>,
+}
+
+// ============ Legal Resources ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalResources {
+    pub jurisdiction: String,
+    pub property_law_system: Option<String>,
+    #[serde(default)]
+    pub key_statutes: Vec<Statute>,
+    pub dispute_process: Option<String>,
+    pub typical_timeline: Option<String>,
+    #[serde(default)]
+    pub resources: Vec<LegalResource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Statute {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalResource {
+    pub name: String,
+    pub r#type: Option<String>,
+    pub description: Option<String>,
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Provider-neutral geocoding result. The client reads only these fields,
+// so the provider behind /api/geocode can change without breaking it.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeocodeResult {
+    pub formatted_address: String,
+    pub coordinates: Coordinates,
+    pub components: AddressComponents,
+    /// IANA name, e.g. `America/New_York`
+    pub timezone: Option<String>,
+    /// ISO 4217 code, e.g. `USD`
+    pub currency: Option<String>,
+    /// 0 (unknown or huge area) to 10 (within 250 m), as OpenCage defines it
+    pub confidence: Option<u8>,
+    pub bounds: Option<Bounds>,
+    /// The geocoder that produced this result, e.g. `opencage`
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub northeast: Coordinates,
+    pub southwest: Coordinates,
+}
+
+/// The parts of an address, each `None` when the provider did not know it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressComponents {
+    pub country: Option<String>,
+    /// Upper-case ISO 3166-1 alpha-2
+    pub country_code: Option<String>,
+    pub state: Option<String>,
+    pub county: Option<String>,
+    pub city: Option<String>,
+    pub postcode: Option<String>,
+    pub suburb: Option<String>,
+    pub road: Option<String>,
+}
+
+/// One provider's answer to a lookup, as cached: the normalized results
+/// and the provider's own response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Geocoded {
+    pub provider: String,
+    pub results: Vec<GeocodeResult>,
+    pub raw: Value,
+}
+
+/// Body of the geocoding endpoints. `raw` is the provider's own payload,
+/// included only when asked for with `raw=true`.
+#[derive(Debug, Serialize)]
+pub struct GeocodeResponse {
+    pub results: Vec<GeocodeResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Value>,
+}
+
+/// How one query of a batch went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Ok,
+    NoResults,
+    Error,
+}
+
+/// One query of a batch, in the position it was sent. Repeated queries
+/// each get an item, with the same outcome.
+#[derive(Debug, Serialize)]
+pub struct BatchItem {
+    pub index: usize,
+    pub query: String,
+    pub status: BatchStatus,
+    /// Served from the cache rather than the provider
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<GeocodeResult>,
+    /// `{code, message}` from the error catalog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+/// One type-ahead suggestion: just enough to show it and search for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub label: String,
+    pub coordinates: Option<Coordinates>,
+    /// The geocoder behind it; `None` for a past search it did not return
+    pub provider: Option<String>,
+    /// The user has searched for this place before
+    pub from_history: bool,
+    /// Distance from `near`, when both points are known
+    pub distance_km: Option<f64>,
+}
@@ -1,2 +1,4 @@
+pub mod analysis;
 pub mod cache_entries;
+pub mod geocode;
 pub mod search_history;
@@ -0,0 +1,336 @@
+use axum::{
+    extract::{State, Json},
+    response::IntoResponse,
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::search::AppState;
+use crate::auth::AuthUser;
+use crate::error::ApiError;
+use crate::models::analysis::{Coordinates, PropertyReport};
+use crate::models::geocode::GeocodeResult;
+use crate::services::analysis_fallback::{estimated_report, NearbyData};
+use crate::services::analysis_schema::validate_report;
+use crate::services::analysis_prompt::{build_prompt, SYSTEM_PROMPT};
+use crate::services::cache::{normalize_text, CacheKey, CacheKind, CACHE_HEADER};
+use crate::services::geocode::{cached, GeocodeLookup};
+use crate::services::llm::{CompletionRequest, Message, UseCase};
+use crate::services::upstream::UpstreamError;
+
+const ANALYSIS_MAX_TOKENS: u32 = 3000;
+const FALLBACK_MAX_NEIGHBOURS: usize = 5;
+const KM_PER_DEGREE: f64 = 111.0;
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeRequest {
+    pub location: String,
+}
+
+/// POST /api/analyze - Full property risk report for a location
+pub async fn analyze_property(
+    State(state): State<AppState>,
+    user: Option<AuthUser>,
+    Json(payload): Json<AnalyzeRequest>,
+) -> impl IntoResponse {
+    let location = payload.location.trim();
+    if location.is_empty() {
+        return ApiError::AnalyzeEmptyLocation.into_response();
+    }
+
+    let cache_key = analysis_cache_key(location);
+
+    let (result, status) = state
+        .cache
+        .get_or_fetch(&cache_key, || run_analysis(&state, location))
+        .await;
+
+    match result {
+        Ok(data) => (StatusCode::OK, [(CACHE_HEADER, status.header_value())], Json(data)).into_response(),
+        Err(e) => {
+            // Never cached, so the next request tries the AI service again
+            tracing::warn!("Analysis failed for {}, serving estimated report: {:?}", location, e.body);
+            match fallback_report(&state, user.as_ref(), location).await {
+                Ok(data) => (StatusCode::OK, [(CACHE_HEADER, status.header_value())], Json(data)).into_response(),
+                Err(_) => e.into_response(),
+            }
+        }
+    }
+}
+
+fn analysis_cache_key(location: &str) -> CacheKey {
+    let location = normalize_text(location);
+    CacheKey::new(CacheKind::Analysis, Some(&location), json!({ "location": location }))
+}
+
+/// Geocodes the location, asks the LLM for a report and returns it as JSON.
+pub(super) async fn run_analysis(state: &AppState, location: &str) -> Result<Value, UpstreamError> {
+    let geocoded = geocode_location(state, location).await;
+    let prompt = build_prompt(&location_context(location, geocoded.as_ref()));
+
+    let mut messages = vec![Message::new("system", SYSTEM_PROMPT), Message::new("user", prompt)];
+
+    tracing::info!("Generating property analysis for: {}", location);
+    let content = complete(state, &messages).await?;
+
+    let mut report = match validate_report(&content, location) {
+        Ok(report) => report,
+        Err(problems) => {
+            // One retry, telling the model exactly what was wrong
+            tracing::warn!("Analysis for {} failed validation, re-prompting: {:?}", location, problems);
+            messages.push(Message::new("assistant", content));
+            messages.push(Message::new("user", correction_prompt(&problems)));
+
+            let retry = complete(state, &messages).await?;
+            let mut report = validate_report(&retry, location).map_err(|problems| {
+                tracing::error!("Analysis for {} failed validation after re-prompt: {:?}", location, problems);
+                validation_error(&problems)
+            })?;
+            report.validation.reprompted = true;
+            report
+        }
+    };
+
+    // Geocoder facts beat whatever the model guessed
+    if let Some(result) = &geocoded {
+        apply_geocode(&mut report, result);
+    }
+
+    serde_json::to_value(&report).map_err(|e| parse_error(&e.to_string()))
+}
+
+/// Estimated report built from the geocoder and what is known nearby, used
+/// when the AI service cannot produce a real one.
+async fn fallback_report(
+    state: &AppState,
+    user: Option<&AuthUser>,
+    location: &str,
+) -> Result<Value, serde_json::Error> {
+    let geocoded = geocode_location(state, location).await;
+    let nearby = nearby_data(state, user, location, geocoded.as_ref()).await;
+
+    tracing::info!(
+        "Estimating report for {} from {} cached analyses and {} scores",
+        location,
+        nearby.reports.len(),
+        nearby.area_searches
+    );
+
+    serde_json::to_value(estimated_report(location, geocoded.as_ref(), &nearby))
+}
+
+/// Rows of `search_history` near the location: by distance when the
+/// geocoder gave coordinates (`$1`..`$4`), otherwise by matching city and
+/// state (`$5`, `$6`).
+const NEARBY_SEARCHES: &str = r#"
+    location_name IS NOT NULL
+    AND (
+      ($1::float8 IS NOT NULL
+        AND latitude::float8 BETWEEN $1 - $3 AND $1 + $3
+        AND longitude::float8 BETWEEN $2 - $4 AND $2 + $4)
+      OR ($5::text IS NOT NULL AND lower(city) = lower($5)
+        AND ($6::text IS NULL OR lower(state) = lower($6)))
+    )
+"#;
+
+/// What is known around the location. Other users' searches only
+/// contribute their average risk score; reports come from the analysis
+/// cache for the caller's own past searches. Nothing here calls the AI
+/// service.
+async fn nearby_data(
+    state: &AppState,
+    user: Option<&AuthUser>,
+    location: &str,
+    geocoded: Option<&GeocodeResult>,
+) -> NearbyData {
+    let Some(result) = geocoded else {
+        return NearbyData::default();
+    };
+
+    let city = result.components.city.as_deref();
+    let region = result.components.state.as_deref();
+
+    let radius_km = state.config.analysis.fallback_radius_km;
+    let (lat, lng) = (result.coordinates.lat, result.coordinates.lng);
+    let lat_delta = radius_km / KM_PER_DEGREE;
+    let lng_delta = radius_km / (KM_PER_DEGREE * lat.to_radians().cos().max(0.01));
+
+    let mut nearby = NearbyData::default();
+
+    let area = sqlx::query_as::<_, (Option<f64>, i64)>(&format!(
+        "SELECT avg(LEAST(GREATEST(risk_score, 0), 100))::float8, count(risk_score) FROM search_history WHERE {}",
+        NEARBY_SEARCHES
+    ))
+    .bind(lat)
+    .bind(lng)
+    .bind(lat_delta)
+    .bind(lng_delta)
+    .bind(city)
+    .bind(region)
+    .fetch_one(&state.pool)
+    .await;
+
+    match area {
+        Ok((score, searches)) => {
+            nearby.area_score = score;
+            nearby.area_searches = searches;
+        }
+        Err(e) => tracing::warn!("Failed to load nearby risk scores for fallback: {:?}", e),
+    }
+
+    let Some(user) = user else {
+        return nearby;
+    };
+
+    let rows = sqlx::query_scalar::<_, String>(&format!(
+        r#"
+        SELECT location_name
+        FROM search_history
+        WHERE user_id = $7 AND {}
+        ORDER BY
+          CASE WHEN latitude IS NULL OR $1::float8 IS NULL THEN NULL
+               ELSE power(latitude::float8 - $1, 2) + power(longitude::float8 - $2, 2) END NULLS LAST,
+          created_at DESC
+        LIMIT 50
+        "#,
+        NEARBY_SEARCHES
+    ))
+    .bind(lat)
+    .bind(lng)
+    .bind(lat_delta)
+    .bind(lng_delta)
+    .bind(city)
+    .bind(region)
+    .bind(user.user_id)
+    .fetch_all(&state.pool)
+    .await;
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!("Failed to load nearby searches for fallback: {:?}", e);
+            return nearby;
+        }
+    };
+
+    let mut seen = Vec::new();
+
+    for name in rows {
+        let name = name.trim().to_string();
+        let normalized = name.to_lowercase();
+        if name.is_empty()
+            || normalized == location.to_lowercase()
+            || seen.contains(&normalized)
+            || nearby.reports.len() >= FALLBACK_MAX_NEIGHBOURS
+        {
+            continue;
+        }
+        seen.push(normalized);
+
+        if let Some(data) = state.cache.peek(&analysis_cache_key(&name).key).await
+            && let Ok(report) = serde_json::from_value::<PropertyReport>(data)
+        {
+            nearby.reports.push(report);
+        }
+    }
+
+    nearby
+}
+
+/// First geocoding result for the location, via the shared geocode cache.
+/// Failures are logged and the analysis continues with the bare string.
+async fn geocode_location(state: &AppState, location: &str) -> Option<GeocodeResult> {
+    let lookup = GeocodeLookup::Forward {
+        q: location.to_string(),
+        language: None,
+    };
+
+    let (result, _) = cached(&state.cache, &lookup, || state.geocoder.fetch(&lookup)).await;
+
+    match result {
+        Ok(geocoded) => geocoded.results.into_iter().next(),
+        Err(e) => {
+            tracing::warn!("Geocoding failed, proceeding with basic location: {:?}", e.body);
+            None
+        }
+    }
+}
+
+fn location_context(location: &str, geocoded: Option<&GeocodeResult>) -> String {
+    let Some(result) = geocoded else {
+        return format!("Location: {}", location);
+    };
+
+    let components = &result.components;
+    let formatted = Some(result.formatted_address.as_str()).filter(|address| !address.is_empty());
+    let mut lines = vec![format!("Location: {}", formatted.unwrap_or(location))];
+
+    if let Some(country) = &components.country {
+        let code = components.country_code.as_deref().unwrap_or_default();
+        lines.push(format!("Country: {} ({})", country, code));
+    }
+    if let Some(state) = &components.state {
+        lines.push(format!("State/Region: {}", state));
+    }
+    if let Some(city) = &components.city {
+        lines.push(format!("City: {}", city));
+    }
+    if let Some(county) = &components.county {
+        lines.push(format!("County: {}", county));
+    }
+    if let Some(timezone) = &result.timezone {
+        lines.push(format!("Timezone: {}", timezone));
+    }
+    lines.push(format!("Coordinates: {}, {}", result.coordinates.lat, result.coordinates.lng));
+
+    lines.join("\n")
+}
+
+fn apply_geocode(report: &mut PropertyReport, result: &GeocodeResult) {
+    let info = &mut report.location_info;
+    let components = &result.components;
+
+    if !result.formatted_address.is_empty() {
+        info.formatted_address = result.formatted_address.clone();
+    }
+    info.coordinates = Some(Coordinates {
+        lat: result.coordinates.lat,
+        lng: result.coordinates.lng,
+    });
+    if let Some(country) = &components.country {
+        info.country = Some(country.clone());
+    }
+    if let Some(region) = components.state.as_ref().or(components.county.as_ref()) {
+        info.region = Some(region.clone());
+    }
+}
+
+/// Sends the conversation to the analysis providers and returns the reply text.
+async fn complete(state: &AppState, messages: &[Message]) -> Result<String, UpstreamError> {
+    let request = CompletionRequest {
+        max_tokens: Some(ANALYSIS_MAX_TOKENS),
+        temperature: Some(0.1),
+        json_output: true,
+        ..CompletionRequest::new(messages.to_vec())
+    };
+
+    let completion = state.llm.complete(UseCase::Analysis, &request).await?;
+    tracing::info!("Analysis generated by {}", completion.provider);
+    Ok(completion.content)
+}
+
+fn correction_prompt(problems: &[String]) -> String {
+    format!(
+        "Your previous response failed validation:\n- {}\n\nReturn the complete corrected JSON object with the same structure. Output valid English JSON only, no markdown.",
+        problems.join("\n- ")
+    )
+}
+
+fn validation_error(problems: &[String]) -> UpstreamError {
+    ApiError::AnalysisValidationError.detail("problems", problems).into()
+}
+
+fn parse_error(message: &str) -> UpstreamError {
+    ApiError::AnalysisParseError.with_message(message).into()
+}
@@ -1,197 +1,259 @@
 use axum::{
     extract::{State, Json, Query},
+    Extension,
     response::IntoResponse,
     http::StatusCode,
 };
+use futures_util::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::env;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
 
+use super::limits::RequestsLeft;
 use super::search::AppState;
+use crate::error::ApiError;
+use crate::models::geocode::{BatchItem, BatchStatus, GeocodeResponse, Geocoded};
+use crate::services::cache::{CacheKey, CacheKind, CacheStatus, CACHE_HEADER};
+use crate::services::geocode::{cached, GeocodeLookup};
+use crate::services::quota::{add_lookup, add_tokens};
+use crate::services::upstream::UpstreamError;
 
-// ============ Google Maps Proxy ============
-
-#[derive(Debug, Deserialize)]
-pub struct MapsConfigRequest {
-    // Empty - just returns the API key
-}
-
-/// GET /api/maps/config - Returns Google Maps configuration
-pub async fn get_maps_config(
-    State(_state): State<AppState>,
-) -> impl IntoResponse {
-    let api_key = match env::var("GOOGLE_MAPS_API_KEY") {
-        Ok(key) => {
-            if key.is_empty() {
-                tracing::error!("GOOGLE_MAPS_API_KEY is empty");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": "Google Maps API key is not configured",
-                        "message": "The GOOGLE_MAPS_API_KEY environment variable is empty. Please add a valid API key to the backend .env file.",
-                        "code": "MAPS_KEY_EMPTY"
-                    }))
-                ).into_response();
-            }
-            key
-        },
-        Err(_) => {
-            tracing::error!("GOOGLE_MAPS_API_KEY not found in environment");
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Google Maps API key is missing",
-                    "message": "The GOOGLE_MAPS_API_KEY environment variable is not set. Please add it to the backend .env file.",
-                    "code": "MAPS_KEY_MISSING"
-                }))
-            ).into_response();
-        }
-    };
-
-    tracing::info!("Successfully retrieved Google Maps API key");
-    (StatusCode::OK, Json(json!({"apiKey": api_key}))).into_response()
-}
-
-// ============ OpenCage Geocoding Proxy ============
+// ============ Geocoding ============
 
 #[derive(Debug, Deserialize)]
 pub struct GeocodeRequest {
     pub q: String,  // Query (address or coordinates)
+    /// At most `FORWARD_LIMIT`; applied to the cached answer
     pub limit: Option<u32>,
     pub language: Option<String>,
+    /// Include the provider's own response as `raw`; not part of the cache key
+    #[serde(default)]
+    pub raw: bool,
 }
 
-/// GET /api/geocode - Proxy for OpenCage geocoding
+/// GET /api/geocode - Geocodes an address through the configured providers
+/// and returns normalized `GeocodeResult`s
 pub async fn geocode_address(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Query(params): Query<GeocodeRequest>,
 ) -> impl IntoResponse {
     // Validate query parameter
     if params.q.trim().is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "Invalid request",
-                "message": "The 'q' parameter (location query) cannot be empty",
-                "code": "INVALID_QUERY"
-            }))
-        ).into_response();
+        return ApiError::InvalidQuery.into_response();
     }
 
-    let api_key = match env::var("OPENCAGE_API_KEY") {
-        Ok(key) => {
-            if key.is_empty() {
-                tracing::error!("OPENCAGE_API_KEY is empty");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": "Geocoding API key is not configured",
-                        "message": "The OPENCAGE_API_KEY environment variable is empty. Please add a valid API key to the backend .env file.",
-                        "code": "GEOCODE_KEY_EMPTY"
-                    }))
-                ).into_response();
-            }
-            key
-        },
-        Err(_) => {
-            tracing::error!("OPENCAGE_API_KEY not found in environment");
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Geocoding API key is missing",
-                    "message": "The OPENCAGE_API_KEY environment variable is not set. Please add it to the backend .env file.",
-                    "code": "GEOCODE_KEY_MISSING"
-                }))
-            ).into_response();
-        }
+    let lookup = GeocodeLookup::Forward {
+        q: params.q,
+        language: params.language,
     };
 
-    let client = reqwest::Client::new();
-    
-    let mut url = format!(
-        "https://api.opencagedata.com/geocode/v1/json?q={}&key={}",
-        urlencoding::encode(&params.q),
-        api_key
-    );
+    let (result, status) = cached(&state.cache, &lookup, || state.geocoder.fetch(&lookup)).await;
 
-    if let Some(limit) = params.limit {
-        url.push_str(&format!("&limit={}", limit));
+    match result {
+        Ok(mut geocoded) => {
+            if let Some(limit) = params.limit {
+                geocoded.results.truncate(limit as usize);
+            }
+            let body = GeocodeResponse {
+                results: geocoded.results,
+                raw: params.raw.then_some(geocoded.raw),
+            };
+            (StatusCode::OK, [(CACHE_HEADER, status.header_value())], Json(body)).into_response()
+        }
+        Err(e) => e.into_response(),
     }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchGeocodeRequest {
+    pub queries: Vec<String>,
+    pub language: Option<String>,
+}
 
-    if let Some(language) = params.language {
-        url.push_str(&format!("&language={}", language));
+/// POST /api/geocode/batch - Geocodes up to `GEOCODE_BATCH_MAX` addresses.
+/// Repeated queries are looked up once and cached ones are not sent again.
+/// The rest go to the providers `GEOCODE_BATCH_CONCURRENCY` at a time, each
+/// provider held to its own rate. Each one counts against the
+/// caller's daily request quota, and once that is used up the remaining
+/// items fail instead of being sent. Each item reports its own outcome, so
+/// one bad address does not fail the batch.
+pub async fn batch_geocode(
+    State(state): State<AppState>,
+    requests_left: Option<Extension<RequestsLeft>>,
+    Json(payload): Json<BatchGeocodeRequest>,
+) -> impl IntoResponse {
+    let config = &state.config.geocode;
+    if payload.queries.is_empty() {
+        return ApiError::GeocodeBatchEmpty.into_response();
+    }
+    if payload.queries.len() > config.batch_max {
+        return ApiError::GeocodeBatchTooLarge
+            .with_message(format!(
+                "{} queries sent; a batch may hold at most {}",
+                payload.queries.len(),
+                config.batch_max
+            ))
+            .into_response();
     }
 
-    tracing::info!("Proxying geocoding request for: {}", params.q);
-
-    match client.get(&url).send().await {
-        Ok(response) => {
-            let status = response.status();
-            
-            if !status.is_success() {
-                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                tracing::error!("Geocoding API error ({}): {}", status, error_text);
-                
-                let (error_msg, code) = match status.as_u16() {
-                    401 => ("Invalid or expired OpenCage API key", "GEOCODE_UNAUTHORIZED"),
-                    402 => ("OpenCage API quota exceeded", "GEOCODE_QUOTA_EXCEEDED"),
-                    403 => ("OpenCage API access forbidden", "GEOCODE_FORBIDDEN"),
-                    429 => ("Too many geocoding requests. Please try again later", "GEOCODE_RATE_LIMIT"),
-                    _ => ("Geocoding service error", "GEOCODE_ERROR"),
-                };
-                
-                return (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                    Json(json!({
-                        "error": error_msg,
-                        "message": error_text,
-                        "code": code,
-                        "status": status.as_u16()
-                    }))
-                ).into_response();
+    // One lookup per distinct cache key, in first-seen order
+    let mut unique: Vec<(String, GeocodeLookup)> = Vec::new();
+    let item_keys: Vec<Option<String>> = payload
+        .queries
+        .iter()
+        .map(|query| {
+            let query = query.trim();
+            if query.is_empty() {
+                return None;
+            }
+            let lookup = GeocodeLookup::Forward {
+                q: query.to_string(),
+                language: payload.language.clone(),
+            };
+            let key = lookup.cache_key().key;
+            if !unique.iter().any(|(seen, _)| *seen == key) {
+                unique.push((key.clone(), lookup));
             }
+            Some(key)
+        })
+        .collect();
 
-            match response.json::<Value>().await {
-                Ok(data) => {
-                    tracing::info!("Successfully proxied geocoding request");
-                    (StatusCode::OK, Json(data)).into_response()
+    let state = &state;
+    let budget = &AtomicI64::new(requests_left.and_then(|Extension(RequestsLeft(left))| left).unwrap_or(i64::MAX));
+    let outcomes: HashMap<String, (Result<Geocoded, UpstreamError>, CacheStatus)> = stream::iter(unique)
+        .map(|(key, lookup)| async move {
+            let outcome = cached(&state.cache, &lookup, || async {
+                if budget.fetch_sub(1, Ordering::Relaxed) <= 0 {
+                    return Err(ApiError::QuotaRequestsExceeded
+                        .with_message("The daily request quota ran out before this address was looked up")
+                        .into());
                 }
-                Err(e) => {
-                    tracing::error!("Failed to parse geocoding response: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to parse geocoding response",
-                            "message": "The geocoding service returned an invalid response format",
-                            "code": "GEOCODE_PARSE_ERROR"
-                        }))
-                    ).into_response()
+                add_lookup();
+                state.geocoder.fetch_paced(&lookup).await
+            })
+            .await;
+            (key, outcome)
+        })
+        .buffer_unordered(config.batch_concurrency)
+        .collect()
+        .await;
+
+    let items: Vec<BatchItem> = payload
+        .queries
+        .iter()
+        .zip(item_keys)
+        .enumerate()
+        .map(|(index, (query, key))| {
+            let mut item = BatchItem {
+                index,
+                query: query.clone(),
+                status: BatchStatus::Error,
+                cached: false,
+                result: None,
+                error: None,
+            };
+            let Some((result, status)) = key.and_then(|key| outcomes.get(&key)) else {
+                item.error = Some(item_error(&ApiError::InvalidQuery.with_message("The query is empty").into()));
+                return item;
+            };
+
+            item.cached = *status == CacheStatus::Hit;
+            match result {
+                Ok(geocoded) => {
+                    item.result = geocoded.results.first().cloned();
+                    item.status = if item.result.is_some() { BatchStatus::Ok } else { BatchStatus::NoResults };
                 }
+                Err(e) => item.error = Some(item_error(e)),
             }
-        }
-        Err(e) => {
-            tracing::error!("Failed to call geocoding service: {:?}", e);
-            
-            let (error_msg, code) = if e.is_timeout() {
-                ("Geocoding request timed out", "GEOCODE_TIMEOUT")
-            } else if e.is_connect() {
-                ("Cannot connect to geocoding service", "GEOCODE_CONNECTION_ERROR")
-            } else {
-                ("Geocoding service unavailable", "GEOCODE_SERVICE_ERROR")
+            item
+        })
+        .collect();
+
+    let count = |status| items.iter().filter(|item| item.status == status).count();
+    tracing::info!(
+        "Geocoded a batch of {} ({} unique): {} ok, {} failed",
+        items.len(),
+        outcomes.len(),
+        count(BatchStatus::Ok),
+        count(BatchStatus::Error)
+    );
+
+    Json(json!({
+        "total": items.len(),
+        "unique": outcomes.len(),
+        "ok": count(BatchStatus::Ok),
+        "no_results": count(BatchStatus::NoResults),
+        "failed": count(BatchStatus::Error),
+        "items": items
+    }))
+    .into_response()
+}
+
+/// The catalog code and message of a failed batch item.
+fn item_error(e: &UpstreamError) -> Value {
+    json!({ "code": e.body["code"], "message": e.body["message"] })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReverseGeocodeRequest {
+    pub lat: Option<String>,
+    pub lng: Option<String>,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+/// GET /api/reverse-geocode - The address at a point, for map clicks and
+/// device location. Coordinates are snapped to `GEOCODE_REVERSE_PRECISION`
+/// decimals first, so nearby points share a cache entry.
+pub async fn reverse_geocode(
+    State(state): State<AppState>,
+    Query(params): Query<ReverseGeocodeRequest>,
+) -> impl IntoResponse {
+    let Some((lat, lng)) = parse_coordinates(params.lat.as_deref(), params.lng.as_deref()) else {
+        return ApiError::InvalidCoordinates.into_response();
+    };
+
+    let precision = state.config.geocode.reverse_precision;
+    let lookup = GeocodeLookup::Reverse {
+        lat: snap(lat, precision),
+        lng: snap(lng, precision),
+        language: params.language,
+    };
+
+    let (result, status) = cached(&state.cache, &lookup, || state.geocoder.fetch(&lookup)).await;
+
+    match result {
+        Ok(geocoded) => {
+            if geocoded.results.is_empty() {
+                return ApiError::NoResults
+                    .with_message(format!("No address found at {}", lookup.describe()))
+                    .into_response();
+            }
+            let body = GeocodeResponse {
+                results: geocoded.results,
+                raw: params.raw.then_some(geocoded.raw),
             };
-            
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(json!({
-                    "error": error_msg,
-                    "message": format!("Failed to reach OpenCage API: {}", e),
-                    "code": code
-                }))
-            ).into_response()
+            (StatusCode::OK, [(CACHE_HEADER, status.header_value())], Json(body)).into_response()
         }
+        Err(e) => e.into_response(),
     }
 }
 
+pub(super) fn parse_coordinates(lat: Option<&str>, lng: Option<&str>) -> Option<(f64, f64)> {
+    let lat: f64 = lat?.trim().parse().ok()?;
+    let lng: f64 = lng?.trim().parse().ok()?;
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)).then_some((lat, lng))
+}
+
+/// Rounds to `precision` decimals, so nearby points give the same cache key.
+fn snap(value: f64, precision: u32) -> f64 {
+    let factor = 10f64.powi(precision as i32);
+    // Adding 0.0 turns -0.0 into 0.0
+    (value * factor).round() / factor + 0.0
+}
+
 // ============ Gemini AI Proxy ============
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -223,130 +285,33 @@ pub struct GeminiGenerationConfig {
 
 /// POST /api/gemini - Proxy for Gemini AI
 pub async fn gemini_generate(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Json(payload): Json<GeminiRequest>,
 ) -> impl IntoResponse {
     // Validate request
     if payload.contents.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "Invalid request",
-                "message": "The 'contents' array cannot be empty",
-                "code": "GEMINI_EMPTY_CONTENTS"
-            }))
-        ).into_response();
+        return ApiError::GeminiEmptyContents.into_response();
     }
 
-    let api_key = match env::var("GEMINI_API_KEY") {
-        Ok(key) => {
-            if key.is_empty() {
-                tracing::error!("GEMINI_API_KEY is empty");
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": "Gemini API key is not configured",
-                        "message": "The GEMINI_API_KEY environment variable is empty. Please add a valid API key to the backend .env file.",
-                        "code": "GEMINI_KEY_EMPTY"
-                    }))
-                ).into_response();
-            }
-            key
-        },
-        Err(_) => {
-            tracing::error!("GEMINI_API_KEY not found in environment");
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Gemini API key is missing",
-                    "message": "The GEMINI_API_KEY environment variable is not set. Please add it to the backend .env file.",
-                    "code": "GEMINI_KEY_MISSING"
-                }))
-            ).into_response();
-        }
-    };
+    let cache_key = CacheKey::new(CacheKind::Gemini, None, json!(payload));
 
-    let client = reqwest::Client::new();
-    
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent?key={}",
-        api_key
-    );
+    let (result, status) = state
+        .cache
+        .get_or_fetch(&cache_key, || fetch_gemini(&state, &payload))
+        .await;
 
-    tracing::info!("Proxying Gemini AI request");
-
-    match client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            let status = response.status();
-            
-            if !status.is_success() {
-                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                tracing::error!("Gemini API error ({}): {}", status, error_text);
-                
-                let (error_msg, code) = match status.as_u16() {
-                    400 => ("Invalid Gemini API request", "GEMINI_BAD_REQUEST"),
-                    401 => ("Invalid or expired Gemini API key", "GEMINI_UNAUTHORIZED"),
-                    403 => ("Gemini API access forbidden", "GEMINI_FORBIDDEN"),
-                    429 => ("Too many AI requests. Please try again later", "GEMINI_RATE_LIMIT"),
-                    500 => ("Gemini service internal error", "GEMINI_SERVER_ERROR"),
-                    503 => ("Gemini service temporarily unavailable", "GEMINI_UNAVAILABLE"),
-                    _ => ("Gemini AI service error", "GEMINI_ERROR"),
-                };
-                
-                return (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                    Json(json!({
-                        "error": error_msg,
-                        "message": error_text,
-                        "code": code,
-                        "status": status.as_u16()
-                    }))
-                ).into_response();
-            }
+    match result {
+        Ok(data) => (StatusCode::OK, [(CACHE_HEADER, status.header_value())], Json(data)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-            match response.json::<Value>().await {
-                Ok(data) => {
-                    tracing::info!("Successfully proxied Gemini request");
-                    (StatusCode::OK, Json(data)).into_response()
-                }
-                Err(e) => {
-                    tracing::error!("Failed to parse Gemini response: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to parse Gemini response",
-                            "message": "The Gemini API returned an invalid response format",
-                            "code": "GEMINI_PARSE_ERROR"
-                        }))
-                    ).into_response()
-                }
-            }
-        }
-        Err(e) => {
-            tracing::error!("Failed to call Gemini API: {:?}", e);
-            
-            let (error_msg, code) = if e.is_timeout() {
-                ("Gemini request timed out", "GEMINI_TIMEOUT")
-            } else if e.is_connect() {
-                ("Cannot connect to Gemini service", "GEMINI_CONNECTION_ERROR")
-            } else {
-                ("Gemini service unavailable", "GEMINI_SERVICE_ERROR")
-            };
-            
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(json!({
-                    "error": error_msg,
-                    "message": format!("Failed to reach Gemini API: {}", e),
-                    "code": code
-                }))
-            ).into_response()
-        }
+/// Calls Gemini for a validated generation request
+pub(super) async fn fetch_gemini(state: &AppState, payload: &GeminiRequest) -> Result<Value, UpstreamError> {
+    let data = state.llm.gemini().generate_content(None, &json!(payload)).await?;
+    if let Some(tokens) = data["usageMetadata"]["totalTokenCount"].as_i64() {
+        add_tokens(tokens);
     }
+    tracing::info!("Successfully proxied Gemini request");
+    Ok(data)
 }
@@ -0,0 +1,149 @@
+use axum::{
+    extract::{State, Json, Query},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::ai_chat::fetch_details;
+use super::analyze::run_analysis;
+use super::api_proxy::{fetch_gemini, GeminiRequest};
+use super::search::AppState;
+use crate::auth::AdminUser;
+use crate::error::ApiError;
+use crate::services::cache::{CacheKey, CacheKind};
+use crate::services::geocode::GeocodeLookup;
+use crate::services::llm::CompletionRequest;
+use crate::services::upstream::UpstreamError;
+
+fn database_error(e: sqlx::Error) -> axum::response::Response {
+    tracing::error!("Cache administration query failed: {:?}", e);
+    ApiError::CacheDbError.into_response()
+}
+
+/// GET /admin/cache/stats - Hit/miss counters and entry counts per type
+pub async fn cache_stats(
+    State(state): State<AppState>,
+    AdminUser(_admin): AdminUser,
+) -> impl IntoResponse {
+    let entries = match state.cache.entry_counts().await {
+        Ok(rows) => rows
+            .into_iter()
+            .map(|(r#type, live, expired)| json!({ "type": r#type, "live": live, "expired": expired }))
+            .collect::<Vec<_>>(),
+        Err(e) => return database_error(e),
+    };
+
+    Json(json!({
+        "memory_entries": state.cache.memory_len(),
+        "counters": state.cache.counters(),
+        "entries": entries
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeQuery {
+    pub r#type: Option<String>,
+    pub prefix: Option<String>,
+}
+
+/// DELETE /admin/cache?type=&prefix= - Purges entries by type and/or key prefix
+pub async fn purge_cache(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    Query(params): Query<PurgeQuery>,
+) -> impl IntoResponse {
+    let r#type = params.r#type.filter(|t| !t.is_empty());
+    let prefix = params.prefix.filter(|p| !p.is_empty());
+
+    if r#type.is_none() && prefix.is_none() {
+        return ApiError::CachePurgeFilterRequired.into_response();
+    }
+
+    match state.cache.purge(r#type.as_deref(), prefix.as_deref()).await {
+        Ok(deleted) => {
+            tracing::info!(
+                "Admin {} purged {} cache entries (type={:?}, prefix={:?})",
+                admin.user_id, deleted, r#type, prefix
+            );
+            Json(json!({ "deleted": deleted })).into_response()
+        }
+        Err(e) => database_error(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub key: String,
+}
+
+/// Replays the upstream call recorded with a cache entry.
+async fn refetch(
+    state: &AppState,
+    kind: CacheKind,
+    request: &Value,
+) -> Option<Result<Value, UpstreamError>> {
+    Some(match kind {
+        CacheKind::Geocode => {
+            let lookup: GeocodeLookup = serde_json::from_value(request.clone()).ok()?;
+            state.geocoder.fetch(&lookup).await
+        }
+        CacheKind::Gemini => {
+            let payload: GeminiRequest = serde_json::from_value(request.clone()).ok()?;
+            fetch_gemini(state, &payload).await
+        }
+        CacheKind::Chat => {
+            let request: CompletionRequest = serde_json::from_value(request.clone()).ok()?;
+            fetch_details(state, &request).await
+        }
+        CacheKind::Analysis => run_analysis(state, request["location"].as_str()?).await,
+    })
+}
+
+/// POST /admin/cache/refresh - Re-runs the upstream call behind one key
+pub async fn refresh_cache_entry(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    Json(payload): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    let stored = match state.cache.stored(&payload.key).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            return ApiError::CacheKeyNotFound
+                .with_message(format!("No cache entry with key {}", payload.key))
+                .into_response();
+        }
+        Err(e) => return database_error(e),
+    };
+
+    let not_refreshable = || ApiError::CacheNotRefreshable.into_response();
+
+    let (Some(kind), Some(request)) = (stored.kind, stored.request) else {
+        return not_refreshable();
+    };
+
+    let Some(result) = refetch(&state, kind, &request).await else {
+        return not_refreshable();
+    };
+
+    match result {
+        Ok(data) => {
+            let cache_key = CacheKey {
+                key: payload.key,
+                kind,
+                request,
+            };
+            state.cache.put(&cache_key, &data).await;
+            tracing::info!("Admin {} refreshed cache entry [{}]", admin.user_id, cache_key.key);
+            Json(json!({
+                "key": cache_key.key,
+                "type": kind.as_str(),
+                "refreshed": true,
+                "data": data
+            }))
+            .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
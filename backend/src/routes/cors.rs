@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+use crate::error::{ApiError, REQUEST_ID_HEADER};
+use crate::routes::limits::{REQUESTS_LEFT_HEADER, TOKENS_LEFT_HEADER};
+use crate::routes::maps::TOKEN_HEADER;
+
+// Two policies: public routes (health probes, the error catalog) can be
+// read from any listed origin, while the API only answers the origins in
+// CORS_ALLOWED_ORIGINS. A browser request from any other origin is refused
+// with 403 before it reaches a handler, so a foreign page cannot spend our
+// upstream quota even with a simple request that skips preflight.
+
+/// Request headers browsers may send cross-origin
+const ALLOWED_HEADERS: [HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::CONTENT_TYPE,
+    HeaderName::from_static(REQUEST_ID_HEADER),
+    HeaderName::from_static(TOKEN_HEADER),
+];
+
+/// Response headers scripts may read
+const EXPOSED_HEADERS: [HeaderName; 5] = [
+    HeaderName::from_static(REQUEST_ID_HEADER),
+    HeaderName::from_static("x-cache"),
+    header::RETRY_AFTER,
+    HeaderName::from_static(REQUESTS_LEFT_HEADER),
+    HeaderName::from_static(TOKENS_LEFT_HEADER),
+];
+
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    name: &'static str,
+    /// `None` allows any origin
+    origins: Option<Vec<String>>,
+    methods: Vec<Method>,
+    credentials: bool,
+    max_age: std::time::Duration,
+}
+
+impl CorsPolicy {
+    /// The policy for authenticated and paid routes.
+    pub fn api(config: &CorsConfig) -> Arc<Self> {
+        Arc::new(Self {
+            name: "api",
+            origins: origin_list(&config.allowed_origins),
+            methods: vec![Method::GET, Method::POST, Method::PATCH, Method::DELETE, Method::OPTIONS],
+            credentials: config.allow_credentials,
+            max_age: config.max_age,
+        })
+    }
+
+    /// The policy for read-only public routes. Never sends credentials.
+    pub fn public(config: &CorsConfig) -> Arc<Self> {
+        Arc::new(Self {
+            name: "public",
+            origins: origin_list(&config.public_origins),
+            methods: vec![Method::GET, Method::HEAD, Method::OPTIONS],
+            credentials: false,
+            max_age: config.max_age,
+        })
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        match &self.origins {
+            None => true,
+            Some(origins) => origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+        }
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let origin = match &self.origins {
+            None => AllowOrigin::any(),
+            Some(origins) => AllowOrigin::list(
+                origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            ),
+        };
+
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(self.methods.clone())
+            .allow_headers(ALLOWED_HEADERS)
+            .expose_headers(EXPOSED_HEADERS)
+            .allow_credentials(self.credentials)
+            .max_age(self.max_age)
+    }
+}
+
+fn origin_list(origins: &[String]) -> Option<Vec<String>> {
+    if origins.iter().any(|origin| origin == "*") {
+        None
+    } else {
+        Some(origins.to_vec())
+    }
+}
+
+/// Refuses requests whose `Origin` the policy does not allow. Requests
+/// without an `Origin` (curl, servers) and same-origin requests pass.
+pub async fn enforce(State(policy): State<Arc<CorsPolicy>>, request: Request, next: Next) -> Response {
+    let Some(origin) = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    else {
+        return next.run(request).await;
+    };
+
+    if policy.allows(&origin) || is_same_origin(&request, &origin) {
+        return next.run(request).await;
+    }
+
+    tracing::warn!(
+        "Blocked cross-origin {} {} from {} ({} policy)",
+        request.method(),
+        request.uri().path(),
+        origin,
+        policy.name
+    );
+    ApiError::CorsOriginNotAllowed
+        .with_message(format!("Requests from {} are not allowed", origin))
+        .into_response()
+}
+
+/// The page was served by this host, e.g. the backend's own docs.
+fn is_same_origin(request: &Request, origin: &str) -> bool {
+    let Some(host) = request.headers().get(header::HOST).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    origin
+        .split_once("://")
+        .is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host))
+}
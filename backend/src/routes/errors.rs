@@ -0,0 +1,10 @@
+use axum::{response::IntoResponse, Json};
+use serde_json::json;
+
+use crate::error::catalog;
+
+/// GET /api/errors - Every error code the API can return, for clients
+/// that localize messages by code
+pub async fn list_errors() -> impl IntoResponse {
+    Json(json!({ "errors": catalog() }))
+}
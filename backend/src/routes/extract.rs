@@ -0,0 +1,69 @@
+use axum::{
+    extract::{State, Json},
+    response::IntoResponse,
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::search::AppState;
+use crate::error::ApiError;
+use crate::services::llm::{CompletionRequest, Message, UseCase};
+
+const NO_ADDRESS: &str = "No address found";
+
+// English: "Precise extraction assistant. Output only the address or 'No address found'."
+const SYSTEM_PROMPT: &str = "精确提取助手。仅输出地址或 'No address found'。";
+
+#[derive(Debug, Deserialize)]
+pub struct ExtractAddressRequest {
+    pub text: String,
+}
+
+/// POST /api/extract-address - Pulls the property address out of OCR text
+pub async fn extract_address(
+    State(state): State<AppState>,
+    Json(payload): Json<ExtractAddressRequest>,
+) -> impl IntoResponse {
+    if payload.text.trim().is_empty() {
+        return ApiError::OcrEmptyText.into_response();
+    }
+
+    let request = CompletionRequest {
+        temperature: Some(0.1),
+        ..CompletionRequest::new(vec![
+            Message::new("system", SYSTEM_PROMPT),
+            Message::new("user", ocr_prompt(&payload.text)),
+        ])
+    };
+
+    match state.llm.complete(UseCase::Ocr, &request).await {
+        Ok(completion) => {
+            let address = completion.content.trim().trim_matches('"').trim();
+            let address = (!address.is_empty() && !address.eq_ignore_ascii_case(NO_ADDRESS))
+                .then(|| address.to_string());
+            (StatusCode::OK, Json(json!({ "address": address, "provider": completion.provider }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+// English: "Extract the primary property address from the OCR text below. If
+// there are several, pick the one the document is about (e.g. the property
+// for sale or rent). Return only the address string, no other text. If no
+// address is found, return "No address found"."
+fn ocr_prompt(text: &str) -> String {
+    format!(
+        r#"从以下OCR文本中提取主要房产地址。
+如果发现多个地址，请选择作为文档主题的那个（例如，正在出售或租赁的房产）。
+
+仅返回地址字符串。不要包含其他文本。
+如果未找到地址，返回 "No address found"。
+
+文档文本:
+"""
+{}
+""""#,
+        text
+    )
+}
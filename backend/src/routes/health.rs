@@ -1,7 +1,103 @@
-use axum::{response::IntoResponse, Json};
-use serde_json::json;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
 
-pub async fn health_check() -> impl IntoResponse {
-    const MESSAGE: &str = "API Services";
-    Json(json!({ "status": "ok", "message": MESSAGE }))
+use super::search::AppState;
+use crate::services::circuit::CircuitState;
+
+/// How long readiness waits for a connection and a round-trip
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// GET /health - Reports each upstream's circuit breaker and recent error
+/// rate. `status` is `degraded` while any circuit is not closed.
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let upstreams = state.http.circuits();
+    let degraded = upstreams.iter().any(|c| c.state != CircuitState::Closed);
+
+    Json(json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "upstreams": upstreams
+    }))
+}
+
+/// GET /health/live - The process is up and serving requests.
+pub async fn liveness() -> impl IntoResponse {
+    Json(json!({ "status": "alive" }))
+}
+
+/// GET /health/ready - Checks the database and required configuration.
+/// Returns 503 when either is unhealthy so the instance is taken out of
+/// rotation.
+pub async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    // Read before the round-trip so the check's own connection isn't counted
+    let pool = pool_usage(&state);
+    let database = check_database(&state).await;
+    let config = check_config(&state);
+
+    let ready = database["status"] == "ok" && config["status"] == "ok";
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "checks": {
+                "database": database,
+                "config": config,
+                "pool": pool
+            }
+        })),
+    )
+}
+
+/// A `SELECT 1` round-trip. Also fails when no connection frees up in
+/// time, which is how an exhausted pool shows up.
+async fn check_database(state: &AppState) -> Value {
+    let started = Instant::now();
+    let result = timeout(DB_CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(&state.pool)).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(Ok(_)) => json!({ "status": "ok", "latency_ms": latency_ms }),
+        Ok(Err(e)) => {
+            tracing::error!("Readiness database check failed: {:?}", e);
+            json!({ "status": "error", "latency_ms": latency_ms, "error": "Database query failed" })
+        }
+        Err(_) => {
+            tracing::error!("Readiness database check timed out after {}s", DB_CHECK_TIMEOUT.as_secs());
+            json!({
+                "status": "error",
+                "latency_ms": latency_ms,
+                "error": format!("No database response within {}s", DB_CHECK_TIMEOUT.as_secs())
+            })
+        }
+    }
+}
+
+/// Lists required API keys that are unset or empty.
+fn check_config(state: &AppState) -> Value {
+    let missing = state.config.keys.missing_required();
+
+    if missing.is_empty() {
+        json!({ "status": "ok" })
+    } else {
+        tracing::warn!("Readiness config check failed; missing {}", missing.join(", "));
+        json!({ "status": "error", "missing": missing })
+    }
+}
+
+fn pool_usage(state: &AppState) -> Value {
+    let size = state.pool.size();
+    let idle = state.pool.num_idle() as u32;
+    let max = state.pool.options().get_max_connections();
+    let in_use = size.saturating_sub(idle);
+
+    json!({
+        "size": size,
+        "idle": idle,
+        "in_use": in_use,
+        "max": max,
+        "utilization": if max == 0 { 0.0 } else { in_use as f64 / max as f64 }
+    })
 }
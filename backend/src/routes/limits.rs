@@ -0,0 +1,227 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveDate;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use super::search::AppState;
+use crate::auth::AuthUser;
+use crate::error::ApiError;
+use crate::services::quota::{metered, QuotaCheck, QuotaService, QuotaUsage, Spent, Subject};
+
+pub const REQUESTS_LEFT_HEADER: &str = "x-quota-remaining-requests";
+pub const TOKENS_LEFT_HEADER: &str = "x-quota-remaining-tokens";
+
+/// Requests the caller has left today after the current one, for handlers
+/// that may make many paid upstream calls. `None` when unlimited or unknown.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestsLeft(pub Option<i64>);
+
+/// Rate limits and daily quotas for the routes that spend on paid
+/// upstreams. A valid access token makes the caller the user; anyone else
+/// is limited by client IP. The burst check is in memory and runs first,
+/// so a flood never reaches the database. What the handler spent is
+/// charged after it returns: every completion it made (re-prompts and
+/// failover included) adds the `usage` its provider reported, and every
+/// geocoder cache miss counts as another request, so cache hits cost
+/// nothing extra. Streams are charged from their `done` event instead,
+/// since they finish after the handler.
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let subject = subject(&mut parts, &state).await;
+    let mut request = Request::from_parts(parts, body);
+    let path = request.uri().path().to_string();
+
+    if let Err(wait) = state.limiter.acquire(&subject.to_string()) {
+        tracing::warn!("Rate limited {} on {}", subject, path);
+        return too_many_requests(ApiError::RateLimited, wait, None, &state.quotas);
+    }
+
+    let usage = match state.quotas.record_request(&subject).await {
+        Ok(QuotaCheck::Allowed(usage)) => Some(usage),
+        Ok(QuotaCheck::RequestsExceeded(usage)) => {
+            tracing::warn!("{} used up its daily request quota ({} requests)", subject, usage.requests);
+            return too_many_requests(ApiError::QuotaRequestsExceeded, until_midnight(), Some(&usage), &state.quotas);
+        }
+        Ok(QuotaCheck::TokensExceeded(usage)) => {
+            tracing::warn!("{} used up its daily token quota ({} tokens)", subject, usage.tokens);
+            return too_many_requests(ApiError::QuotaTokensExceeded, until_midnight(), Some(&usage), &state.quotas);
+        }
+        Err(e) => {
+            // Keep serving on the burst limit alone rather than fail every AI call
+            tracing::error!("Quota check for {} failed, allowing the request: {:?}", subject, e);
+            None
+        }
+    };
+
+    let requests_left = usage.as_ref().and_then(|usage| state.quotas.requests_left(usage));
+    request.extensions_mut().insert(RequestsLeft(requests_left));
+
+    let (mut response, spent) = metered(next.run(request)).await;
+    let Some(mut usage) = usage else {
+        return response;
+    };
+
+    usage.requests += spent.lookups;
+    quota_headers(response.headers_mut(), &usage, &state.quotas);
+    if !spent.is_empty() {
+        spawn_charge(state.quotas.clone(), subject.clone(), usage.day, spent);
+    }
+    if is_stream(&response) {
+        response = charge_stream(response, state.quotas.clone(), subject, usage.day);
+    }
+    response
+}
+
+async fn subject(parts: &mut Parts, state: &AppState) -> Subject {
+    if let Ok(user) = AuthUser::from_request_parts(parts, state).await {
+        return Subject::User(user.user_id);
+    }
+
+    let forwarded = state
+        .config
+        .limits
+        .trust_forwarded_for
+        .then(|| parts.headers.get("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        // Each proxy appends the address it saw, so only the last entry was
+        // written by ours; anything before it came from the client
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    Subject::Ip(forwarded.or(peer).unwrap_or([0, 0, 0, 0].into()))
+}
+
+fn too_many_requests(error: ApiError, wait: Duration, usage: Option<&QuotaUsage>, quotas: &QuotaService) -> Response {
+    // Round up so clients never retry a moment too early
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let mut response = error.detail("retry_after", retry_after).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    if let Some(usage) = usage {
+        quota_headers(headers, usage, quotas);
+    }
+    response
+}
+
+fn quota_headers(headers: &mut HeaderMap, usage: &QuotaUsage, quotas: &QuotaService) {
+    if let Some(left) = quotas.requests_left(usage) {
+        headers.insert(REQUESTS_LEFT_HEADER, HeaderValue::from(left));
+    }
+    if let Some(left) = quotas.tokens_left(usage) {
+        headers.insert(TOKENS_LEFT_HEADER, HeaderValue::from(left));
+    }
+}
+
+fn until_midnight() -> Duration {
+    let now = chrono::Utc::now();
+    let midnight = (now.date_naive() + chrono::Days::new(1)).and_hms_opt(0, 0, 0).unwrap().and_utc();
+    (midnight - now).to_std().unwrap_or_default()
+}
+
+fn is_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"text/event-stream"))
+}
+
+/// Charges a stream's tokens when its `done` event goes out.
+fn charge_stream(response: Response, quotas: Arc<QuotaService>, subject: Subject, day: NaiveDate) -> Response {
+    let (parts, body) = response.into_parts();
+    let events = body.into_data_stream().inspect(move |chunk| {
+        if let Ok(chunk) = chunk
+            && let Some(tokens) = done_event_tokens(chunk)
+        {
+            spawn_charge(quotas.clone(), subject.clone(), day, Spent { tokens, lookups: 0 });
+        }
+    });
+    Response::from_parts(parts, Body::from_stream(events))
+}
+
+fn done_event_tokens(chunk: &[u8]) -> Option<i64> {
+    let text = std::str::from_utf8(chunk).ok()?;
+    if !text.lines().any(|line| line == "event: done") {
+        return None;
+    }
+    let data: Value = serde_json::from_str(text.lines().find_map(|line| line.strip_prefix("data: "))?).ok()?;
+    data["usage"]["total_tokens"].as_i64()
+}
+
+fn spawn_charge(quotas: Arc<QuotaService>, subject: Subject, day: NaiveDate, spent: Spent) {
+    tokio::spawn(async move {
+        if let Err(e) = quotas.charge(&subject, day, spent).await {
+            tracing::error!("Failed to charge {:?} to {}: {:?}", spent, subject, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LimitsConfig;
+    use axum::http::StatusCode;
+    use sqlx::postgres::PgPoolOptions;
+
+    /// Only the limits are read; the pool is never used.
+    fn quotas(daily_requests: i64, daily_tokens: i64) -> QuotaService {
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        QuotaService::new(
+            pool,
+            &LimitsConfig {
+                burst: 10,
+                per_minute: 20,
+                daily_requests,
+                daily_tokens,
+                trust_forwarded_for: false,
+            },
+        )
+    }
+
+    fn usage(requests: i64, tokens: i64) -> QuotaUsage {
+        QuotaUsage {
+            subject: "ip:127.0.0.1".to_string(),
+            day: QuotaService::today(),
+            requests,
+            tokens,
+        }
+    }
+
+    #[tokio::test]
+    async fn too_many_requests_rounds_retry_after_up() {
+        let response = too_many_requests(ApiError::RateLimited, Duration::from_millis(1500), None, &quotas(500, 0));
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+        assert!(response.headers().get(REQUESTS_LEFT_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn quota_headers_report_what_is_left() {
+        let mut headers = HeaderMap::new();
+        quota_headers(&mut headers, &usage(120, 600_000), &quotas(500, 500_000));
+
+        assert_eq!(headers[REQUESTS_LEFT_HEADER], "380");
+        // Overspent tokens show as none left, not a negative count
+        assert_eq!(headers[TOKENS_LEFT_HEADER], "0");
+    }
+
+    #[tokio::test]
+    async fn quota_headers_are_left_out_for_disabled_quotas() {
+        let mut headers = HeaderMap::new();
+        quota_headers(&mut headers, &usage(120, 600), &quotas(0, 0));
+
+        assert!(headers.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::search::AppState;
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorResponse};
+use crate::services::http::{TransportError, Upstream};
+use crate::services::maps_token::MapsClaims;
+
+/// Circuit breaker name for Google Maps
+pub(super) const GOOGLE_MAPS: &str = "google_maps";
+
+pub const TOKEN_HEADER: &str = "x-maps-token";
+
+const MAP_TYPES: [&str; 4] = ["roadmap", "satellite", "terrain", "hybrid"];
+const MAX_STATIC_SIZE: u32 = 640;
+const MAX_TEXT_LEN: usize = 200;
+const MAX_MARKERS_LEN: usize = 500;
+const PLACE_FIELDS: &str = "place_id,name,formatted_address,geometry/location";
+
+// ============ Origins and Tokens ============
+
+/// The page origin behind a request: the `Origin` header, or the origin of
+/// the `Referer` for requests that don't send one (such as `<img>` loads).
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(origin) = value(header::ORIGIN)
+        && origin != "null"
+    {
+        return Some(origin.trim_end_matches('/').to_lowercase());
+    }
+
+    // Opaque origins (file:, data:) serialize as "null"
+    let origin = Url::parse(value(header::REFERER)?).ok()?.origin().ascii_serialization();
+    (origin != "null").then(|| origin.to_lowercase())
+}
+
+/// Checks the map token sent in `X-Maps-Token` or the `token` parameter.
+fn authorize(state: &AppState, headers: &HeaderMap, token: Option<&str>) -> Result<MapsClaims, ApiError> {
+    let token = headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(token)
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or(ApiError::MapsTokenMissing)?;
+
+    state.maps.verify(token, request_origin(headers).as_deref())
+}
+
+fn maps_key(state: &AppState) -> Result<&str, ApiError> {
+    match state.config.keys.google_maps.as_deref() {
+        Some("") => {
+            tracing::error!("GOOGLE_MAPS_API_KEY is empty");
+            Err(ApiError::MapsKeyEmpty)
+        }
+        Some(key) => Ok(key),
+        None => {
+            tracing::error!("GOOGLE_MAPS_API_KEY not found in environment");
+            Err(ApiError::MapsKeyMissing)
+        }
+    }
+}
+
+/// POST /api/maps/token - Signs a short-lived map token for the caller's origin
+pub async fn issue_maps_token(
+    State(state): State<AppState>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(origin) = request_origin(&headers) else {
+        return ApiError::MapsOriginRequired.into_response();
+    };
+
+    // A Referer-only request skips the CORS check, so look again here
+    let allowed = &state.config.cors.allowed_origins;
+    if !allowed.is_empty() && !allowed.iter().any(|o| o == "*" || *o == origin) {
+        tracing::warn!("User {} asked for a map token from unknown origin {}", user.user_id, origin);
+        return ApiError::MapsOriginNotAllowed.into_response();
+    }
+
+    Json(state.maps.issue(user.user_id, &origin)).into_response()
+}
+
+/// GET /api/maps/config - Returns the raw Google Maps key, for allow-listed origins only
+pub async fn get_maps_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(origin) = request_origin(&headers) else {
+        return ApiError::MapsOriginRequired.into_response();
+    };
+
+    if !state.config.maps.key_origins.contains(&origin) {
+        tracing::warn!("Refused the Google Maps key to origin {}", origin);
+        return ApiError::MapsOriginNotAllowed
+            .with_message(format!(
+                "{} is not listed in MAPS_KEY_ORIGINS. Use POST /api/maps/token and the map proxy instead.",
+                origin
+            ))
+            .into_response();
+    }
+
+    match maps_key(&state) {
+        Ok(api_key) => {
+            tracing::info!("Handed the Google Maps key to allow-listed origin {}", origin);
+            Json(json!({ "apiKey": api_key })).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============ Static Maps Proxy ============
+
+#[derive(Debug, Deserialize)]
+pub struct StaticMapQuery {
+    pub token: Option<String>,
+    pub center: String,
+    pub zoom: Option<u8>,
+    /// `WIDTHxHEIGHT`, each at most 640
+    pub size: Option<String>,
+    pub scale: Option<u8>,
+    pub maptype: Option<String>,
+    pub markers: Option<String>,
+}
+
+impl StaticMapQuery {
+    /// The Static Maps parameters, minus the key, or why they are invalid.
+    fn params(&self) -> Result<Vec<(&'static str, String)>, String> {
+        let center = self.center.trim();
+        if center.is_empty() || center.len() > MAX_TEXT_LEN {
+            return Err(format!("'center' must be 1 to {} characters", MAX_TEXT_LEN));
+        }
+
+        let zoom = self.zoom.unwrap_or(15);
+        if zoom > 21 {
+            return Err("'zoom' must be between 0 and 21".to_string());
+        }
+
+        let size = self.size.as_deref().unwrap_or("600x300");
+        let dimensions = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+        if !matches!(dimensions, Some((w, h)) if (1..=MAX_STATIC_SIZE).contains(&w) && (1..=MAX_STATIC_SIZE).contains(&h)) {
+            return Err(format!("'size' must be WIDTHxHEIGHT with each side 1 to {}", MAX_STATIC_SIZE));
+        }
+
+        let mut params = vec![
+            ("center", center.to_string()),
+            ("zoom", zoom.to_string()),
+            ("size", size.to_string()),
+        ];
+
+        if let Some(scale) = self.scale {
+            if !matches!(scale, 1 | 2) {
+                return Err("'scale' must be 1 or 2".to_string());
+            }
+            params.push(("scale", scale.to_string()));
+        }
+        if let Some(maptype) = &self.maptype {
+            if !MAP_TYPES.contains(&maptype.as_str()) {
+                return Err(format!("'maptype' must be one of: {}", MAP_TYPES.join(", ")));
+            }
+            params.push(("maptype", maptype.clone()));
+        }
+        if let Some(markers) = &self.markers {
+            if markers.len() > MAX_MARKERS_LEN {
+                return Err(format!("'markers' must be at most {} characters", MAX_MARKERS_LEN));
+            }
+            params.push(("markers", markers.clone()));
+        }
+        Ok(params)
+    }
+}
+
+/// GET /api/maps/static - Proxies a Google Static Maps image
+pub async fn static_map(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<StaticMapQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = authorize(&state, &headers, params.token.as_deref()) {
+        return e.into_response();
+    }
+
+    let query = match params.params() {
+        Ok(query) => query,
+        Err(message) => return ApiError::MapsInvalidParams.with_message(message).into_response(),
+    };
+
+    let response = match send_maps(&state, "staticmap", query).await {
+        Ok(response) => response,
+        Err(e) => return e.into_response(),
+    };
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("image/png")
+        .to_string();
+
+    match response.bytes().await {
+        Ok(image) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "private, max-age=3600".to_string()),
+            ],
+            image,
+        )
+            .into_response(),
+        Err(e) => maps_transport_error(&TransportError::from_reqwest(e, state.http.timeout(Upstream::Maps)))
+            .into_response(),
+    }
+}
+
+// ============ Places Proxy ============
+
+#[derive(Debug, Deserialize)]
+pub struct PlacesQuery {
+    pub token: Option<String>,
+    pub input: String,
+    /// `lat,lng` to prefer places near that point
+    pub near: Option<String>,
+}
+
+/// GET /api/maps/places - Proxies a Google Places "find place" lookup
+pub async fn find_place(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<PlacesQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = authorize(&state, &headers, params.token.as_deref()) {
+        return e.into_response();
+    }
+
+    let input = params.input.trim();
+    if input.is_empty() || input.len() > MAX_TEXT_LEN {
+        return ApiError::MapsInvalidParams
+            .with_message(format!("'input' must be 1 to {} characters", MAX_TEXT_LEN))
+            .into_response();
+    }
+
+    let mut query = vec![
+        ("input", input.to_string()),
+        ("inputtype", "textquery".to_string()),
+        ("fields", PLACE_FIELDS.to_string()),
+    ];
+    if let Some(near) = &params.near {
+        let point = near
+            .split_once(',')
+            .and_then(|(lat, lng)| Some((lat.trim().parse::<f64>().ok()?, lng.trim().parse::<f64>().ok()?)))
+            .filter(|(lat, lng)| lat.abs() <= 90.0 && lng.abs() <= 180.0);
+        let Some((lat, lng)) = point else {
+            return ApiError::MapsInvalidParams
+                .with_message("'near' must be 'lat,lng' with lat in -90..90 and lng in -180..180")
+                .into_response();
+        };
+        query.push(("locationbias", format!("point:{},{}", lat, lng)));
+    }
+
+    let response = match send_maps(&state, "place/findplacefromtext/json", query).await {
+        Ok(response) => response,
+        Err(e) => return e.into_response(),
+    };
+
+    let body = match response.json::<Value>().await {
+        Ok(body) => body,
+        Err(e) if e.is_timeout() => {
+            return maps_transport_error(&TransportError::Timeout(state.http.timeout(Upstream::Maps))).into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to parse Google Places response: {:?}", e);
+            return ApiError::MapsParseError.into_response();
+        }
+    };
+
+    // Places reports failures in `status` on a 200 response
+    let message = body["error_message"].as_str().unwrap_or_default().to_string();
+    let error = match body["status"].as_str() {
+        Some("OK") | Some("ZERO_RESULTS") => {
+            return Json(json!({ "candidates": body["candidates"], "status": body["status"] })).into_response();
+        }
+        Some("OVER_QUERY_LIMIT") => ApiError::MapsRateLimit,
+        Some("REQUEST_DENIED") => ApiError::MapsRequestDenied,
+        Some("INVALID_REQUEST") => ApiError::MapsInvalidParams,
+        _ => ApiError::MapsError,
+    };
+    tracing::error!("Google Places returned {}: {}", body["status"], message);
+
+    if message.is_empty() {
+        error.into_response()
+    } else {
+        error.with_message(message).into_response()
+    }
+}
+
+// ============ Upstream ============
+
+/// Calls a Google Maps endpoint with the server's key appended, mapping
+/// any non-success status to the `MAPS_*` codes.
+async fn send_maps(
+    state: &AppState,
+    path: &str,
+    query: Vec<(&'static str, String)>,
+) -> Result<reqwest::Response, ErrorResponse> {
+    let api_key = maps_key(state)?;
+    let url = Url::parse_with_params(
+        &format!("{}/{}", state.config.upstreams.google_maps_base_url, path),
+        query.iter().map(|(k, v)| (*k, v.as_str())).chain([("key", api_key)]),
+    )
+    .map_err(|e| {
+        tracing::error!("Invalid Google Maps URL: {}", e);
+        ErrorResponse::from(ApiError::InternalError)
+    })?;
+
+    let breaker = state.http.breaker(GOOGLE_MAPS);
+    let request = state.http.client().get(url);
+    let response = state
+        .http
+        .send(Upstream::Maps, &breaker, request)
+        .await
+        .map_err(|e| maps_transport_error(&e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+    tracing::error!("Google Maps error ({}): {}", status, error_text);
+
+    let error = match status.as_u16() {
+        403 => ApiError::MapsRequestDenied,
+        429 => ApiError::MapsRateLimit,
+        _ => ApiError::MapsError,
+    };
+    Err(error
+        .with_message(error_text)
+        .status(StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)))
+}
+
+/// Maps a Google Maps call that never got (or lost) its response.
+fn maps_transport_error(e: &TransportError) -> ErrorResponse {
+    if let TransportError::CircuitOpen(retry_in) = e {
+        return ApiError::MapsCircuitOpen
+            .with_message(format!("Not calling Google Maps: {}", e))
+            .detail("retry_after", retry_in.as_secs());
+    }
+
+    tracing::error!("Failed to call Google Maps: {:?}", e);
+
+    let error = if e.is_timeout() {
+        ApiError::MapsTimeout
+    } else if e.is_connect() {
+        ApiError::MapsConnectionError
+    } else {
+        ApiError::MapsServiceError
+    };
+    error.with_message(format!("Failed to reach Google Maps: {}", e))
+}
@@ -2,27 +2,112 @@ mod health;
 mod search;
 mod ai_chat;
 mod api_proxy;
+mod analyze;
+mod cache_admin;
+mod cors;
+mod errors;
+mod extract;
+mod limits;
+pub mod maps;
+mod quota_admin;
+mod suggest;
 
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
 use sqlx::PgPool;
-use tower_http::cors::CorsLayer;
+use std::sync::Arc;
 
-use self::search::AppState;
+use crate::auth::JwtVerifier;
+use crate::config::Config;
+use crate::error;
+use crate::services::cache::CacheService;
+use crate::services::geocode::GeocodeRouter;
+use crate::services::http::HttpClient;
+use crate::services::llm::LlmRouter;
+use crate::services::maps_token::MapsTokens;
+use crate::services::quota::QuotaService;
+use crate::services::rate_limit::RateLimiter;
 
-pub fn create_router(pool: PgPool) -> Router {
-    let state = AppState { pool };
+pub use self::cors::CorsPolicy;
+pub use self::search::AppState;
 
-    Router::new()
+pub fn create_router(config: Config, pool: PgPool, auth: JwtVerifier) -> Router {
+    let config = Arc::new(config);
+    let http = Arc::new(HttpClient::new(&config.http, &config.circuit));
+    // Registered up front so /health lists it before its first call; the
+    // geocoders register theirs as they are built
+    http.breaker(maps::GOOGLE_MAPS);
+    let state = AppState {
+        cache: Arc::new(CacheService::new(pool.clone(), &config.cache)),
+        quotas: Arc::new(QuotaService::new(pool.clone(), &config.limits)),
+        pool,
+        auth: Arc::new(auth),
+        llm: Arc::new(LlmRouter::new(&config, http.clone())),
+        geocoder: Arc::new(GeocodeRouter::new(&config, http.clone())),
+        http,
+        maps: Arc::new(MapsTokens::new(&config)),
+        limiter: Arc::new(RateLimiter::new(&config.limits)),
+        config: config.clone(),
+    };
+
+    state.cache.clone().spawn_sweeper(config.cache.sweep_interval);
+
+    let public_cors = CorsPolicy::public(&config.cors);
+    let public = Router::new()
         .route("/health", get(health::health_check))
-        .route("/search", post(search::create_search_history))
-        .route("/search", get(search::get_recent_searches))
+        .route("/health/live", get(health::liveness))
+        .route("/health/ready", get(health::readiness))
+        .route("/api/errors", get(errors::list_errors))
+        .layer(public_cors.layer())
+        .layer(middleware::from_fn_with_state(public_cors, cors::enforce));
+
+    // Routes that spend AI tokens or can fan out into many paid lookups
+    let limited = Router::new()
         .route("/api/details", post(ai_chat::get_details))
-        .route("/api/maps/config", get(api_proxy::get_maps_config))
-        .route("/api/geocode", get(api_proxy::geocode_address))
         .route("/api/gemini", post(api_proxy::gemini_generate))
-        .layer(CorsLayer::permissive())
+        .route("/api/analyze", post(analyze::analyze_property))
+        .route("/api/extract-address", post(extract::extract_address))
+        .route("/api/geocode/batch", post(api_proxy::batch_geocode))
+        .route("/api/geocode/suggest", get(suggest::suggest))
+        .route_layer(middleware::from_fn_with_state(state.clone(), limits::limit));
+
+    let api_cors = CorsPolicy::api(&config.cors);
+    let api = Router::new()
+        .route(
+            "/search",
+            get(search::get_recent_searches)
+                .post(search::create_search_history)
+                .delete(search::clear_searches),
+        )
+        .route(
+            "/search/:id",
+            get(search::get_search)
+                .patch(search::update_search)
+                .delete(search::delete_search),
+        )
+        .route("/api/maps/token", post(maps::issue_maps_token))
+        .route("/api/maps/config", get(maps::get_maps_config))
+        .route("/api/maps/static", get(maps::static_map))
+        .route("/api/maps/places", get(maps::find_place))
+        .route("/api/geocode", get(api_proxy::geocode_address))
+        .route("/api/reverse-geocode", get(api_proxy::reverse_geocode))
+        .route("/admin/cache", delete(cache_admin::purge_cache))
+        .route("/admin/cache/stats", get(cache_admin::cache_stats))
+        .route("/admin/cache/refresh", post(cache_admin::refresh_cache_entry))
+        .route("/admin/quotas", get(quota_admin::list_quotas))
+        .route(
+            "/admin/quotas/:subject",
+            get(quota_admin::get_quota).delete(quota_admin::reset_quota),
+        )
+        .merge(limited)
+        .layer(api_cors.layer())
+        .layer(middleware::from_fn_with_state(api_cors, cors::enforce));
+
+    public
+        .merge(api)
+        .layer(middleware::from_fn(error::request_id))
         .with_state(state)
 }
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use super::search::AppState;
+use crate::auth::AdminUser;
+use crate::error::ApiError;
+use crate::services::quota::{QuotaUsage, Subject};
+
+/// Callers listed by GET /admin/quotas
+const TOP_CALLERS: i64 = 100;
+
+fn database_error(e: sqlx::Error) -> axum::response::Response {
+    tracing::error!("Quota administration query failed: {:?}", e);
+    ApiError::QuotaDbError.into_response()
+}
+
+fn usage_json(state: &AppState, usage: &QuotaUsage) -> serde_json::Value {
+    json!({
+        "subject": usage.subject,
+        "day": usage.day,
+        "requests": usage.requests,
+        "tokens": usage.tokens,
+        "requests_left": state.quotas.requests_left(usage),
+        "tokens_left": state.quotas.tokens_left(usage)
+    })
+}
+
+/// GET /admin/quotas - Today's heaviest callers and the configured limits
+pub async fn list_quotas(
+    State(state): State<AppState>,
+    AdminUser(_admin): AdminUser,
+) -> impl IntoResponse {
+    let (daily_requests, daily_tokens) = state.quotas.limits();
+    match state.quotas.top_usage(TOP_CALLERS).await {
+        Ok(rows) => Json(json!({
+            "daily_requests": daily_requests,
+            "daily_tokens": daily_tokens,
+            "usage": rows.iter().map(|usage| usage_json(&state, usage)).collect::<Vec<_>>()
+        }))
+        .into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// GET /admin/quotas/:subject - Today's usage for `user:<uuid>` or `ip:<address>`
+pub async fn get_quota(
+    State(state): State<AppState>,
+    AdminUser(_admin): AdminUser,
+    Path(subject): Path<String>,
+) -> impl IntoResponse {
+    let Ok(subject) = subject.parse::<Subject>() else {
+        return ApiError::QuotaSubjectInvalid.into_response();
+    };
+
+    match state.quotas.usage(&subject).await {
+        Ok(usage) => Json(usage_json(&state, &usage)).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// DELETE /admin/quotas/:subject - Clears today's usage and refills the rate limit
+pub async fn reset_quota(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    Path(subject): Path<String>,
+) -> impl IntoResponse {
+    let Ok(subject) = subject.parse::<Subject>() else {
+        return ApiError::QuotaSubjectInvalid.into_response();
+    };
+
+    state.limiter.reset(&subject.to_string());
+    match state.quotas.reset(&subject).await {
+        Ok(had_usage) => {
+            tracing::info!("Admin {} reset the quota of {}", admin.user_id, subject);
+            Json(json!({ "subject": subject.to_string(), "reset": had_usage })).into_response()
+        }
+        Err(e) => database_error(e),
+    }
+}
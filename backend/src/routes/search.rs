@@ -1,23 +1,45 @@
 use axum::{
-    extract::{State, Json},
+    extract::{State, Json, Path, Query},
     response::IntoResponse,
+    http::StatusCode,
 };
-use sqlx::{PgPool, types::BigDecimal};
-use serde::Deserialize;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder, types::BigDecimal};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::auth::{AuthUser, JwtVerifier};
+use crate::config::Config;
+use crate::error::ApiError;
 use crate::models::search_history::SearchHistory;
+use crate::services::cache::CacheService;
+use crate::services::geocode::GeocodeRouter;
+use crate::services::http::HttpClient;
+use crate::services::llm::LlmRouter;
+use crate::services::maps_token::MapsTokens;
+use crate::services::quota::QuotaService;
+use crate::services::rate_limit::RateLimiter;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
+    pub auth: Arc<JwtVerifier>,
+    pub cache: Arc<CacheService>,
+    pub llm: Arc<LlmRouter>,
+    pub http: Arc<HttpClient>,
+    pub config: Arc<Config>,
+    pub maps: Arc<MapsTokens>,
+    pub limiter: Arc<RateLimiter>,
+    pub quotas: Arc<QuotaService>,
+    pub geocoder: Arc<GeocodeRouter>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateSearchHistoryRequest {
     pub location_name: String,
-    pub user_id: Option<Uuid>,
     pub risk_score: Option<i32>,
     pub search_data: Option<Value>,
     pub latitude: Option<BigDecimal>,
@@ -26,8 +48,10 @@ pub struct CreateSearchHistoryRequest {
     pub state: Option<String>,
 }
 
+/// POST /search - Records a search for the authenticated user
 pub async fn create_search_history(
     State(state): State<AppState>,
+    user: AuthUser,
     Json(payload): Json<CreateSearchHistoryRequest>,
 ) -> impl IntoResponse {
     let result = sqlx::query_as::<_, SearchHistory>(
@@ -40,7 +64,7 @@ pub async fn create_search_history(
         RETURNING *
         "#
     )
-    .bind(payload.user_id)
+    .bind(user.user_id)
     .bind(payload.location_name)
     .bind(payload.risk_score)
     .bind(payload.search_data)
@@ -52,36 +76,374 @@ pub async fn create_search_history(
     .await;
 
     match result {
-        Ok(record) => (axum::http::StatusCode::CREATED, Json(json!(record))).into_response(),
-        Err(e) => {
-            tracing::error!("Failed to create search history: {:?}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to create record"})),
-            )
-                .into_response()
+        Ok(record) => (StatusCode::CREATED, Json(json!(record))).into_response(),
+        Err(e) => database_error("create", e),
+    }
+}
+
+// ============ Listing ============
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchHistoryQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub min_risk: Option<i32>,
+    pub max_risk: Option<i32>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    Newest,
+    Oldest,
+    RiskDesc,
+    RiskAsc,
+}
+
+impl SortOrder {
+    fn parse(value: Option<&str>) -> Option<Self> {
+        match value.unwrap_or("newest") {
+            "newest" => Some(SortOrder::Newest),
+            "oldest" => Some(SortOrder::Oldest),
+            "risk_desc" => Some(SortOrder::RiskDesc),
+            "risk_asc" => Some(SortOrder::RiskAsc),
+            _ => None,
+        }
+    }
+
+    /// Column expression the keyset is built on. Nulls are folded to a
+    /// sentinel so rows without a score still page deterministically.
+    fn key_expr(self) -> &'static str {
+        match self {
+            SortOrder::Newest | SortOrder::Oldest => "COALESCE(created_at, to_timestamp(0))",
+            SortOrder::RiskDesc | SortOrder::RiskAsc => "COALESCE(risk_score, -1)",
+        }
+    }
+
+    fn descending(self) -> bool {
+        matches!(self, SortOrder::Newest | SortOrder::RiskDesc)
+    }
+}
+
+/// Position of the last row on a page, handed back to the client as an
+/// opaque base64 token.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: SortOrder,
+    created_at: Option<DateTime<Utc>>,
+    risk_score: Option<i32>,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn after(sort: SortOrder, record: &SearchHistory) -> Self {
+        Cursor {
+            sort,
+            created_at: record.created_at,
+            risk_score: record.risk_score,
+            id: record.id,
         }
     }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// Escapes LIKE wildcards so user input only ever matches literally.
+pub(super) fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
 }
 
+/// GET /search - Pages through the authenticated user's search history
 pub async fn get_recent_searches(
     State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<SearchHistoryQuery>,
 ) -> impl IntoResponse {
+    let Some(sort) = SortOrder::parse(params.sort.as_deref()) else {
+        return ApiError::InvalidSort.into_response();
+    };
+
+    let cursor = match params.cursor.as_deref() {
+        Some(token) => match Cursor::decode(token) {
+            Some(cursor) if cursor.sort == sort => Some(cursor),
+            _ => {
+                return ApiError::InvalidCursor.into_response();
+            }
+        },
+        None => None,
+    };
+
+    if let (Some(min), Some(max)) = (params.min_risk, params.max_risk)
+        && min > max
+    {
+        return ApiError::InvalidRange
+            .with_message("'min_risk' cannot be greater than 'max_risk'")
+            .into_response();
+    }
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from > to
+    {
+        return ApiError::InvalidRange
+            .with_message("'from' cannot be later than 'to'")
+            .into_response();
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM search_history WHERE user_id = ");
+    query.push_bind(user.user_id);
+
+    if let Some(city) = params.city.filter(|c| !c.trim().is_empty()) {
+        query.push(" AND lower(city) = lower(").push_bind(city.trim().to_string()).push(")");
+    }
+    if let Some(region) = params.state.filter(|s| !s.trim().is_empty()) {
+        query.push(" AND lower(state) = lower(").push_bind(region.trim().to_string()).push(")");
+    }
+    if let Some(min) = params.min_risk {
+        query.push(" AND risk_score >= ").push_bind(min);
+    }
+    if let Some(max) = params.max_risk {
+        query.push(" AND risk_score <= ").push_bind(max);
+    }
+    if let Some(from) = params.from {
+        query.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = params.to {
+        query.push(" AND created_at <= ").push_bind(to);
+    }
+    if let Some(text) = params.q.filter(|q| !q.trim().is_empty()) {
+        query
+            .push(" AND location_name ILIKE ")
+            .push_bind(like_pattern(text.trim()));
+    }
+
+    let key = sort.key_expr();
+    let (cmp, dir) = if sort.descending() { ("<", "DESC") } else { (">", "ASC") };
+
+    if let Some(cursor) = cursor {
+        query.push(format!(" AND ({}, id) {} (", key, cmp));
+        match sort {
+            SortOrder::Newest | SortOrder::Oldest => {
+                query.push_bind(cursor.created_at.unwrap_or(DateTime::<Utc>::UNIX_EPOCH));
+            }
+            SortOrder::RiskDesc | SortOrder::RiskAsc => {
+                query.push_bind(cursor.risk_score.unwrap_or(-1));
+            }
+        }
+        query.push(", ").push_bind(cursor.id).push(")");
+    }
+
+    query.push(format!(" ORDER BY {} {}, id {} LIMIT ", key, dir, dir));
+    query.push_bind(limit + 1);
+
+    let result = query
+        .build_query_as::<SearchHistory>()
+        .fetch_all(&state.pool)
+        .await;
+
+    match result {
+        Ok(mut records) => {
+            let has_more = records.len() as i64 > limit;
+            records.truncate(limit as usize);
+
+            let next_cursor = if has_more {
+                records.last().map(|last| Cursor::after(sort, last).encode())
+            } else {
+                None
+            };
+
+            Json(json!({
+                "items": records,
+                "next_cursor": next_cursor
+            }))
+            .into_response()
+        }
+        Err(e) => database_error("fetch", e),
+    }
+}
+
+// ============ Single Record ============
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSearchHistoryRequest {
+    pub location_name: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub notes: Option<String>,
+    /// Shallow-merged into the stored `search_data` object
+    pub search_data: Option<Value>,
+}
+
+fn not_found(id: Uuid) -> axum::response::Response {
+    ApiError::SearchNotFound
+        .with_message(format!("No search history entry with id {}", id))
+        .into_response()
+}
+
+fn database_error(action: &str, e: sqlx::Error) -> axum::response::Response {
+    tracing::error!("Failed to {} search history: {:?}", action, e);
+    ApiError::SearchDbError
+        .with_message(format!("Failed to {} search history", action))
+        .into_response()
+}
+
+/// Loads a row and checks it belongs to the caller: 404 if it doesn't
+/// exist, 403 if it belongs to someone else.
+async fn fetch_owned(
+    pool: &PgPool,
+    id: Uuid,
+    user: &AuthUser,
+) -> Result<SearchHistory, axum::response::Response> {
+    let record = sqlx::query_as::<_, SearchHistory>("SELECT * FROM search_history WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| database_error("fetch", e))?
+        .ok_or_else(|| not_found(id))?;
+
+    if record.user_id != Some(user.user_id) {
+        tracing::warn!("User {} attempted to access search {} they do not own", user.user_id, id);
+        return Err(ApiError::SearchForbidden.into_response());
+    }
+
+    Ok(record)
+}
+
+/// GET /search/:id - Returns one of the caller's searches
+pub async fn get_search(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match fetch_owned(&state.pool, id, &user).await {
+        Ok(record) => Json(json!(record)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// PATCH /search/:id - Renames a search or attaches notes/extra data to it
+pub async fn update_search(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateSearchHistoryRequest>,
+) -> impl IntoResponse {
+    if payload.location_name.is_none()
+        && payload.city.is_none()
+        && payload.state.is_none()
+        && payload.notes.is_none()
+        && payload.search_data.is_none()
+    {
+        return ApiError::EmptyUpdate.into_response();
+    }
+
+    if let Some(name) = &payload.location_name
+        && name.trim().is_empty()
+    {
+        return ApiError::InvalidLocationName.into_response();
+    }
+
+    if let Some(data) = &payload.search_data
+        && !data.is_object()
+    {
+        return ApiError::InvalidSearchData.into_response();
+    }
+
+    if let Err(response) = fetch_owned(&state.pool, id, &user).await {
+        return response;
+    }
+
+    let mut patch = payload.search_data.unwrap_or_else(|| json!({}));
+    if let Some(notes) = payload.notes {
+        patch["notes"] = json!(notes);
+    }
+
     let result = sqlx::query_as::<_, SearchHistory>(
-        "SELECT * FROM search_history ORDER BY created_at DESC LIMIT 10"
+        r#"
+        UPDATE search_history SET
+            location_name = COALESCE($3, location_name),
+            city = COALESCE($4, city),
+            state = COALESCE($5, state),
+            search_data = COALESCE(search_data, '{}'::jsonb) || $6,
+            updated_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#
     )
-    .fetch_all(&state.pool)
+    .bind(id)
+    .bind(user.user_id)
+    .bind(payload.location_name.map(|name| name.trim().to_string()))
+    .bind(payload.city)
+    .bind(payload.state)
+    .bind(patch)
+    .fetch_optional(&state.pool)
     .await;
 
     match result {
-        Ok(records) => Json(json!(records)).into_response(),
-        Err(e) => {
-            tracing::error!("Failed to fetch search history: {:?}", e);
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to fetch records"})),
-            )
-                .into_response()
+        Ok(Some(record)) => Json(json!(record)).into_response(),
+        Ok(None) => not_found(id),
+        Err(e) => database_error("update", e),
+    }
+}
+
+/// DELETE /search/:id - Removes one of the caller's searches
+pub async fn delete_search(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    if let Err(response) = fetch_owned(&state.pool, id, &user).await {
+        return response;
+    }
+
+    let result = sqlx::query("DELETE FROM search_history WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user.user_id)
+        .execute(&state.pool)
+        .await;
+
+    match result {
+        Ok(done) if done.rows_affected() == 0 => not_found(id),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => database_error("delete", e),
+    }
+}
+
+/// DELETE /search - Clears the caller's entire search history
+pub async fn clear_searches(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM search_history WHERE user_id = $1")
+        .bind(user.user_id)
+        .execute(&state.pool)
+        .await;
+
+    match result {
+        Ok(done) => {
+            tracing::info!("Cleared {} search history entries for user {}", done.rows_affected(), user.user_id);
+            Json(json!({ "deleted": done.rows_affected() })).into_response()
         }
+        Err(e) => database_error("delete", e),
     }
 }
@@ -0,0 +1,288 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::api_proxy::parse_coordinates;
+use super::search::{like_pattern, AppState};
+use crate::auth::AuthUser;
+use crate::error::ApiError;
+use crate::models::geocode::{Coordinates, GeocodeResult, Suggestion};
+use crate::services::cache::{normalize_text, CacheStatus, CACHE_HEADER};
+use crate::services::geocode::{cached, from_cached, GeocodeLookup};
+use crate::services::quota::add_lookup;
+use crate::services::upstream::UpstreamError;
+
+/// Past searches considered for each query
+const HISTORY_CANDIDATES: i64 = 20;
+/// Added to the score of a place the user has searched for before
+const HISTORY_BOOST: f64 = 1.0;
+/// Distance from `near` at which the proximity bonus is halved
+const PROXIMITY_SCALE_KM: f64 = 50.0;
+/// A geocoder result this close to a past search is the same place
+const SAME_PLACE_KM: f64 = 0.5;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestRequest {
+    pub q: String,
+    /// Bias point as "lat,lng"
+    pub near: Option<String>,
+    pub language: Option<String>,
+}
+
+/// GET /api/geocode/suggest - Type-ahead suggestions for a partial address.
+/// A shorter prefix already fetched is filtered instead of calling the
+/// geocoder again, results are ranked by distance from `near`, and a
+/// signed-in user's past searches are boosted.
+pub async fn suggest(
+    State(state): State<AppState>,
+    user: Option<AuthUser>,
+    Query(params): Query<SuggestRequest>,
+) -> impl IntoResponse {
+    let query = normalize_text(&params.q);
+    if query.is_empty() {
+        return ApiError::InvalidQuery.into_response();
+    }
+    let max_chars = state.config.geocode.suggest_max_chars;
+    if query.chars().count() > max_chars {
+        return ApiError::InvalidQuery
+            .with_message(format!("The 'q' parameter may be at most {} characters", max_chars))
+            .into_response();
+    }
+
+    let near = match params.near.as_deref() {
+        Some(near) => {
+            let point = near
+                .split_once(',')
+                .and_then(|(lat, lng)| parse_coordinates(Some(lat), Some(lng)));
+            let Some((lat, lng)) = point else {
+                return ApiError::InvalidCoordinates
+                    .with_message("'near' must be \"lat,lng\" with lat from -90 to 90 and lng from -180 to 180")
+                    .into_response();
+            };
+            Some(Coordinates { lat, lng })
+        }
+        None => None,
+    };
+
+    let history = match &user {
+        Some(user) => past_searches(&state, user.user_id, &query).await,
+        None => Vec::new(),
+    };
+
+    // Too short to geocode usefully; past searches can still match
+    let (places, status, prefix) = if query.chars().count() < state.config.geocode.suggest_min_chars {
+        (Vec::new(), CacheStatus::Bypass, None)
+    } else {
+        match geocoder_places(&state, &query, params.language).await {
+            Ok(found) => found,
+            // Past searches are still worth showing without the geocoder
+            Err(e) if !history.is_empty() => {
+                tracing::warn!("Suggestions for '{}' fell back to history: {:?}", query, e.body);
+                (Vec::new(), CacheStatus::Miss, None)
+            }
+            Err(e) => return e.into_response(),
+        }
+    };
+
+    let suggestions = rank(places, history, near, state.config.geocode.suggest_limit);
+
+    (
+        StatusCode::OK,
+        [(CACHE_HEADER, status.header_value())],
+        Json(json!({
+            "query": query,
+            "prefix": prefix,
+            "suggestions": suggestions
+        })),
+    )
+        .into_response()
+}
+
+/// Geocoder results for `query`. The query itself, then the longest cached
+/// prefix, is looked for in the in-memory cache; the prefix is used when
+/// some of its results still match what has been typed since, so most
+/// keystrokes never reach the provider. Returns the prefix that was reused,
+/// if any.
+async fn geocoder_places(
+    state: &AppState,
+    query: &str,
+    language: Option<String>,
+) -> Result<(Vec<GeocodeResult>, CacheStatus, Option<String>), UpstreamError> {
+    let config = &state.config.geocode;
+    let lookup = |q: &str| GeocodeLookup::Forward {
+        q: q.to_string(),
+        language: language.clone(),
+    };
+
+    if let Some(geocoded) = state.cache.peek_memory(&lookup(query).cache_key().key).and_then(from_cached) {
+        return Ok((geocoded.results, CacheStatus::Hit, None));
+    }
+
+    let mut prefixes = query
+        .char_indices()
+        .map(|(i, _)| &query[..i])
+        .filter(|prefix| !prefix.ends_with(' ') && prefix.chars().count() >= config.suggest_min_chars)
+        .rev();
+    let longest = prefixes.find_map(|prefix| {
+        let data = state.cache.peek_memory(&lookup(prefix).cache_key().key)?;
+        Some((prefix, from_cached(data)?))
+    });
+    if let Some((prefix, geocoded)) = longest {
+        let matching: Vec<GeocodeResult> = geocoded
+            .results
+            .into_iter()
+            .filter(|result| matches_query(&result.formatted_address, query))
+            .collect();
+        if !matching.is_empty() {
+            tracing::debug!("Suggestions for '{}' served from prefix '{}'", query, prefix);
+            return Ok((matching, CacheStatus::Hit, Some(prefix.to_string())));
+        }
+    }
+
+    let lookup = lookup(query);
+    let (result, status) = cached(&state.cache, &lookup, || {
+        add_lookup();
+        state.geocoder.fetch_paced(&lookup)
+    })
+    .await;
+    Ok((result?.results, status, None))
+}
+
+/// Every word typed starts some word of the label, ignoring case and
+/// punctuation.
+fn matches_query(label: &str, query: &str) -> bool {
+    let words = |text: &str| -> Vec<String> {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let label = words(label);
+    words(query)
+        .iter()
+        .all(|typed| label.iter().any(|word| word.starts_with(typed.as_str())))
+}
+
+/// A location the user searched for before.
+struct PastSearch {
+    name: String,
+    coordinates: Option<Coordinates>,
+}
+
+/// The user's distinct past searches containing `query`, most recent
+/// first. Failures only cost the boost, so they are logged and ignored.
+async fn past_searches(state: &AppState, user_id: Uuid, query: &str) -> Vec<PastSearch> {
+    let rows = sqlx::query_as::<_, (String, Option<f64>, Option<f64>)>(
+        r#"
+        SELECT location_name, lat, lng FROM (
+          SELECT DISTINCT ON (lower(location_name))
+            location_name, latitude::float8 AS lat, longitude::float8 AS lng, created_at
+          FROM search_history
+          WHERE user_id = $1 AND location_name ILIKE $2
+          ORDER BY lower(location_name), created_at DESC
+        ) past
+        ORDER BY created_at DESC NULLS LAST
+        LIMIT $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(like_pattern(query))
+    .bind(HISTORY_CANDIDATES)
+    .fetch_all(&state.pool)
+    .await;
+
+    match rows {
+        Ok(rows) => rows
+            .into_iter()
+            .map(|(name, lat, lng)| PastSearch {
+                name,
+                coordinates: lat.zip(lng).map(|(lat, lng)| Coordinates { lat, lng }),
+            })
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to load search history for suggestions: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Orders geocoder results and past searches into at most `limit`
+/// suggestions. Each starts from its position in its own list, gains up to
+/// one point for being near `near`, and `HISTORY_BOOST` for being a place
+/// the user searched for. A past search the geocoder also returned is
+/// shown once, as the geocoder's result.
+fn rank(
+    places: Vec<GeocodeResult>,
+    mut history: Vec<PastSearch>,
+    near: Option<Coordinates>,
+    limit: usize,
+) -> Vec<Suggestion> {
+    let position = |i: usize, n: usize| 1.0 - i as f64 / n as f64;
+    let proximity = |distance: Option<f64>| distance.map_or(0.0, |km| 1.0 / (1.0 + km / PROXIMITY_SCALE_KM));
+    let distance = |point: Option<Coordinates>| near.zip(point).map(|(a, b)| distance_km(a, b));
+
+    let mut scored: Vec<(f64, Suggestion)> = Vec::new();
+
+    let count = places.len();
+    for (i, place) in places.into_iter().enumerate() {
+        let past = history.iter().position(|past| {
+            normalize_text(&past.name) == normalize_text(&place.formatted_address)
+                || past.coordinates.is_some_and(|point| distance_km(point, place.coordinates) <= SAME_PLACE_KM)
+        });
+        if let Some(past) = past {
+            history.remove(past);
+        }
+
+        let distance_km = distance(Some(place.coordinates));
+        let boost = if past.is_some() { HISTORY_BOOST } else { 0.0 };
+        scored.push((
+            position(i, count) + proximity(distance_km) + boost,
+            Suggestion {
+                label: place.formatted_address,
+                coordinates: Some(place.coordinates),
+                provider: Some(place.provider),
+                from_history: past.is_some(),
+                distance_km: distance_km.map(round_km),
+            },
+        ));
+    }
+
+    let count = history.len();
+    for (i, past) in history.into_iter().enumerate() {
+        let distance_km = distance(past.coordinates);
+        scored.push((
+            position(i, count) + proximity(distance_km) + HISTORY_BOOST,
+            Suggestion {
+                label: past.name,
+                coordinates: past.coordinates,
+                provider: None,
+                from_history: true,
+                distance_km: distance_km.map(round_km),
+            },
+        ));
+    }
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(limit).map(|(_, suggestion)| suggestion).collect()
+}
+
+/// Great-circle distance
+fn distance_km(a: Coordinates, b: Coordinates) -> f64 {
+    let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lng = (b.lng - a.lng).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+fn round_km(km: f64) -> f64 {
+    (km * 10.0).round() / 10.0
+}
@@ -0,0 +1,347 @@
+use super::analysis_prompt::trend_years;
+use super::analysis_schema::{
+    air_rating, flood_level, hazard_severity, light_brightness, neighbourhood_rating, noise_level, stability_status,
+    NEUTRAL_TREND,
+};
+use crate::models::analysis::*;
+use crate::models::geocode::GeocodeResult;
+
+// Deterministic report used when the AI service cannot produce one. It is
+// built only from what the backend already knows: the geocoder's answer for
+// the location, analyses cached for the caller's own nearby searches and the
+// average risk score of everyone's nearby searches. Where nothing is
+// known the value is a neutral midpoint or left empty, never invented, and
+// every section that is not first-hand is listed in `estimated_sections`.
+
+const NEUTRAL_SCORE: f64 = 50.0;
+
+/// What is known about the area around the location.
+#[derive(Debug, Default)]
+pub struct NearbyData {
+    /// Cached reports for the caller's nearby searches, closest first
+    pub reports: Vec<PropertyReport>,
+    /// Mean overall risk score saved on anyone's nearby searches
+    pub area_score: Option<f64>,
+    /// How many searches `area_score` averages
+    pub area_searches: i64,
+}
+
+/// Builds an `estimated` report for `location` from the geocoding result
+/// (if geocoding worked) and nearby data. Same inputs, same report.
+pub fn estimated_report(location: &str, geocoded: Option<&GeocodeResult>, nearby: &NearbyData) -> PropertyReport {
+    let location_info = location_info(location, geocoded);
+
+    // National data (politics, economy, law) only carries over within a country
+    let same_country: Vec<&PropertyReport> = nearby
+        .reports
+        .iter()
+        .filter(|r| location_info.country.is_some() && r.location_info.country == location_info.country)
+        .collect();
+
+    let basis = basis(nearby);
+
+    let mut estimated_sections = vec![
+        "risk_analysis".to_string(),
+        "historical_trends".to_string(),
+        "market_intelligence".to_string(),
+        "legal_resources".to_string(),
+    ];
+    if geocoded.is_none() {
+        estimated_sections.insert(0, "location_info".to_string());
+    }
+
+    let historical_trends = historical_trends(&nearby.reports);
+
+    PropertyReport {
+        risk_analysis: risk_analysis(nearby, &same_country, &basis),
+        market_intelligence: market_intelligence(&historical_trends, &location_info, &basis),
+        legal_resources: legal_resources(&same_country, &location_info),
+        historical_trends,
+        location_info,
+        validation: ValidationReport::default(),
+        data_quality: DataQuality::Estimated,
+        estimated_sections,
+    }
+}
+
+/// One sentence saying what the estimate rests on.
+fn basis(nearby: &NearbyData) -> String {
+    match (nearby.reports.len(), nearby.area_searches) {
+        (0, 0) => "No nearby analyses were available; scores are neutral placeholders".to_string(),
+        (0, n) => format!("Estimated from the risk scores of {} nearby searches", n),
+        (n, _) => format!("Estimated from {} previously analyzed nearby locations", n),
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0u32), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| (sum / f64::from(count) * 10.0).round() / 10.0)
+}
+
+fn location_info(location: &str, geocoded: Option<&GeocodeResult>) -> LocationInfo {
+    let Some(result) = geocoded else {
+        return LocationInfo {
+            formatted_address: location.to_string(),
+            coordinates: None,
+            region: None,
+            country: None,
+            jurisdiction: None,
+        };
+    };
+
+    let components = &result.components;
+    let region = components.state.clone().or_else(|| components.county.clone());
+    let country = components.country.clone();
+    let formatted_address = if result.formatted_address.is_empty() {
+        location.to_string()
+    } else {
+        result.formatted_address.clone()
+    };
+
+    LocationInfo {
+        formatted_address,
+        coordinates: Some(Coordinates {
+            lat: result.coordinates.lat,
+            lng: result.coordinates.lng,
+        }),
+        jurisdiction: match (&region, &country) {
+            (Some(region), Some(country)) => Some(format!("{}, {}", region, country)),
+            (None, Some(country)) => Some(country.clone()),
+            _ => None,
+        },
+        region,
+        country,
+    }
+}
+
+// ============ Risk Analysis ============
+
+fn risk_analysis(nearby: &NearbyData, same_country: &[&PropertyReport], basis: &str) -> RiskAnalysis {
+    let reports = &nearby.reports;
+    let avg = |f: &dyn Fn(&RiskAnalysis) -> f64| {
+        mean(reports.iter().map(|r| f(&r.risk_analysis))).unwrap_or(NEUTRAL_SCORE)
+    };
+
+    let overall_score = mean(reports.iter().map(|r| r.risk_analysis.overall_score))
+        .or_else(|| mean(nearby.area_score.into_iter()))
+        .unwrap_or(NEUTRAL_SCORE);
+
+    let factors = vec![basis.to_string()];
+    let scored = |score: f64| ScoredRisk {
+        score,
+        status: RiskStatus::from_score(score),
+        factors: factors.clone(),
+    };
+
+    let flood = avg(&|r| r.flood_risk.score);
+    let neighbourhood = avg(&|r| r.neighbourhood.score);
+    let hazards = avg(&|r| r.environmental_hazards.score);
+    let noise = avg(&|r| r.noise_data.score);
+    let light = avg(&|r| r.light_pollution.score);
+    let aqi = mean(reports.iter().map(|r| r.risk_analysis.air_quality.aqi)).unwrap_or(NEUTRAL_SCORE);
+    let national = same_country.first().map(|r| &r.risk_analysis);
+
+    RiskAnalysis {
+        overall_score,
+        buying_risk: scored(mean(reports.iter().map(|r| r.risk_analysis.buying_risk.score)).unwrap_or(overall_score)),
+        renting_risk: scored(mean(reports.iter().map(|r| r.risk_analysis.renting_risk.score)).unwrap_or(overall_score)),
+        flood_risk: FloodRisk {
+            score: flood,
+            level: flood_level(flood).to_string(),
+            zones: Vec::new(),
+            description: format!("{}. No flood zone data was checked for this address.", basis),
+        },
+        crime_rate: CrimeRate {
+            score: avg(&|r| r.crime_rate.score),
+            rate_per_1000: mean(reports.iter().filter_map(|r| r.risk_analysis.crime_rate.rate_per_1000)),
+            trend: NEUTRAL_TREND.to_string(),
+            types: Vec::new(),
+        },
+        air_quality: AirQuality {
+            aqi,
+            score: avg(&|r| r.air_quality.score),
+            rating: air_rating(aqi).to_string(),
+            pollutants: Vec::new(),
+        },
+        amenities: Amenities {
+            score: avg(&|r| r.amenities.score),
+            walkability: None,
+            nearby: Vec::new(),
+        },
+        transportation: Transportation {
+            score: avg(&|r| r.transportation.score),
+            transit_options: Vec::new(),
+            commute_time: None,
+            walkability_index: None,
+        },
+        neighbourhood: Neighbourhood {
+            score: neighbourhood,
+            rating: neighbourhood_rating(neighbourhood).to_string(),
+            character: None,
+            demographics: None,
+        },
+        environmental_hazards: EnvironmentalHazards {
+            score: hazards,
+            hazards: Vec::new(),
+            severity: hazard_severity(hazards).to_string(),
+        },
+        growth_potential: GrowthPotential {
+            score: avg(&|r| r.growth_potential.score),
+            forecast: NEUTRAL_TREND.to_string(),
+            drivers: Vec::new(),
+            outlook_5yr: None,
+        },
+        political_stability: national.map(|r| r.political_stability.clone()).unwrap_or_else(|| PoliticalStability {
+            score: NEUTRAL_SCORE,
+            status: stability_status(NEUTRAL_SCORE).to_string(),
+            factors: Vec::new(),
+            recent_events: Vec::new(),
+            policy_environment: None,
+        }),
+        trade_economy: national.map(|r| r.trade_economy.clone()).unwrap_or(TradeEconomy {
+            gdp_growth: None,
+            gdp_trend: None,
+            inflation_rate: None,
+            unemployment_rate: None,
+            trade_balance: None,
+            economic_outlook: None,
+            major_industries: Vec::new(),
+            trade_relations: None,
+        }),
+        soil_analysis: SoilAnalysis {
+            r#type: None,
+            stability: None,
+            liquefaction_risk: None,
+            foundation_concerns: None,
+        },
+        noise_data: NoiseData {
+            score: noise,
+            level: noise_level(noise).to_string(),
+            db_avg: None,
+            sources: Vec::new(),
+        },
+        light_pollution: LightPollution {
+            score: light,
+            bortle_scale: None,
+            brightness: light_brightness(light).to_string(),
+            impact: None,
+        },
+        additional_info: AdditionalInfo {
+            solar_potential: None,
+            weather_summary: None,
+            climate_risks: Vec::new(),
+            insurance_considerations: None,
+        },
+    }
+}
+
+// ============ Historical Trends ============
+
+fn historical_trends(reports: &[PropertyReport]) -> HistoricalTrends {
+    let years = trend_years();
+
+    HistoricalTrends {
+        property_values: average_series(reports, &years, |t| {
+            t.property_values.iter().map(|p| (p.year, p.median_price)).collect()
+        })
+        .into_iter()
+        .map(|(year, median_price, change_pct)| PropertyValuePoint { year, median_price, change_pct })
+        .collect(),
+        crime_trends: average_series(reports, &years, |t| {
+            t.crime_trends.iter().map(|p| (p.year, p.incidents_per_1000)).collect()
+        })
+        .into_iter()
+        .map(|(year, incidents_per_1000, change_pct)| CrimeTrendPoint { year, incidents_per_1000, change_pct })
+        .collect(),
+        population: average_series(reports, &years, |t| {
+            t.population.iter().map(|p| (p.year, p.count)).collect()
+        })
+        .into_iter()
+        .map(|(year, count, change_pct)| PopulationPoint { year, count, change_pct })
+        .collect(),
+        development_timeline: Vec::new(),
+    }
+}
+
+/// Averages one series across reports, year by year. Returns an empty
+/// series unless every year has at least one value.
+fn average_series(
+    reports: &[PropertyReport],
+    years: &[i32],
+    points: impl Fn(&HistoricalTrends) -> Vec<(i32, f64)>,
+) -> Vec<(i32, f64, f64)> {
+    let series: Vec<Vec<(i32, f64)>> = reports.iter().map(|r| points(&r.historical_trends)).collect();
+
+    let mut averaged = Vec::with_capacity(years.len());
+    for &year in years {
+        let values = series.iter().flatten().filter(|(y, _)| *y == year).map(|(_, v)| *v);
+        let Some(value) = mean(values) else {
+            return Vec::new();
+        };
+        averaged.push((year, value));
+    }
+
+    let mut result = Vec::with_capacity(averaged.len());
+    for (i, &(year, value)) in averaged.iter().enumerate() {
+        let change = match i.checked_sub(1).map(|prev| averaged[prev].1) {
+            Some(prev) if prev != 0.0 => ((value - prev) / prev * 10000.0).round() / 100.0,
+            _ => 0.0,
+        };
+        result.push((year, value, change));
+    }
+    result
+}
+
+// ============ Market and Legal ============
+
+fn market_intelligence(trends: &HistoricalTrends, info: &LocationInfo, basis: &str) -> MarketIntelligence {
+    let current_trend = match (trends.property_values.first(), trends.property_values.last()) {
+        (Some(first), Some(last)) if first.median_price > 0.0 => {
+            let change = (last.median_price - first.median_price) / first.median_price * 100.0;
+            if change > 2.0 {
+                "Up"
+            } else if change < -2.0 {
+                "Down"
+            } else {
+                "Stable"
+            }
+        }
+        _ => NEUTRAL_TREND,
+    };
+
+    let area = info
+        .region
+        .as_deref()
+        .or(info.country.as_deref())
+        .unwrap_or("this area");
+
+    MarketIntelligence {
+        current_trend: current_trend.to_string(),
+        prediction_6mo: None,
+        prediction_1yr: None,
+        ai_summary: format!(
+            "The AI analysis service was unavailable, so this is an estimated report for {}. {}. Verify with local sources before making decisions.",
+            area, basis
+        ),
+        recent_listings: Vec::new(),
+        news: Vec::new(),
+    }
+}
+
+fn legal_resources(same_country: &[&PropertyReport], info: &LocationInfo) -> LegalResources {
+    if let Some(report) = same_country.first() {
+        return report.legal_resources.clone();
+    }
+
+    LegalResources {
+        jurisdiction: info
+            .jurisdiction
+            .clone()
+            .unwrap_or_else(|| "Unknown".to_string()),
+        property_law_system: None,
+        key_statutes: Vec::new(),
+        dispute_process: None,
+        typical_timeline: None,
+        resources: Vec::new(),
+    }
+}
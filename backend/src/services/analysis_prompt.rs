@@ -0,0 +1,292 @@
+use chrono::{Datelike, Utc};
+
+/// Number of years in every `historical_trends` series.
+pub const TREND_YEARS: i32 = 6;
+
+// English meaning: "Real estate analysis expert. No markdown. Output valid English JSON only."
+pub const SYSTEM_PROMPT: &str = "房地产分析专家。严禁markdown。仅输出有效的英文JSON。";
+
+// English meaning: "You are a senior real estate analyst. Comprehensively analyze: {context}.
+// Provide detailed JSON response (strict structure, English values only)."
+const TEMPLATE: &str = r#"你是资深房地产分析师。全面分析此房产位置:
+{{LOCATION_CONTEXT}}
+
+请提供详细的JSON响应（严禁markdown，仅纯JSON），严格遵循以下结构（所有内容必须用英文输出）:
+
+{
+  "location_info": {
+    "formatted_address": "Full formatted address",
+    "coordinates": { "lat": number, "lng": number },
+    "region": "State/Province",
+    "country": "Country name",
+    "jurisdiction": "Legal jurisdiction"
+  },
+  
+  "risk_analysis": {
+    "overall_score": number (0-100, 100=最高风险),
+    
+    "buying_risk": {
+      "score": number (0-100),
+      "status": "High" | "Medium" | "Low",
+      "factors": ["factor 1", "factor 2"]
+    },
+    
+    "renting_risk": {
+      "score": number (0-100),
+      "status": "High" | "Medium" | "Low",
+      "factors": ["factor 1", "factor 2"]
+    },
+    
+    "flood_risk": {
+      "score": number (0-100),
+      "level": "Extreme" | "High" | "Moderate" | "Low" | "Minimal",
+      "zones": ["zone info"],
+      "description": "Risk explanation"
+    },
+    
+    "crime_rate": {
+      "score": number (0-100, 低=安全),
+      "rate_per_1000": number,
+      "trend": "Increasing" | "Stable" | "Decreasing",
+      "types": ["common crime types"]
+    },
+    
+    "air_quality": {
+      "aqi": number (0-500),
+      "score": number (0-100, 高=好),
+      "rating": "Good" | "Moderate" | "Unhealthy" | "Hazardous",
+      "pollutants": ["pollutants"]
+    },
+    
+    "amenities": {
+      "score": number (0-100),
+      "walkability": number (0-100),
+      "nearby": [
+        { 
+          "type": "Schools", 
+          "count": number, 
+          "closest_distance": "X km",
+          "facilities": [
+            {
+              "name": "Name",
+              "distance": "X km",
+              "rating": number (1-5),
+              "quality": "Excellent|Good|Poor",
+              "type": "Public|Private",
+              "highlights": ["highlights"]
+            }
+          ]
+        },
+        { 
+          "type": "Hospitals", 
+          "count": number, 
+          "closest_distance": "X km",
+          "facilities": [
+            {
+              "name": "Name",
+              "distance": "X km",
+              "rating": number (1-5),
+              "quality": "Excellent|Good|Poor",
+              "specialty": "General|Specialty",
+              "highlights": ["features"]
+            }
+          ]
+        },
+        { 
+          "type": "Shopping", 
+          "count": number, 
+          "closest_distance": "X km",
+          "facilities": [
+            {
+              "name": "Name",
+              "distance": "X km",
+              "type": "Mall|Market"
+            }
+          ]
+        },
+        { 
+          "type": "Parks", 
+          "count": number, 
+          "closest_distance": "X km",
+          "facilities": [
+            {
+              "name": "Name",
+              "distance": "X km",
+              "size": "Large|Small"
+            }
+          ]
+        }
+      ]
+    },
+    
+    "transportation": {
+      "score": number (0-100),
+      "transit_options": ["Bus", "Metro", "Train"],
+      "commute_time": "Time to center",
+      "walkability_index": number (0-100)
+    },
+    
+    "neighbourhood": {
+      "score": number (0-100),
+      "rating": "Excellent" | "Good" | "Average" | "Poor",
+      "character": "Description",
+      "demographics": {
+        "median_age": number,
+        "population_density": "High|Medium|Low"
+      }
+    },
+    
+    "environmental_hazards": {
+      "score": number (0-100, 低=好),
+      "hazards": ["hazards list"],
+      "severity": "High" | "Medium" | "Low" | "None"
+    },
+    
+    "growth_potential": {
+      "score": number (0-100),
+      "forecast": "Strong Growth" | "Moderate Growth" | "Stable" | "Declining",
+      "drivers": ["growth factors"],
+      "outlook_5yr": "5-year outlook"
+    },
+    
+    "political_stability": {
+      "score": number (0-100, 高=稳定),
+      "status": "Very Stable" | "Stable" | "Unstable",
+      "factors": ["political factors"],
+      "recent_events": ["events"],
+      "policy_environment": "Policy overview"
+    },
+    
+    "trade_economy": {
+      "gdp_growth": number (%),
+      "gdp_trend": "Growing" | "Stable" | "Declining",
+      "inflation_rate": number (%),
+      "unemployment_rate": number (%),
+      "trade_balance": "Surplus" | "Deficit",
+      "economic_outlook": "Strong" | "Moderate" | "Weak",
+      "major_industries": ["industries"],
+      "trade_relations": {
+        "status": "Excellent" | "Good" | "Poor",
+        "key_partners": ["partners"],
+        "impact_on_property": "Impact description"
+      }
+    },
+
+    "soil_analysis": {
+      "type": "Clay|Sandy|Loamy|Rocky",
+      "stability": "High" | "Moderate" | "Low",
+      "liquefaction_risk": "High" | "Moderate" | "Low" | "None",
+      "foundation_concerns": "Foundation risks"
+    },
+
+    "noise_data": {
+      "score": number (0-100, 低=安静),
+      "level": "Very Quiet" | "Quiet" | "Moderate" | "Noisy",
+      "db_avg": number (dB),
+      "sources": ["Traffic", "Construction", "Airport"]
+    },
+
+    "light_pollution": {
+      "score": number (0-100, 低=暗/好),
+      "bortle_scale": number (1-9),
+      "brightness": "Dark Sky" | "Good" | "Moderate" | "Bright",
+      "impact": "Visibility impact "
+    },
+
+    "additional_info": {
+      "solar_potential": "Excellent" | "Good" | "Fair" | "Poor",
+      "weather_summary": "Weather summary",
+      "climate_risks": ["risks"],
+      "insurance_considerations": "Insurance note"
+    }
+  },
+  
+  "historical_trends": {
+    "property_values": [
+{{PROPERTY_VALUES}}
+    ],
+    "crime_trends": [
+{{CRIME_TRENDS}}
+    ],
+    "population": [
+{{POPULATION}}
+    ],
+    "development_timeline": [
+{{DEVELOPMENT_TIMELINE}}
+    ]
+  },
+  
+  "market_intelligence": {
+    "current_trend": "Up" | "Down" | "Stable",
+    "prediction_6mo": "6-month forecast",
+    "prediction_1yr": "1-year forecast",
+    "ai_summary": "Summary of outlook, risks, opportunities",
+    "recent_listings": [
+      {
+        "address": "Address",
+        "price": "Formatted price",
+        "type": "Apartment|House|Land",
+        "bedrooms": number,
+        "sqft": number,
+        "date": "Listed date",
+        "coordinates": { "lat": number, "lng": number }
+      }
+    ],
+    "news": [
+      {
+        "headline": "Headline",
+        "summary": "Summary",
+        "date": "Date",
+        "source": "Source",
+        "relevance": "High" | "Medium" | "Low"
+      }
+    ]
+  },
+  
+  "legal_resources": {
+    "jurisdiction": "Jurisdiction",
+    "property_law_system": "Common Law | Civil Law",
+    "key_statutes": [
+      { "name": "Name", "description": "Description" }
+    ],
+    "dispute_process": "Process overview",
+    "typical_timeline": "Timeline",
+    "resources": [
+      { "name": "Name", "type": "Type", "description": "Description" }
+    ]
+  }
+}
+
+仅输出有效的英文JSON格式。不要使用markdown代码块。"#;
+
+/// The last `TREND_YEARS` complete calendar years, oldest first.
+pub fn trend_years() -> Vec<i32> {
+    let last = Utc::now().year() - 1;
+    (last - TREND_YEARS + 1..=last).collect()
+}
+
+fn series(years: &[i32], fields: &str) -> String {
+    years
+        .iter()
+        .map(|year| format!("      {{ \"year\": {}, {} }}", year, fields))
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+/// Builds the user prompt for a location, asking for trend series that end
+/// with last year rather than a fixed range.
+pub fn build_prompt(location_context: &str) -> String {
+    let years = trend_years();
+    let timeline = years[1..]
+        .iter()
+        .map(|year| format!("      {{ \"year\": {}, \"events\": [\"event\"] }}", year))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    TEMPLATE
+        .replace("{{LOCATION_CONTEXT}}", location_context)
+        .replace("{{PROPERTY_VALUES}}", &series(&years, "\"median_price\": number, \"change_pct\": number"))
+        .replace("{{CRIME_TRENDS}}", &series(&years, "\"incidents_per_1000\": number, \"change_pct\": number"))
+        .replace("{{POPULATION}}", &series(&years, "\"count\": number, \"change_pct\": number"))
+        .replace("{{DEVELOPMENT_TIMELINE}}", &timeline)
+}
@@ -0,0 +1,905 @@
+use serde_json::{json, Map, Value};
+
+use super::analysis_prompt::trend_years;
+use crate::models::analysis::{FieldIssue, PropertyReport, ValidationReport};
+
+// Validation and repair of the analysis JSON the LLM returns. Recoverable
+// mistakes (numbers as strings, out-of-range scores, odd enum casing, gaps
+// in a trend series, missing sub-sections) are fixed in place and recorded;
+// anything that leaves the report meaningless is a hard error so the caller
+// can re-prompt.
+
+const SECTIONS: [&str; 4] = [
+    "risk_analysis",
+    "historical_trends",
+    "market_intelligence",
+    "legal_resources",
+];
+
+/// Parses, repairs and types an LLM reply. On failure returns the list of
+/// problems, suitable for feeding back to the model.
+pub fn validate_report(content: &str, location: &str) -> Result<PropertyReport, Vec<String>> {
+    let mut root: Value = serde_json::from_str(extract_json(content))
+        .map_err(|e| vec![format!("Response is not valid JSON: {}", e)])?;
+
+    let mut v = Validator::default();
+    v.report(&mut root, location);
+
+    if !v.errors.is_empty() {
+        return Err(v.errors);
+    }
+
+    let mut report: PropertyReport = serde_json::from_value(root)
+        .map_err(|e| vec![format!("Response does not match the report structure: {}", e)])?;
+
+    report.validation = ValidationReport {
+        repaired: v.repaired,
+        defaulted: v.defaulted,
+        reprompted: false,
+    };
+
+    Ok(report)
+}
+
+/// Strips markdown fences and any prose around the outermost JSON object.
+pub fn extract_json(content: &str) -> &str {
+    match (content.find('{'), content.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => content.trim(),
+    }
+}
+
+/// Reads a number that may have arrived as a string such as "72", "3.5%",
+/// "$1,200,000" or "Approx. 45 dB". Only the first number in the text is
+/// used, so a range like "2-3" reads as 2.
+fn coerce_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => first_number(s),
+        _ => None,
+    }
+}
+
+/// The first numeric token in `text`: an optional minus sign directly before
+/// the digits, digits with `,` thousands separators, and at most one decimal
+/// part.
+fn first_number(text: &str) -> Option<f64> {
+    let bytes = text.as_bytes();
+    let start = bytes.iter().position(u8::is_ascii_digit)?;
+    let negative = start > 0 && bytes[start - 1] == b'-';
+
+    let mut token = String::new();
+    let mut seen_point = false;
+    for (i, &b) in bytes.iter().enumerate().skip(start) {
+        let next_is_digit = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        match b {
+            b'0'..=b'9' => token.push(b as char),
+            b',' if next_is_digit && !seen_point => {}
+            b'.' if next_is_digit && !seen_point => {
+                seen_point = true;
+                token.push('.');
+            }
+            _ => break,
+        }
+    }
+
+    let n: f64 = token.parse().ok().filter(|n: &f64| n.is_finite())?;
+    Some(if negative { -n } else { n })
+}
+
+// Fallbacks for enum fields that are missing or unrecognized. The estimated
+// report uses the same ones, so it only ever holds values the schema allows.
+
+/// Crime trend, growth forecast and market trend when nothing is known
+pub const NEUTRAL_TREND: &str = "Stable";
+
+pub fn status_from_score(score: f64) -> &'static str {
+    if score >= 67.0 {
+        "High"
+    } else if score >= 34.0 {
+        "Medium"
+    } else {
+        "Low"
+    }
+}
+
+pub fn flood_level(score: f64) -> &'static str {
+    match score {
+        s if s >= 80.0 => "Extreme",
+        s if s >= 60.0 => "High",
+        s if s >= 40.0 => "Moderate",
+        s if s >= 20.0 => "Low",
+        _ => "Minimal",
+    }
+}
+
+pub fn air_rating(aqi: f64) -> &'static str {
+    match aqi {
+        a if a <= 50.0 => "Good",
+        a if a <= 100.0 => "Moderate",
+        a if a <= 300.0 => "Unhealthy",
+        _ => "Hazardous",
+    }
+}
+
+pub fn neighbourhood_rating(score: f64) -> &'static str {
+    match score {
+        s if s >= 75.0 => "Excellent",
+        s if s >= 50.0 => "Good",
+        s if s >= 25.0 => "Average",
+        _ => "Poor",
+    }
+}
+
+pub fn hazard_severity(score: f64) -> &'static str {
+    if score < 10.0 { "None" } else { status_from_score(score) }
+}
+
+pub fn stability_status(score: f64) -> &'static str {
+    match score {
+        s if s >= 75.0 => "Very Stable",
+        s if s >= 40.0 => "Stable",
+        _ => "Unstable",
+    }
+}
+
+pub fn noise_level(score: f64) -> &'static str {
+    match score {
+        s if s >= 75.0 => "Noisy",
+        s if s >= 50.0 => "Moderate",
+        s if s >= 25.0 => "Quiet",
+        _ => "Very Quiet",
+    }
+}
+
+pub fn light_brightness(score: f64) -> &'static str {
+    match score {
+        s if s >= 75.0 => "Bright",
+        s if s >= 50.0 => "Moderate",
+        s if s >= 25.0 => "Good",
+        _ => "Dark Sky",
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    repaired: Vec<FieldIssue>,
+    defaulted: Vec<FieldIssue>,
+    errors: Vec<String>,
+}
+
+impl Validator {
+    fn repair(&mut self, field: &str, detail: String) {
+        self.repaired.push(FieldIssue {
+            field: field.to_string(),
+            detail,
+        });
+    }
+
+    fn fill(&mut self, field: &str, detail: String) {
+        self.defaulted.push(FieldIssue {
+            field: field.to_string(),
+            detail,
+        });
+    }
+
+    /// Makes sure `parent[key]` is an object, replacing anything else with
+    /// an empty one that the caller then fills with defaults.
+    fn object<'a>(&mut self, parent: &'a mut Map<String, Value>, key: &str, path: &str) -> &'a mut Map<String, Value> {
+        let field = if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+        if !parent.get(key).is_some_and(Value::is_object) {
+            self.fill(&field, "missing section".to_string());
+            parent.insert(key.to_string(), json!({}));
+        }
+        parent.get_mut(key).and_then(Value::as_object_mut).unwrap()
+    }
+
+    /// Coerces a required number into `[min, max]`, defaulting when absent.
+    fn number(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str, min: f64, max: f64, default: f64) -> f64 {
+        let field = format!("{}.{}", path, key);
+        let value = match obj.get(key) {
+            Some(Value::Number(n)) => n.as_f64(),
+            Some(Value::Null) | None => None,
+            Some(other) => {
+                let parsed = coerce_number(other);
+                if let Some(n) = parsed {
+                    self.repair(&field, format!("converted {} to number {}", other, n));
+                }
+                parsed
+            }
+        };
+
+        let value = match value {
+            Some(n) if n < min || n > max => {
+                let clamped = n.clamp(min, max);
+                self.repair(&field, format!("clamped {} to {}", n, clamped));
+                clamped
+            }
+            Some(n) => n,
+            None => {
+                self.fill(&field, format!("missing, defaulted to {}", default));
+                default
+            }
+        };
+
+        obj.insert(key.to_string(), json!(value));
+        value
+    }
+
+    fn score(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) -> f64 {
+        self.number(obj, key, path, 0.0, 100.0, 50.0)
+    }
+
+    /// Coerces an optional number; unusable values become null.
+    fn optional_number(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str, min: f64, max: f64) {
+        let field = format!("{}.{}", path, key);
+        match obj.get(key) {
+            None | Some(Value::Null) => {}
+            Some(value) => match coerce_number(value) {
+                Some(n) => {
+                    let clamped = n.clamp(min, max);
+                    if !value.is_number() || clamped != n {
+                        self.repair(&field, format!("normalized {} to {}", value, clamped));
+                    }
+                    obj.insert(key.to_string(), json!(clamped));
+                }
+                None => {
+                    self.repair(&field, format!("dropped unusable value {}", value));
+                    obj.insert(key.to_string(), Value::Null);
+                }
+            },
+        }
+    }
+
+    /// Coerces an optional non-negative integer (counts, bedrooms).
+    fn optional_integer(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) {
+        let field = format!("{}.{}", path, key);
+        match obj.get(key) {
+            None | Some(Value::Null) => {}
+            Some(value) if value.is_u64() => {}
+            Some(value) => {
+                let fixed = coerce_number(value)
+                    .filter(|n| *n >= 0.0)
+                    .map(|n| json!(n.round() as u64))
+                    .unwrap_or(Value::Null);
+                self.repair(&field, format!("normalized {} to {}", value, fixed));
+                obj.insert(key.to_string(), fixed);
+            }
+        }
+    }
+
+    /// Canonicalizes an enum-like string against `allowed` (case and
+    /// whitespace insensitive), falling back when it can't be matched.
+    fn enumeration(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str, allowed: &[&str], fallback: &str) {
+        let field = format!("{}.{}", path, key);
+        let raw = obj.get(key).and_then(Value::as_str).map(|s| s.trim().to_string());
+
+        let canonical = raw.as_deref().and_then(|raw| {
+            allowed
+                .iter()
+                .find(|a| a.eq_ignore_ascii_case(raw))
+                .or_else(|| match raw.to_ascii_lowercase().as_str() {
+                    "moderate" => allowed.iter().find(|a| **a == "Medium"),
+                    "medium" => allowed.iter().find(|a| **a == "Moderate"),
+                    _ => None,
+                })
+        });
+
+        let value = match (raw, canonical) {
+            (Some(raw), Some(canonical)) => {
+                if raw != *canonical {
+                    self.repair(&field, format!("normalized \"{}\" to \"{}\"", raw, canonical));
+                }
+                canonical.to_string()
+            }
+            (Some(raw), None) => {
+                self.repair(&field, format!("replaced unknown value \"{}\" with \"{}\"", raw, fallback));
+                fallback.to_string()
+            }
+            (None, _) => {
+                self.fill(&field, format!("missing, defaulted to \"{}\"", fallback));
+                fallback.to_string()
+            }
+        };
+
+        obj.insert(key.to_string(), json!(value));
+    }
+
+    fn string(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str, default: &str) {
+        let field = format!("{}.{}", path, key);
+        match obj.get(key) {
+            Some(Value::String(s)) if !s.trim().is_empty() => {}
+            Some(Value::Number(n)) => {
+                let text = n.to_string();
+                self.repair(&field, format!("converted number to string \"{}\"", text));
+                obj.insert(key.to_string(), json!(text));
+            }
+            _ => {
+                self.fill(&field, format!("missing, defaulted to \"{}\"", default));
+                obj.insert(key.to_string(), json!(default));
+            }
+        }
+    }
+
+    fn optional_string(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) {
+        let field = format!("{}.{}", path, key);
+        match obj.get(key) {
+            None | Some(Value::Null) | Some(Value::String(_)) => {}
+            Some(Value::Number(n)) => {
+                let text = n.to_string();
+                obj.insert(key.to_string(), json!(text));
+            }
+            Some(other) => {
+                self.repair(&field, format!("dropped non-text value {}", other));
+                obj.insert(key.to_string(), Value::Null);
+            }
+        }
+    }
+
+    /// Ensures a list of strings: a lone string becomes a one-item list,
+    /// non-text items are dropped.
+    fn string_list(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) {
+        let field = format!("{}.{}", path, key);
+        let fixed: Vec<Value> = match obj.get(key) {
+            None | Some(Value::Null) => return,
+            Some(Value::Array(items)) if items.iter().all(Value::is_string) => return,
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Value::String(s) => Some(json!(s)),
+                    Value::Number(n) => Some(json!(n.to_string())),
+                    _ => None,
+                })
+                .collect(),
+            Some(Value::String(s)) => vec![json!(s)],
+            Some(_) => Vec::new(),
+        };
+
+        self.repair(&field, "normalized to a list of strings".to_string());
+        obj.insert(key.to_string(), Value::Array(fixed));
+    }
+
+    /// Keeps only the objects in a list that have a usable `required` text
+    /// field, then lets `each` repair the survivors.
+    fn object_list(
+        &mut self,
+        obj: &mut Map<String, Value>,
+        key: &str,
+        path: &str,
+        required: &str,
+        mut each: impl FnMut(&mut Self, &mut Map<String, Value>, &str),
+    ) {
+        let field = format!("{}.{}", path, key);
+        let items = match obj.remove(key) {
+            None | Some(Value::Null) => return,
+            Some(Value::Array(items)) => items,
+            Some(other) => {
+                self.repair(&field, format!("dropped non-list value {}", other));
+                Vec::new()
+            }
+        };
+
+        let mut kept = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let item_path = format!("{}[{}]", field, i);
+            match item {
+                Value::Object(mut map)
+                    if map.get(required).and_then(Value::as_str).is_some_and(|s| !s.trim().is_empty()) =>
+                {
+                    each(self, &mut map, &item_path);
+                    kept.push(Value::Object(map));
+                }
+                _ => self.repair(&item_path, format!("dropped entry without '{}'", required)),
+            }
+        }
+
+        obj.insert(key.to_string(), Value::Array(kept));
+    }
+
+    fn coordinates(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) {
+        let field = format!("{}.{}", path, key);
+        let Some(value) = obj.get(key).filter(|v| !v.is_null()) else {
+            return;
+        };
+
+        let lat = coerce_number(&value["lat"]).filter(|lat| (-90.0..=90.0).contains(lat));
+        let lng = coerce_number(&value["lng"]).filter(|lng| (-180.0..=180.0).contains(lng));
+
+        match (lat, lng) {
+            (Some(lat), Some(lng)) => {
+                obj.insert(key.to_string(), json!({ "lat": lat, "lng": lng }));
+            }
+            _ => {
+                self.repair(&field, "dropped invalid coordinates".to_string());
+                obj.insert(key.to_string(), Value::Null);
+            }
+        }
+    }
+
+    // ============ Sections ============
+
+    fn report(&mut self, root: &mut Value, location: &str) {
+        let Some(root) = root.as_object_mut() else {
+            self.errors.push("Response must be a JSON object".to_string());
+            return;
+        };
+
+        for section in SECTIONS {
+            if !root.get(section).is_some_and(Value::is_object) {
+                self.errors.push(format!("Missing required section '{}'", section));
+            }
+        }
+        if !self.errors.is_empty() {
+            return;
+        }
+
+        let info = self.object(root, "location_info", "");
+        self.string(info, "formatted_address", "location_info", location);
+        self.coordinates(info, "coordinates", "location_info");
+        for key in ["region", "country", "jurisdiction"] {
+            self.optional_string(info, key, "location_info");
+        }
+
+        let risk = root.get_mut("risk_analysis").and_then(Value::as_object_mut).unwrap();
+        self.risk_analysis(risk);
+
+        let trends = root.get_mut("historical_trends").and_then(Value::as_object_mut).unwrap();
+        self.historical_trends(trends);
+
+        let market = root.get_mut("market_intelligence").and_then(Value::as_object_mut).unwrap();
+        self.market_intelligence(market);
+
+        let legal = root.get_mut("legal_resources").and_then(Value::as_object_mut).unwrap();
+        self.legal_resources(legal);
+    }
+
+    fn risk_analysis(&mut self, risk: &mut Map<String, Value>) {
+        let p = "risk_analysis";
+        self.score(risk, "overall_score", p);
+
+        for key in ["buying_risk", "renting_risk"] {
+            let path = format!("{}.{}", p, key);
+            let obj = self.object(risk, key, p);
+            let score = self.score(obj, "score", &path);
+            self.enumeration(obj, "status", &path, &["High", "Medium", "Low"], status_from_score(score));
+            self.string_list(obj, "factors", &path);
+        }
+
+        let path = format!("{}.flood_risk", p);
+        let obj = self.object(risk, "flood_risk", p);
+        let score = self.score(obj, "score", &path);
+        self.enumeration(obj, "level", &path, &["Extreme", "High", "Moderate", "Low", "Minimal"], flood_level(score));
+        self.string_list(obj, "zones", &path);
+        self.string(obj, "description", &path, "No flood risk details were provided");
+
+        let path = format!("{}.crime_rate", p);
+        let obj = self.object(risk, "crime_rate", p);
+        self.score(obj, "score", &path);
+        self.optional_number(obj, "rate_per_1000", &path, 0.0, 1000.0);
+        self.enumeration(obj, "trend", &path, &["Increasing", "Stable", "Decreasing"], NEUTRAL_TREND);
+        self.string_list(obj, "types", &path);
+
+        let path = format!("{}.air_quality", p);
+        let obj = self.object(risk, "air_quality", p);
+        let aqi = self.number(obj, "aqi", &path, 0.0, 500.0, 50.0);
+        self.score(obj, "score", &path);
+        self.enumeration(obj, "rating", &path, &["Good", "Moderate", "Unhealthy", "Hazardous"], air_rating(aqi));
+        self.string_list(obj, "pollutants", &path);
+
+        let path = format!("{}.amenities", p);
+        let obj = self.object(risk, "amenities", p);
+        self.score(obj, "score", &path);
+        self.optional_number(obj, "walkability", &path, 0.0, 100.0);
+        self.object_list(obj, "nearby", &path, "type", |v, group, group_path| {
+            v.optional_integer(group, "count", group_path);
+            v.optional_string(group, "closest_distance", group_path);
+            v.object_list(group, "facilities", group_path, "name", |v, facility, facility_path| {
+                v.optional_number(facility, "rating", facility_path, 1.0, 5.0);
+                for key in ["distance", "quality", "type", "specialty", "size"] {
+                    v.optional_string(facility, key, facility_path);
+                }
+                v.string_list(facility, "highlights", facility_path);
+            });
+        });
+
+        let path = format!("{}.transportation", p);
+        let obj = self.object(risk, "transportation", p);
+        self.score(obj, "score", &path);
+        self.string_list(obj, "transit_options", &path);
+        self.optional_string(obj, "commute_time", &path);
+        self.optional_number(obj, "walkability_index", &path, 0.0, 100.0);
+
+        let path = format!("{}.neighbourhood", p);
+        let obj = self.object(risk, "neighbourhood", p);
+        let score = self.score(obj, "score", &path);
+        let rating = neighbourhood_rating(score);
+        self.enumeration(obj, "rating", &path, &["Excellent", "Good", "Average", "Poor"], rating);
+        self.optional_string(obj, "character", &path);
+        if obj.get("demographics").is_some_and(|d| !d.is_object() && !d.is_null()) {
+            self.repair(&format!("{}.demographics", path), "dropped non-object value".to_string());
+            obj.insert("demographics".to_string(), Value::Null);
+        }
+        if let Some(demographics) = obj.get_mut("demographics").and_then(Value::as_object_mut) {
+            let demo_path = format!("{}.demographics", path);
+            self.optional_number(demographics, "median_age", &demo_path, 0.0, 120.0);
+            self.optional_string(demographics, "population_density", &demo_path);
+        }
+
+        let path = format!("{}.environmental_hazards", p);
+        let obj = self.object(risk, "environmental_hazards", p);
+        let score = self.score(obj, "score", &path);
+        self.enumeration(obj, "severity", &path, &["High", "Medium", "Low", "None"], hazard_severity(score));
+        self.string_list(obj, "hazards", &path);
+
+        let path = format!("{}.growth_potential", p);
+        let obj = self.object(risk, "growth_potential", p);
+        self.score(obj, "score", &path);
+        self.enumeration(
+            obj,
+            "forecast",
+            &path,
+            &["Strong Growth", "Moderate Growth", "Stable", "Declining"],
+            NEUTRAL_TREND,
+        );
+        self.string_list(obj, "drivers", &path);
+        self.optional_string(obj, "outlook_5yr", &path);
+
+        let path = format!("{}.political_stability", p);
+        let obj = self.object(risk, "political_stability", p);
+        let score = self.score(obj, "score", &path);
+        self.enumeration(obj, "status", &path, &["Very Stable", "Stable", "Unstable"], stability_status(score));
+        self.string_list(obj, "factors", &path);
+        self.string_list(obj, "recent_events", &path);
+        self.optional_string(obj, "policy_environment", &path);
+
+        let path = format!("{}.trade_economy", p);
+        let obj = self.object(risk, "trade_economy", p);
+        self.optional_number(obj, "gdp_growth", &path, -100.0, 100.0);
+        self.optional_number(obj, "inflation_rate", &path, -100.0, 1000.0);
+        self.optional_number(obj, "unemployment_rate", &path, 0.0, 100.0);
+        for key in ["gdp_trend", "trade_balance", "economic_outlook"] {
+            self.optional_string(obj, key, &path);
+        }
+        self.string_list(obj, "major_industries", &path);
+        if obj.get("trade_relations").is_some_and(|t| !t.is_object() && !t.is_null()) {
+            self.repair(&format!("{}.trade_relations", path), "dropped non-object value".to_string());
+            obj.insert("trade_relations".to_string(), Value::Null);
+        }
+        if let Some(relations) = obj.get_mut("trade_relations").and_then(Value::as_object_mut) {
+            let relations_path = format!("{}.trade_relations", path);
+            self.optional_string(relations, "status", &relations_path);
+            self.string_list(relations, "key_partners", &relations_path);
+            self.optional_string(relations, "impact_on_property", &relations_path);
+        }
+
+        let path = format!("{}.soil_analysis", p);
+        let obj = self.object(risk, "soil_analysis", p);
+        for key in ["type", "stability", "liquefaction_risk", "foundation_concerns"] {
+            self.optional_string(obj, key, &path);
+        }
+
+        let path = format!("{}.noise_data", p);
+        let obj = self.object(risk, "noise_data", p);
+        let score = self.score(obj, "score", &path);
+        self.enumeration(obj, "level", &path, &["Very Quiet", "Quiet", "Moderate", "Noisy"], noise_level(score));
+        self.optional_number(obj, "db_avg", &path, 0.0, 200.0);
+        self.string_list(obj, "sources", &path);
+
+        let path = format!("{}.light_pollution", p);
+        let obj = self.object(risk, "light_pollution", p);
+        let score = self.score(obj, "score", &path);
+        let brightness = light_brightness(score);
+        self.enumeration(obj, "brightness", &path, &["Dark Sky", "Good", "Moderate", "Bright"], brightness);
+        self.optional_number(obj, "bortle_scale", &path, 1.0, 9.0);
+        self.optional_string(obj, "impact", &path);
+
+        let path = format!("{}.additional_info", p);
+        let obj = self.object(risk, "additional_info", p);
+        for key in ["solar_potential", "weather_summary", "insurance_considerations"] {
+            self.optional_string(obj, key, &path);
+        }
+        self.string_list(obj, "climate_risks", &path);
+    }
+
+    fn historical_trends(&mut self, trends: &mut Map<String, Value>) {
+        let years = trend_years();
+        self.series(trends, "property_values", "median_price", &years);
+        self.series(trends, "crime_trends", "incidents_per_1000", &years);
+        self.series(trends, "population", "count", &years);
+
+        let p = "historical_trends";
+        self.object_list_by_year(trends, "development_timeline", p);
+    }
+
+    /// Rebuilds a yearly series so it has exactly one point per year in
+    /// `years`. Missing years are interpolated from their neighbours (or
+    /// copied from the nearest one at the edges) and `change_pct` is
+    /// recomputed for any point that was filled in.
+    fn series(&mut self, trends: &mut Map<String, Value>, key: &str, value_key: &str, years: &[i32]) {
+        let field = format!("historical_trends.{}", key);
+
+        let mut points: Vec<(i32, f64, Option<f64>)> = trends
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let year = coerce_number(&item["year"])?.round() as i32;
+                        let value = coerce_number(&item[value_key])?;
+                        Some((year, value, coerce_number(&item["change_pct"])))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        points.retain(|(year, _, _)| years.contains(year));
+        points.sort_by_key(|(year, _, _)| *year);
+        points.dedup_by_key(|(year, _, _)| *year);
+
+        if points.is_empty() {
+            self.errors.push(format!(
+                "'{}' must contain {} yearly points for {}-{} with a numeric '{}'",
+                field,
+                years.len(),
+                years[0],
+                years[years.len() - 1],
+                value_key
+            ));
+            return;
+        }
+
+        let mut rebuilt: Vec<(i32, f64, Option<f64>)> = Vec::with_capacity(years.len());
+        let mut filled = Vec::new();
+
+        for &year in years {
+            if let Some(point) = points.iter().find(|(y, _, _)| *y == year) {
+                rebuilt.push(*point);
+                continue;
+            }
+
+            let before = points.iter().rev().find(|(y, _, _)| *y < year);
+            let after = points.iter().find(|(y, _, _)| *y > year);
+            let value = match (before, after) {
+                (Some(&(y0, v0, _)), Some(&(y1, v1, _))) => {
+                    v0 + (v1 - v0) * f64::from(year - y0) / f64::from(y1 - y0)
+                }
+                (Some(&(_, v, _)), None) | (None, Some(&(_, v, _))) => v,
+                (None, None) => unreachable!("points is not empty"),
+            };
+            rebuilt.push((year, value, None));
+            filled.push(year);
+        }
+
+        // Recompute change_pct where it is missing or its base was filled in
+        for i in 0..rebuilt.len() {
+            let recompute = rebuilt[i].2.is_none() || (i > 0 && filled.contains(&rebuilt[i - 1].0));
+            if recompute {
+                let change = if i == 0 || rebuilt[i - 1].1 == 0.0 {
+                    0.0
+                } else {
+                    (rebuilt[i].1 - rebuilt[i - 1].1) / rebuilt[i - 1].1 * 100.0
+                };
+                rebuilt[i].2 = Some((change * 100.0).round() / 100.0);
+            }
+        }
+
+        if !filled.is_empty() {
+            self.repair(&field, format!("filled missing years {:?}", filled));
+        }
+
+        let original_len = trends.get(key).and_then(Value::as_array).map_or(0, Vec::len);
+        if original_len != points.len() {
+            self.repair(&field, format!("dropped {} invalid or out-of-range points", original_len - points.len()));
+        }
+
+        let series: Vec<Value> = rebuilt
+            .into_iter()
+            .map(|(year, value, change)| {
+                let mut point = Map::new();
+                point.insert("year".to_string(), json!(year));
+                point.insert(value_key.to_string(), json!(value));
+                point.insert("change_pct".to_string(), json!(change.unwrap_or(0.0)));
+                Value::Object(point)
+            })
+            .collect();
+
+        trends.insert(key.to_string(), Value::Array(series));
+    }
+
+    fn object_list_by_year(&mut self, obj: &mut Map<String, Value>, key: &str, path: &str) {
+        let field = format!("{}.{}", path, key);
+        let Some(items) = obj.get(key).and_then(Value::as_array) else {
+            obj.insert(key.to_string(), json!([]));
+            return;
+        };
+
+        let mut kept = Vec::new();
+        for item in items {
+            let Some(year) = coerce_number(&item["year"]) else {
+                self.repair(&field, "dropped entry without a year".to_string());
+                continue;
+            };
+            let mut entry = Map::new();
+            entry.insert("year".to_string(), json!(year.round() as i32));
+            entry.insert("events".to_string(), item["events"].clone());
+            self.string_list(&mut entry, "events", &field);
+            kept.push(Value::Object(entry));
+        }
+
+        obj.insert(key.to_string(), Value::Array(kept));
+    }
+
+    fn market_intelligence(&mut self, market: &mut Map<String, Value>) {
+        let p = "market_intelligence";
+        self.enumeration(market, "current_trend", p, &["Up", "Down", "Stable"], NEUTRAL_TREND);
+        self.optional_string(market, "prediction_6mo", p);
+        self.optional_string(market, "prediction_1yr", p);
+        self.string(market, "ai_summary", p, "No market summary was provided");
+
+        self.object_list(market, "recent_listings", p, "address", |v, listing, path| {
+            for key in ["price", "type", "date"] {
+                v.optional_string(listing, key, path);
+            }
+            v.optional_integer(listing, "bedrooms", path);
+            v.optional_number(listing, "sqft", path, 0.0, 1_000_000.0);
+            v.coordinates(listing, "coordinates", path);
+        });
+
+        self.object_list(market, "news", p, "headline", |v, item, path| {
+            for key in ["summary", "date", "source", "relevance"] {
+                v.optional_string(item, key, path);
+            }
+        });
+    }
+
+    fn legal_resources(&mut self, legal: &mut Map<String, Value>) {
+        let p = "legal_resources";
+        self.string(legal, "jurisdiction", p, "Unknown");
+        for key in ["property_law_system", "dispute_process", "typical_timeline"] {
+            self.optional_string(legal, key, p);
+        }
+        self.object_list(legal, "key_statutes", p, "name", |v, statute, path| {
+            v.optional_string(statute, "description", path);
+        });
+        self.object_list(legal, "resources", p, "name", |v, resource, path| {
+            v.optional_string(resource, "type", path);
+            v.optional_string(resource, "description", path);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest reply that passes validation: every section present and one
+    /// point per trend series, everything else left for defaults.
+    fn minimal_reply() -> Value {
+        let year = trend_years()[0];
+        json!({
+            "risk_analysis": {},
+            "historical_trends": {
+                "property_values": [{ "year": year, "median_price": 100000 }],
+                "crime_trends": [{ "year": year, "incidents_per_1000": 20 }],
+                "population": [{ "year": year, "count": 5000 }]
+            },
+            "market_intelligence": {},
+            "legal_resources": {}
+        })
+    }
+
+    fn validate(reply: &Value) -> Result<PropertyReport, Vec<String>> {
+        validate_report(&reply.to_string(), "Springfield")
+    }
+
+    #[test]
+    fn coerces_the_first_number_in_text() {
+        let cases = [
+            ("72", 72.0),
+            ("3.5%", 3.5),
+            ("$1,200,000", 1_200_000.0),
+            ("Approx. 45 dB", 45.0),
+            ("2-3", 2.0),
+            ("1.2.3", 1.2),
+            ("-4.5% YoY", -4.5),
+            ("about 7.", 7.0),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(coerce_number(&json!(text)), Some(expected), "{:?}", text);
+        }
+
+        assert_eq!(coerce_number(&json!(12)), Some(12.0));
+        assert_eq!(coerce_number(&json!("n/a")), None);
+        assert_eq!(coerce_number(&json!(null)), None);
+    }
+
+    #[test]
+    fn repairs_numbers_given_as_text() {
+        let mut reply = minimal_reply();
+        reply["risk_analysis"]["overall_score"] = json!("Approx. 45 points");
+        reply["risk_analysis"]["noise_data"] = json!({ "score": 30, "db_avg": "55 dB" });
+        reply["risk_analysis"]["air_quality"] = json!({ "aqi": 900, "score": 40 });
+
+        let report = validate(&reply).unwrap();
+        let risk = &report.risk_analysis;
+        assert_eq!(risk.overall_score, 45.0);
+        assert_eq!(risk.noise_data.db_avg, Some(55.0));
+        assert_eq!(risk.air_quality.aqi, 500.0);
+
+        let repaired: Vec<&str> = report.validation.repaired.iter().map(|i| i.field.as_str()).collect();
+        assert!(repaired.contains(&"risk_analysis.overall_score"));
+        assert!(repaired.contains(&"risk_analysis.air_quality.aqi"));
+    }
+
+    #[test]
+    fn normalizes_enum_values() {
+        let mut reply = minimal_reply();
+        reply["risk_analysis"]["crime_rate"] = json!({ "score": 50, "trend": " increasing " });
+        reply["risk_analysis"]["buying_risk"] = json!({ "score": 50, "status": "moderate" });
+        reply["risk_analysis"]["growth_potential"] = json!({ "score": 50, "forecast": "Booming" });
+
+        let report = validate(&reply).unwrap();
+        let risk = serde_json::to_value(&report.risk_analysis).unwrap();
+        assert_eq!(risk["crime_rate"]["trend"], "Increasing");
+        assert_eq!(risk["buying_risk"]["status"], "Medium");
+        assert_eq!(risk["growth_potential"]["forecast"], "Stable");
+    }
+
+    #[test]
+    fn fills_defaults_for_missing_fields() {
+        let report = validate(&minimal_reply()).unwrap();
+        let risk = serde_json::to_value(&report.risk_analysis).unwrap();
+
+        assert_eq!(risk["overall_score"], 50.0);
+        assert_eq!(risk["buying_risk"]["status"], "Medium");
+        assert_eq!(risk["flood_risk"]["level"], "Moderate");
+        assert_eq!(risk["political_stability"]["status"], "Stable");
+        assert_eq!(report.location_info.formatted_address, "Springfield");
+        assert_eq!(report.historical_trends.property_values.len(), trend_years().len());
+
+        let defaulted: Vec<&str> = report.validation.defaulted.iter().map(|i| i.field.as_str()).collect();
+        assert!(defaulted.contains(&"risk_analysis.buying_risk"));
+        assert!(defaulted.contains(&"location_info.formatted_address"));
+        assert!(!report.validation.reprompted);
+    }
+
+    #[test]
+    fn reports_problems_that_need_a_reprompt() {
+        let problems = validate_report("Sorry, I can't help with that.", "Springfield").unwrap_err();
+        assert!(problems[0].starts_with("Response is not valid JSON"));
+
+        let mut reply = minimal_reply();
+        reply.as_object_mut().unwrap().remove("legal_resources");
+        let problems = validate(&reply).unwrap_err();
+        assert_eq!(problems, vec!["Missing required section 'legal_resources'".to_string()]);
+
+        let mut reply = minimal_reply();
+        reply["historical_trends"]["population"] = json!([{ "year": 1900, "count": "lots" }]);
+        let problems = validate(&reply).unwrap_err();
+        assert!(problems[0].starts_with("'historical_trends.population' must contain"));
+    }
+
+    #[test]
+    fn estimated_report_uses_allowed_enum_values() {
+        use crate::services::analysis_fallback::{estimated_report, NearbyData};
+
+        let report = estimated_report("Springfield", None, &NearbyData::default());
+        let mut root = serde_json::to_value(&report).unwrap();
+        let mut v = Validator::default();
+        v.report(&mut root, "Springfield");
+
+        let replaced: Vec<&FieldIssue> = v
+            .repaired
+            .iter()
+            .filter(|issue| issue.detail.starts_with("replaced unknown value"))
+            .collect();
+        assert!(replaced.is_empty(), "{:?}", replaced.iter().map(|i| &i.field).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn extracts_json_from_a_fenced_reply() {
+        let content = "Here you go:\n```json\n{\"a\": 1}\n```";
+        assert_eq!(extract_json(content), "{\"a\": 1}");
+    }
+}
@@ -0,0 +1,656 @@
+use chrono::{DateTime, Duration, Utc};
+use lru::LruCache;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tokio::sync::OnceCell;
+
+use super::upstream::UpstreamError;
+use crate::config::CacheConfig;
+use crate::models::cache_entries::CacheEntry;
+
+/// Response header telling the client whether the body came from the cache.
+pub const CACHE_HEADER: &str = "X-Cache";
+
+const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// What a cached payload is. Stored in `cache_entries.type` and used as the
+/// leading segment of every key, so entries of one kind share a prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Geocode,
+    Gemini,
+    Chat,
+    Analysis,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 4] = [
+        CacheKind::Geocode,
+        CacheKind::Gemini,
+        CacheKind::Chat,
+        CacheKind::Analysis,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheKind::Geocode => "geocode",
+            CacheKind::Gemini => "gemini",
+            CacheKind::Chat => "chat",
+            CacheKind::Analysis => "analysis",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            CacheKind::Geocode => 0,
+            CacheKind::Gemini => 1,
+            CacheKind::Chat => 2,
+            CacheKind::Analysis => 3,
+        }
+    }
+}
+
+/// Whether a response was served without calling upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// The answer was not looked for in the cache at all
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn header_value(self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// Lower-cases, trims and collapses runs of whitespace so that trivially
+/// different spellings of the same query share a cache entry.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Identifies one cacheable upstream request.
+///
+/// The key is `<kind>:[<label>:]<sha256 of the request>`; the optional
+/// label (e.g. the geocoded query) keeps keys greppable and purgeable by
+/// prefix. The request is hashed exactly as given (object keys are already
+/// ordered, serde_json's map being a BTreeMap). Callers whose queries may
+/// differ trivially normalize them with `normalize_text` first; prompts and
+/// payloads are left alone, since case matters there. The request itself
+/// is stored next to the response so an entry can be force-refreshed later.
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    pub key: String,
+    pub kind: CacheKind,
+    pub request: Value,
+}
+
+impl CacheKey {
+    pub fn new(kind: CacheKind, label: Option<&str>, request: Value) -> Self {
+        let canonical = request.to_string();
+        let digest = hex::encode(Sha256::digest(canonical.as_bytes()));
+
+        let key = match label.map(normalize_text).filter(|l| !l.is_empty()) {
+            Some(label) => format!("{}:{}:{}", kind.as_str(), label, digest),
+            None => format!("{}:{}", kind.as_str(), digest),
+        };
+
+        Self { key, kind, request }
+    }
+}
+
+/// Format version written next to the request and response. An upstream
+/// response may itself have `request` and `response` fields, so only rows
+/// carrying this marker are unwrapped.
+const STORED_VERSION: u64 = 1;
+
+/// A row's `data`, as written by this or an earlier version.
+enum Stored {
+    /// `{"__v": 1, "request": ..., "response": ...}`
+    Wrapped { request: Value, response: Value },
+    /// The bare response, from before requests were recorded
+    Bare(Value),
+}
+
+impl Stored {
+    fn wrap(request: &Value, response: &Value) -> Value {
+        json!({ "__v": STORED_VERSION, "request": request, "response": response })
+    }
+
+    fn parse(data: Value) -> Self {
+        match data {
+            Value::Object(mut map) if map.get("__v").and_then(Value::as_u64) == Some(STORED_VERSION) => {
+                Stored::Wrapped {
+                    request: map.remove("request").unwrap_or(Value::Null),
+                    response: map.remove("response").unwrap_or(Value::Null),
+                }
+            }
+            other => Stored::Bare(other),
+        }
+    }
+
+    fn request(self) -> Option<Value> {
+        match self {
+            Stored::Wrapped { request, .. } => Some(request),
+            Stored::Bare(_) => None,
+        }
+    }
+
+    fn response(self) -> Value {
+        match self {
+            Stored::Wrapped { response, .. } | Stored::Bare(response) => response,
+        }
+    }
+}
+
+struct MemoryEntry {
+    kind: CacheKind,
+    data: Value,
+    expires_at: Instant,
+}
+
+/// One in-progress upstream call that identical requests wait on.
+type Flight = Arc<OnceCell<Result<Value, UpstreamError>>>;
+
+#[derive(Default)]
+struct KindCounters {
+    memory_hits: AtomicU64,
+    database_hits: AtomicU64,
+    coalesced: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KindStats {
+    pub r#type: &'static str,
+    pub memory_hits: u64,
+    pub database_hits: u64,
+    pub coalesced: u64,
+    pub misses: u64,
+}
+
+/// A stored entry together with the request needed to refresh it.
+pub struct StoredEntry {
+    pub kind: Option<CacheKind>,
+    pub request: Option<Value>,
+}
+
+/// Server-side response cache: a bounded in-process LRU in front of the
+/// `cache_entries` table, with single-flight deduplication of misses.
+pub struct CacheService {
+    pool: PgPool,
+    geocode_ttl: Duration,
+    gemini_ttl: Duration,
+    chat_ttl: Duration,
+    analysis_ttl: Duration,
+    memory: Mutex<LruCache<String, MemoryEntry>>,
+    inflight: Mutex<HashMap<String, Flight>>,
+    counters: [KindCounters; 4],
+}
+
+impl CacheService {
+    pub fn new(pool: PgPool, config: &CacheConfig) -> Self {
+        let ttl = |ttl: std::time::Duration| Duration::from_std(ttl).unwrap_or(Duration::seconds(DEFAULT_TTL_SECS));
+
+        Self {
+            geocode_ttl: ttl(config.geocode_ttl),
+            gemini_ttl: ttl(config.gemini_ttl),
+            chat_ttl: ttl(config.chat_ttl),
+            analysis_ttl: ttl(config.analysis_ttl),
+            memory: Mutex::new(LruCache::new(config.memory_capacity)),
+            inflight: Mutex::new(HashMap::new()),
+            counters: Default::default(),
+            pool,
+        }
+    }
+
+    fn ttl(&self, kind: CacheKind) -> Duration {
+        match kind {
+            CacheKind::Geocode => self.geocode_ttl,
+            CacheKind::Gemini => self.gemini_ttl,
+            CacheKind::Chat => self.chat_ttl,
+            CacheKind::Analysis => self.analysis_ttl,
+        }
+    }
+
+    fn count(&self, kind: CacheKind, counter: fn(&KindCounters) -> &AtomicU64) {
+        counter(&self.counters[kind.index()]).fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Serves the request from memory, then the database, and only then
+    /// runs `fetch`. Concurrent callers for the same key share one `fetch`:
+    /// the first runs it, the rest wait and receive a clone of its result,
+    /// errors included. Successful results are written to both tiers.
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        cache_key: &CacheKey,
+        fetch: F,
+    ) -> (Result<Value, UpstreamError>, CacheStatus)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Value, UpstreamError>>,
+    {
+        self.get_or_fetch_checked(cache_key, |_| true, fetch).await
+    }
+
+    /// `get_or_fetch` for callers that must parse what comes back. A stored
+    /// entry `usable` rejects is logged and treated as a miss, so `fetch`
+    /// replaces it instead of it being served until it expires.
+    pub async fn get_or_fetch_checked<F, Fut>(
+        &self,
+        cache_key: &CacheKey,
+        usable: impl Fn(&Value) -> bool,
+        fetch: F,
+    ) -> (Result<Value, UpstreamError>, CacheStatus)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Value, UpstreamError>>,
+    {
+        let key = cache_key.key.as_str();
+        let kind = cache_key.kind;
+        let usable = |data: Value| {
+            if usable(&data) {
+                return Some(data);
+            }
+            tracing::warn!("Cache entry [{}] is unreadable; refetching", key);
+            None
+        };
+
+        if let Some(data) = self.memory_get(key).and_then(usable) {
+            tracing::debug!("Cache HIT (memory) for [{}]", key);
+            self.count(kind, |c| &c.memory_hits);
+            return (Ok(data), CacheStatus::Hit);
+        }
+
+        let flight = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        // Only the caller that initializes the flight can observe a miss;
+        // everyone who waited on it was served without an upstream call.
+        let mut initialized = false;
+        let mut status = CacheStatus::Hit;
+        let initialized_ref = &mut initialized;
+        let status_ref = &mut status;
+
+        let result = flight
+            .get_or_init(|| async move {
+                *initialized_ref = true;
+                if let Some(data) = self.get(key).await.and_then(usable) {
+                    self.count(kind, |c| &c.database_hits);
+                    return Ok(data);
+                }
+
+                *status_ref = CacheStatus::Miss;
+                self.count(kind, |c| &c.misses);
+                let result = fetch().await;
+                if let Ok(data) = &result {
+                    self.put(cache_key, data).await;
+                }
+                result
+            })
+            .await
+            .clone();
+
+        if !initialized {
+            self.count(kind, |c| &c.coalesced);
+        }
+
+        {
+            let mut inflight = self.inflight.lock().unwrap();
+            if inflight.get(key).is_some_and(|current| Arc::ptr_eq(current, &flight)) {
+                inflight.remove(key);
+            }
+        }
+
+        (result, status)
+    }
+
+    /// Reads an entry from either tier without counting it as a hit or miss
+    /// and without ever calling upstream.
+    pub async fn peek(&self, key: &str) -> Option<Value> {
+        match self.memory_get(key) {
+            Some(data) => Some(data),
+            None => self.get(key).await,
+        }
+    }
+
+    /// `peek` against memory only, for callers probing many keys at once.
+    pub fn peek_memory(&self, key: &str) -> Option<Value> {
+        self.memory_get(key)
+    }
+
+    fn memory_get(&self, key: &str) -> Option<Value> {
+        let mut memory = self.memory.lock().unwrap();
+        match memory.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.data.clone()),
+            Some(_) => {
+                memory.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn memory_put(&self, key: &str, kind: CacheKind, data: &Value, expires_at: DateTime<Utc>) {
+        let Ok(remaining) = (expires_at - Utc::now()).to_std() else {
+            return;
+        };
+
+        self.memory.lock().unwrap().put(
+            key.to_string(),
+            MemoryEntry {
+                kind,
+                data: data.clone(),
+                expires_at: Instant::now() + remaining,
+            },
+        );
+    }
+
+    /// Returns the cached response for `key` from the database if present
+    /// and not expired, promoting it into memory. Database errors are logged
+    /// and treated as a miss.
+    async fn get(&self, key: &str) -> Option<Value> {
+        let result = sqlx::query_as::<_, CacheEntry>(
+            "SELECT * FROM cache_entries WHERE key = $1 AND (expires_at IS NULL OR expires_at > NOW())",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await;
+
+        match result {
+            Ok(Some(entry)) => {
+                let data = Stored::parse(entry.data).response();
+                tracing::debug!("Cache HIT (database) for [{}]", entry.key);
+                if let Some(kind) = CacheKind::parse(&entry.r#type) {
+                    // Rows without an expiry are kept in memory for one default TTL
+                    let expires_at = entry
+                        .expires_at
+                        .unwrap_or_else(|| Utc::now() + Duration::seconds(DEFAULT_TTL_SECS));
+                    self.memory_put(key, kind, &data, expires_at);
+                }
+                Some(data)
+            }
+            Ok(None) => {
+                tracing::debug!("Cache MISS for [{}]", key);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("Cache lookup failed for [{}]: {:?}", key, e);
+                None
+            }
+        }
+    }
+
+    /// Inserts or refreshes an entry in both tiers using the TTL configured
+    /// for its kind.
+    pub async fn put(&self, cache_key: &CacheKey, data: &Value) {
+        let entry = CacheEntry {
+            key: cache_key.key.clone(),
+            r#type: cache_key.kind.as_str().to_string(),
+            data: Stored::wrap(&cache_key.request, data),
+            created_at: Some(Utc::now()),
+            expires_at: Some(Utc::now() + self.ttl(cache_key.kind)),
+        };
+
+        if let Some(expires_at) = entry.expires_at {
+            self.memory_put(&entry.key, cache_key.kind, data, expires_at);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO cache_entries (key, type, data, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (key) DO UPDATE SET
+                type = EXCLUDED.type,
+                data = EXCLUDED.data,
+                created_at = EXCLUDED.created_at,
+                expires_at = EXCLUDED.expires_at
+            "#,
+        )
+        .bind(&entry.key)
+        .bind(&entry.r#type)
+        .bind(&entry.data)
+        .bind(entry.created_at)
+        .bind(entry.expires_at)
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to save cache entry [{}]: {:?}", entry.key, e);
+        }
+    }
+
+    // ============ Administration ============
+
+    /// Hit/miss counters per kind since the process started.
+    pub fn counters(&self) -> Vec<KindStats> {
+        CacheKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let c = &self.counters[kind.index()];
+                KindStats {
+                    r#type: kind.as_str(),
+                    memory_hits: c.memory_hits.load(Ordering::Relaxed),
+                    database_hits: c.database_hits.load(Ordering::Relaxed),
+                    coalesced: c.coalesced.load(Ordering::Relaxed),
+                    misses: c.misses.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    pub fn memory_len(&self) -> usize {
+        self.memory.lock().unwrap().len()
+    }
+
+    /// Row counts per `type` in the table, split into live and expired.
+    pub async fn entry_counts(&self) -> Result<Vec<(String, i64, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, i64, i64)>(
+            r#"
+            SELECT type,
+                   COUNT(*) FILTER (WHERE expires_at IS NULL OR expires_at > NOW()),
+                   COUNT(*) FILTER (WHERE expires_at <= NOW())
+            FROM cache_entries
+            GROUP BY type
+            ORDER BY type
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Deletes every entry matching `type` and/or key `prefix` from both
+    /// tiers. Returns the number of rows removed from the table.
+    pub async fn purge(&self, r#type: Option<&str>, prefix: Option<&str>) -> Result<u64, sqlx::Error> {
+        {
+            let mut memory = self.memory.lock().unwrap();
+            let doomed: Vec<String> = memory
+                .iter()
+                .filter(|(key, entry)| {
+                    r#type.is_none_or(|t| entry.kind.as_str() == t)
+                        && prefix.is_none_or(|p| key.starts_with(p))
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in doomed {
+                memory.pop(&key);
+            }
+        }
+
+        let like = prefix.map(|p| {
+            let escaped = p.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            format!("{}%", escaped)
+        });
+
+        let result = sqlx::query(
+            "DELETE FROM cache_entries WHERE ($1::text IS NULL OR type = $1) AND ($2::text IS NULL OR key LIKE $2)",
+        )
+        .bind(r#type)
+        .bind(like)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Looks up a stored entry regardless of expiry, for refreshing.
+    pub async fn stored(&self, key: &str) -> Result<Option<StoredEntry>, sqlx::Error> {
+        let entry = sqlx::query_as::<_, CacheEntry>("SELECT * FROM cache_entries WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(entry.map(|entry| StoredEntry {
+            kind: CacheKind::parse(&entry.r#type),
+            request: Stored::parse(entry.data).request(),
+        }))
+    }
+
+    /// Deletes expired rows from the table and expired entries from memory.
+    pub async fn sweep_expired(&self) -> Result<u64, sqlx::Error> {
+        {
+            let now = Instant::now();
+            let mut memory = self.memory.lock().unwrap();
+            let expired: Vec<String> = memory
+                .iter()
+                .filter(|(_, entry)| entry.expires_at <= now)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in expired {
+                memory.pop(&key);
+            }
+        }
+
+        let result = sqlx::query("DELETE FROM cache_entries WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Starts the background task that calls `sweep_expired` every
+    /// `interval`.
+    pub fn spawn_sweeper(self: Arc<Self>, interval: std::time::Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match self.sweep_expired().await {
+                    Ok(0) => {}
+                    Ok(deleted) => tracing::info!("Cache sweeper removed {} expired entries", deleted),
+                    Err(e) => tracing::warn!("Cache sweeper failed: {:?}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use futures_util::future::join_all;
+    use sqlx::postgres::PgPoolOptions;
+    use std::{num::NonZeroUsize, sync::atomic::AtomicUsize};
+
+    /// A cache whose database is never reachable, so every lookup that
+    /// misses memory ends up calling `fetch`.
+    fn cache() -> CacheService {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgres://terratruce@127.0.0.1:1/terratruce")
+            .unwrap();
+        let hour = std::time::Duration::from_secs(3600);
+        CacheService::new(
+            pool,
+            &CacheConfig {
+                geocode_ttl: hour,
+                gemini_ttl: hour,
+                chat_ttl: hour,
+                analysis_ttl: hour,
+                memory_capacity: NonZeroUsize::new(10).unwrap(),
+                sweep_interval: hour,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_share_one_fetch() {
+        let cache = cache();
+        let key = CacheKey::new(CacheKind::Geocode, Some("main st"), json!({ "q": "main st" }));
+        let fetches = AtomicUsize::new(0);
+
+        let callers = (0..8).map(|_| {
+            cache.get_or_fetch(&key, || async {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok(json!({ "results": ["12 Main St"] }))
+            })
+        });
+        let outcomes = join_all(callers).await;
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        for (result, _) in &outcomes {
+            assert_eq!(result.as_ref().unwrap(), &json!({ "results": ["12 Main St"] }));
+        }
+        let misses = outcomes.iter().filter(|(_, status)| *status == CacheStatus::Miss).count();
+        assert_eq!(misses, 1);
+
+        // The flight is gone and the value is in memory
+        let (result, status) = cache.get_or_fetch(&key, || async { unreachable!() }).await;
+        assert_eq!(result.unwrap(), json!({ "results": ["12 Main St"] }));
+        assert_eq!(status, CacheStatus::Hit);
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_an_error_and_it_is_not_cached() {
+        let cache = cache();
+        let key = CacheKey::new(CacheKind::Geocode, Some("nowhere"), json!({ "q": "nowhere" }));
+        let fetches = AtomicUsize::new(0);
+
+        let callers = (0..4).map(|_| {
+            cache.get_or_fetch(&key, || async {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Err(ApiError::GeocodeRateLimit.into())
+            })
+        });
+        let outcomes = join_all(callers).await;
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(outcomes.iter().all(|(result, _)| result.is_err()));
+
+        let (result, status) = cache.get_or_fetch(&key, || async { Ok(json!("found")) }).await;
+        assert_eq!(result.unwrap(), json!("found"));
+        assert_eq!(status, CacheStatus::Miss);
+    }
+}
@@ -0,0 +1,332 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+use crate::config::CircuitConfig;
+
+// A circuit breaker per upstream provider. After enough consecutive
+// failures the circuit opens and calls fail immediately instead of waiting
+// on a provider that is down. Once the open period is over, one probe call
+// is let through (half-open): success closes the circuit, failure opens it
+// again.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// One upstream's breaker state, as reported by `/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub name: &'static str,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Calls and failures within the error-rate window
+    pub recent_calls: usize,
+    pub recent_failures: usize,
+    /// `recent_failures / recent_calls`, or 0 with no recent calls
+    pub error_rate: f64,
+    pub window_secs: u64,
+    /// Seconds until an open circuit lets a probe through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    probing: bool,
+    /// (when, failed) for every finished call within the window
+    recent: VecDeque<(Instant, bool)>,
+}
+
+pub struct CircuitBreaker {
+    name: &'static str,
+    settings: CircuitConfig,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    fn new(name: &'static str, settings: CircuitConfig) -> Self {
+        Self {
+            name,
+            settings,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                probing: false,
+                recent: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Asks to make a call. Refused while the circuit is open, and while a
+    /// half-open probe is already in flight; the error is how long until
+    /// the next probe may be tried.
+    pub fn try_acquire(&self) -> Result<CircuitPermit<'_>, Duration> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state == CircuitState::Open {
+            let elapsed = inner.opened_at.elapsed();
+            if elapsed < self.settings.open_for {
+                return Err(self.settings.open_for - elapsed);
+            }
+            tracing::info!("Circuit for {} is half-open; sending a probe request", self.name);
+            inner.state = CircuitState::HalfOpen;
+        }
+
+        if inner.state == CircuitState::HalfOpen {
+            if inner.probing {
+                return Err(Duration::ZERO);
+            }
+            inner.probing = true;
+        }
+
+        Ok(CircuitPermit {
+            breaker: self,
+            finished: false,
+        })
+    }
+
+    pub fn snapshot(&self) -> CircuitSnapshot {
+        let mut inner = self.inner.lock().unwrap();
+        self.prune(&mut inner);
+
+        let recent_calls = inner.recent.len();
+        let recent_failures = inner.recent.iter().filter(|(_, failed)| *failed).count();
+        let retry_in_secs = (inner.state == CircuitState::Open).then(|| {
+            self.settings
+                .open_for
+                .saturating_sub(inner.opened_at.elapsed())
+                .as_secs_f64()
+                .ceil() as u64
+        });
+
+        CircuitSnapshot {
+            name: self.name,
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            recent_calls,
+            recent_failures,
+            error_rate: if recent_calls == 0 {
+                0.0
+            } else {
+                recent_failures as f64 / recent_calls as f64
+            },
+            window_secs: self.settings.window.as_secs(),
+            retry_in_secs,
+        }
+    }
+
+    fn record(&self, failed: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.recent.push_back((Instant::now(), failed));
+        self.prune(&mut inner);
+        inner.probing = false;
+
+        if !failed {
+            if inner.state != CircuitState::Closed {
+                tracing::info!("Circuit for {} closed after a successful probe", self.name);
+            }
+            inner.state = CircuitState::Closed;
+            inner.consecutive_failures = 0;
+            return;
+        }
+
+        inner.consecutive_failures += 1;
+        let trip = match inner.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => inner.consecutive_failures >= self.settings.failure_threshold,
+            CircuitState::Open => false,
+        };
+        if trip {
+            tracing::warn!(
+                "Circuit for {} opened after {} consecutive failures; failing fast for {}s",
+                self.name,
+                inner.consecutive_failures,
+                self.settings.open_for.as_secs()
+            );
+            inner.state = CircuitState::Open;
+            inner.opened_at = Instant::now();
+        }
+    }
+
+    fn prune(&self, inner: &mut Inner) {
+        while let Some((at, _)) = inner.recent.front() {
+            if at.elapsed() <= self.settings.window {
+                break;
+            }
+            inner.recent.pop_front();
+        }
+    }
+}
+
+/// Permission for one call. Report how it went with `success` or
+/// `failure`; a permit dropped without either (the caller gave up) frees a
+/// half-open probe slot without counting as an outcome.
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    finished: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn success(mut self) {
+        self.finished = true;
+        self.breaker.record(false);
+    }
+
+    pub fn failure(mut self) {
+        self.finished = true;
+        self.breaker.record(true);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.breaker.inner.lock().unwrap().probing = false;
+        }
+    }
+}
+
+/// Every upstream's breaker, keyed by provider name.
+pub struct CircuitBreakers {
+    settings: CircuitConfig,
+    breakers: Mutex<BTreeMap<&'static str, Arc<CircuitBreaker>>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: &CircuitConfig) -> Self {
+        Self {
+            settings: config.clone(),
+            breakers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The breaker for `name`, created on first use.
+    pub fn get(&self, name: &'static str) -> Arc<CircuitBreaker> {
+        self.breakers
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(name, self.settings.clone())))
+            .clone()
+    }
+
+    pub fn snapshots(&self) -> Vec<CircuitSnapshot> {
+        let breakers: Vec<Arc<CircuitBreaker>> = self.breakers.lock().unwrap().values().cloned().collect();
+        breakers.iter().map(|breaker| breaker.snapshot()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN_FOR: Duration = Duration::from_millis(50);
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            "test",
+            CircuitConfig {
+                failure_threshold: 3,
+                open_for: OPEN_FOR,
+                window: Duration::from_secs(60),
+            },
+        )
+    }
+
+    fn fail(breaker: &CircuitBreaker, times: usize) {
+        for _ in 0..times {
+            breaker.try_acquire().expect("closed circuit allows calls").failure();
+        }
+    }
+
+    #[test]
+    fn opens_at_the_failure_threshold() {
+        let breaker = breaker();
+
+        fail(&breaker, 2);
+        assert_eq!(breaker.snapshot().state, CircuitState::Closed);
+
+        fail(&breaker, 1);
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.state, CircuitState::Open);
+        assert_eq!(snapshot.consecutive_failures, 3);
+        assert_eq!(snapshot.recent_failures, 3);
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let breaker = breaker();
+
+        fail(&breaker, 2);
+        breaker.try_acquire().unwrap().success();
+        fail(&breaker, 2);
+
+        assert_eq!(breaker.snapshot().state, CircuitState::Closed);
+        assert_eq!(breaker.snapshot().consecutive_failures, 2);
+    }
+
+    #[test]
+    fn rejects_calls_while_open() {
+        let breaker = breaker();
+        fail(&breaker, 3);
+
+        let wait = breaker.try_acquire().err().expect("open circuit refuses calls");
+        assert!(wait > Duration::ZERO && wait <= OPEN_FOR, "{:?}", wait);
+    }
+
+    #[tokio::test]
+    async fn lets_one_probe_through_after_the_open_period() {
+        let breaker = breaker();
+        fail(&breaker, 3);
+        tokio::time::sleep(OPEN_FOR).await;
+
+        let probe = breaker.try_acquire().expect("probe after the open period");
+        assert_eq!(breaker.snapshot().state, CircuitState::HalfOpen);
+        assert_eq!(breaker.try_acquire().err(), Some(Duration::ZERO));
+
+        probe.success();
+        assert_eq!(breaker.snapshot().state, CircuitState::Closed);
+        assert_eq!(breaker.snapshot().consecutive_failures, 0);
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_failed_probe_opens_the_circuit_again() {
+        let breaker = breaker();
+        fail(&breaker, 3);
+        tokio::time::sleep(OPEN_FOR).await;
+
+        breaker.try_acquire().unwrap().failure();
+
+        assert_eq!(breaker.snapshot().state, CircuitState::Open);
+        assert!(breaker.try_acquire().is_err());
+    }
+
+    #[tokio::test]
+    async fn an_abandoned_probe_frees_the_slot() {
+        let breaker = breaker();
+        fail(&breaker, 3);
+        tokio::time::sleep(OPEN_FOR).await;
+
+        drop(breaker.try_acquire().unwrap());
+
+        assert_eq!(breaker.snapshot().state, CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+mod nominatim;
+mod opencage;
+
+use axum::{async_trait, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{future::Future, sync::Arc};
+
+use self::nominatim::Nominatim;
+use self::opencage::OpenCage;
+pub use self::opencage::normalize as normalize_opencage;
+use super::cache::{normalize_text, CacheKey, CacheKind, CacheService, CacheStatus};
+use super::http::HttpClient;
+use super::rate_limit::Pacer;
+use super::upstream::UpstreamError;
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::models::geocode::Geocoded;
+
+/// Results asked for on every forward lookup. Callers wanting fewer take
+/// them from the front, so one cached answer serves every limit.
+pub const FORWARD_LIMIT: u32 = 10;
+
+/// A forward (address to point) or reverse (point to address) lookup.
+/// Serialized as the cache key request, so it must stay stable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GeocodeLookup {
+    Reverse {
+        lat: f64,
+        lng: f64,
+        language: Option<String>,
+    },
+    Forward {
+        q: String,
+        language: Option<String>,
+    },
+}
+
+impl GeocodeLookup {
+    /// Keyed on the query (or point) so entries can be purged by prefix.
+    /// Queries differing only in case or spacing share an entry, as do
+    /// requests for different numbers of results.
+    pub fn cache_key(&self) -> CacheKey {
+        let mut request = self.clone();
+        let label = match &mut request {
+            GeocodeLookup::Forward { q, .. } => {
+                *q = normalize_text(q);
+                q.clone()
+            }
+            GeocodeLookup::Reverse { lat, lng, .. } => format!("{},{}", lat, lng),
+        };
+        CacheKey::new(CacheKind::Geocode, Some(&label), json!(request))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            GeocodeLookup::Forward { q, .. } => q.clone(),
+            GeocodeLookup::Reverse { lat, lng, .. } => format!("{}, {}", lat, lng),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn lookup(&self, lookup: &GeocodeLookup) -> Result<Geocoded, UpstreamError>;
+}
+
+/// A configured geocoder and the pace bulk and type-ahead lookups are sent
+/// to it at.
+struct Provider {
+    geocoder: Arc<dyn Geocoder>,
+    pacer: Pacer,
+}
+
+/// The configured geocoders, tried in `GEOCODE_PROVIDERS` order. A
+/// provider that fails for any reason other than a bad query hands over
+/// to the next; finding nothing is an answer, not a failure.
+pub struct GeocodeRouter {
+    providers: Vec<Provider>,
+}
+
+impl GeocodeRouter {
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        let geocode = &config.geocode;
+        let providers: Vec<Provider> = geocode
+            .providers
+            .iter()
+            .filter_map(|name| {
+                let (geocoder, rate): (Arc<dyn Geocoder>, u32) = match name.as_str() {
+                    "opencage" => (Arc::new(OpenCage::new(config, http.clone())), geocode.batch_rate_per_sec),
+                    "nominatim" => (Arc::new(Nominatim::new(config, http.clone())), geocode.nominatim_rate_per_sec),
+                    _ => return None,
+                };
+                Some(Provider {
+                    geocoder,
+                    pacer: Pacer::per_second(rate),
+                })
+            })
+            .collect();
+
+        let names: Vec<&str> = providers.iter().map(|p| p.geocoder.name()).collect();
+        tracing::info!("Geocoding providers: {}", names.join(" -> "));
+        Self { providers }
+    }
+
+    pub async fn lookup(&self, lookup: &GeocodeLookup) -> Result<Geocoded, UpstreamError> {
+        self.route(lookup, false).await
+    }
+
+    /// Tries each provider in turn, waiting for its pacer first when
+    /// `paced`, so a failover is held to the next provider's own rate.
+    async fn route(&self, lookup: &GeocodeLookup, paced: bool) -> Result<Geocoded, UpstreamError> {
+        let mut last_error = None;
+        for (i, provider) in self.providers.iter().enumerate() {
+            if paced {
+                provider.pacer.wait().await;
+            }
+            match provider.geocoder.lookup(lookup).await {
+                Ok(geocoded) => {
+                    tracing::info!("Geocoded '{}' via {}", lookup.describe(), provider.geocoder.name());
+                    return Ok(geocoded);
+                }
+                Err(e) if e.status != StatusCode::BAD_REQUEST && i + 1 < self.providers.len() => {
+                    tracing::warn!(
+                        "Geocoder {} failed ({}), failing over to {}",
+                        provider.geocoder.name(),
+                        e.status,
+                        self.providers[i + 1].geocoder.name()
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| ApiError::GeocodeNoProvider.into()))
+    }
+
+    /// `lookup` as the cached JSON value.
+    pub async fn fetch(&self, lookup: &GeocodeLookup) -> Result<Value, UpstreamError> {
+        let geocoded = self.lookup(lookup).await?;
+        Ok(json!(geocoded))
+    }
+
+    /// `fetch` paced per provider, for batch items and suggestions, which
+    /// can send many lookups in a burst.
+    pub async fn fetch_paced(&self, lookup: &GeocodeLookup) -> Result<Value, UpstreamError> {
+        let geocoded = self.route(lookup, true).await?;
+        Ok(json!(geocoded))
+    }
+}
+
+/// Reads a cached lookup. Entries written before geocoding had providers
+/// hold OpenCage's raw response, recognized by its `results` array and
+/// `status` object; anything else is unreadable.
+pub fn from_cached(data: Value) -> Option<Geocoded> {
+    if let Ok(geocoded) = serde_json::from_value::<Geocoded>(data.clone()) {
+        return Some(geocoded);
+    }
+    if !(data["results"].is_array() && data["status"].is_object()) {
+        return None;
+    }
+    Some(Geocoded {
+        provider: opencage::NAME.to_string(),
+        results: normalize_opencage(&data),
+        raw: data,
+    })
+}
+
+/// `lookup` through the response cache, calling `fetch` on a miss. An
+/// entry `from_cached` cannot read counts as a miss and is replaced.
+pub async fn cached<F, Fut>(
+    cache: &CacheService,
+    lookup: &GeocodeLookup,
+    fetch: F,
+) -> (Result<Geocoded, UpstreamError>, CacheStatus)
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Value, UpstreamError>>,
+{
+    let (result, status) = cache
+        .get_or_fetch_checked(&lookup.cache_key(), |data| from_cached(data.clone()).is_some(), fetch)
+        .await;
+    let result = result.and_then(|data| from_cached(data).ok_or_else(|| ApiError::GeocodeParseError.into()));
+    (result, status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_current_and_legacy_opencage_entries() {
+        let current = json!({ "provider": "nominatim", "results": [], "raw": [] });
+        assert_eq!(from_cached(current).unwrap().provider, "nominatim");
+
+        let legacy = json!({
+            "results": [{
+                "formatted": "12 Main St, Springfield",
+                "geometry": { "lat": 39.8, "lng": -89.6 },
+                "components": { "city": "Springfield" }
+            }],
+            "status": { "code": 200, "message": "OK" }
+        });
+        let geocoded = from_cached(legacy).unwrap();
+        assert_eq!(geocoded.provider, opencage::NAME);
+        assert_eq!(geocoded.results.len(), 1);
+        assert_eq!(geocoded.results[0].components.city.as_deref(), Some("Springfield"));
+    }
+
+    #[test]
+    fn rejects_entries_in_any_other_format() {
+        for data in [
+            json!({ "results": [{ "formatted": "12 Main St" }] }),
+            json!({ "status": { "code": 200 } }),
+            json!([{ "lat": "39.8", "lon": "-89.6" }]),
+            json!("corrupt"),
+            Value::Null,
+        ] {
+            assert!(from_cached(data.clone()).is_none(), "read {}", data);
+        }
+    }
+}
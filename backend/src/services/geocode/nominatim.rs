@@ -0,0 +1,137 @@
+use axum::async_trait;
+use reqwest::{header::USER_AGENT, Url};
+use serde_json::Value;
+use std::sync::Arc;
+
+use super::{GeocodeLookup, Geocoder, FORWARD_LIMIT};
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::models::geocode::{AddressComponents, Bounds, Coordinates, GeocodeResult, Geocoded};
+use crate::services::circuit::CircuitBreaker;
+use crate::services::http::{HttpClient, Upstream};
+use crate::services::upstream::{send_json, Labels, UpstreamError};
+
+/// Provider and circuit breaker name
+pub const NAME: &str = "nominatim";
+
+const LABELS: Labels = Labels {
+    prefix: "NOMINATIM",
+    service: "Nominatim",
+};
+
+/// Nominatim's usage policy requires an identifying User-Agent
+const CLIENT_NAME: &str = "TerraTruce-backend";
+
+/// A Nominatim server (the public OpenStreetMap one or self-hosted). It
+/// needs no key, so it also covers for OpenCage when that key is unset.
+pub struct Nominatim {
+    http: Arc<HttpClient>,
+    breaker: Arc<CircuitBreaker>,
+    base_url: String,
+}
+
+impl Nominatim {
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        Self {
+            breaker: http.breaker(NAME),
+            http,
+            base_url: config.upstreams.nominatim_base_url.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for Nominatim {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn lookup(&self, lookup: &GeocodeLookup) -> Result<Geocoded, UpstreamError> {
+        let mut params = vec![("format", "jsonv2".to_string()), ("addressdetails", "1".to_string())];
+        let (path, language) = match lookup {
+            GeocodeLookup::Forward { q, language } => {
+                params.push(("q", q.clone()));
+                params.push(("limit", FORWARD_LIMIT.to_string()));
+                ("search", language)
+            }
+            GeocodeLookup::Reverse { lat, lng, language } => {
+                params.push(("lat", lat.to_string()));
+                params.push(("lon", lng.to_string()));
+                ("reverse", language)
+            }
+        };
+        if let Some(language) = language {
+            params.push(("accept-language", language.clone()));
+        }
+
+        let url = Url::parse_with_params(&format!("{}/{}", self.base_url, path), &params).map_err(|e| {
+            tracing::error!("Invalid Nominatim URL: {:?}", e);
+            UpstreamError::from(ApiError::NominatimServiceError)
+        })?;
+
+        let request = self.http.client().get(url).header(USER_AGENT, CLIENT_NAME);
+        let raw = send_json(&self.http, Upstream::Geocode, &self.breaker, request, LABELS).await?;
+        Ok(Geocoded {
+            provider: NAME.to_string(),
+            results: normalize(&raw),
+            raw,
+        })
+    }
+}
+
+/// Normalizes a `search` array or a single `reverse` place. A reverse
+/// lookup that found nothing answers `{"error": "Unable to geocode"}`.
+fn normalize(data: &Value) -> Vec<GeocodeResult> {
+    match data {
+        Value::Array(places) => places.iter().filter_map(result).collect(),
+        Value::Object(place) if !place.contains_key("error") => result(data).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn result(place: &Value) -> Option<GeocodeResult> {
+    let address = &place["address"];
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| address[*key].as_str())
+            .map(str::to_string)
+    };
+
+    // [south, north, west, east], as strings
+    let bbox: Vec<f64> = place["boundingbox"]
+        .as_array()
+        .map(|edges| edges.iter().filter_map(number).collect())
+        .unwrap_or_default();
+    let bounds = (bbox.len() == 4).then(|| Bounds {
+        northeast: Coordinates { lat: bbox[1], lng: bbox[3] },
+        southwest: Coordinates { lat: bbox[0], lng: bbox[2] },
+    });
+
+    Some(GeocodeResult {
+        formatted_address: place["display_name"].as_str().unwrap_or_default().to_string(),
+        coordinates: Coordinates {
+            lat: number(&place["lat"])?,
+            lng: number(&place["lon"])?,
+        },
+        components: AddressComponents {
+            country: first(&["country"]),
+            country_code: first(&["country_code"]).map(|code| code.to_uppercase()),
+            state: first(&["state", "province", "region"]),
+            county: first(&["county", "state_district"]),
+            city: first(&["city", "town", "village", "hamlet", "municipality"]),
+            postcode: first(&["postcode"]),
+            suburb: first(&["suburb", "neighbourhood", "quarter", "city_district"]),
+            road: first(&["road"]),
+        },
+        timezone: None,
+        currency: None,
+        confidence: None,
+        bounds,
+        provider: NAME.to_string(),
+    })
+}
+
+/// Nominatim sends coordinates as strings
+fn number(value: &Value) -> Option<f64> {
+    value.as_str()?.parse().ok()
+}
@@ -0,0 +1,134 @@
+use axum::async_trait;
+use reqwest::Url;
+use serde_json::Value;
+use std::sync::Arc;
+
+use super::{GeocodeLookup, Geocoder, FORWARD_LIMIT};
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::models::geocode::{AddressComponents, Bounds, Coordinates, GeocodeResult, Geocoded};
+use crate::services::circuit::CircuitBreaker;
+use crate::services::http::{HttpClient, Upstream};
+use crate::services::upstream::{send_json, Labels, UpstreamError};
+
+/// Provider and circuit breaker name
+pub const NAME: &str = "opencage";
+
+const LABELS: Labels = Labels {
+    prefix: "GEOCODE",
+    service: "OpenCage API",
+};
+
+/// OpenCage's `geocode/v1/json`, which takes an address or a "lat,lng"
+/// point in the same `q` parameter.
+pub struct OpenCage {
+    http: Arc<HttpClient>,
+    breaker: Arc<CircuitBreaker>,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenCage {
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        Self {
+            breaker: http.breaker(NAME),
+            http,
+            base_url: config.upstreams.opencage_base_url.clone(),
+            api_key: config.keys.opencage.clone(),
+        }
+    }
+
+    fn api_key(&self) -> Result<&str, UpstreamError> {
+        match self.api_key.as_deref() {
+            Some("") => {
+                tracing::error!("OPENCAGE_API_KEY is empty");
+                Err(ApiError::GeocodeKeyEmpty.into())
+            }
+            Some(key) => Ok(key),
+            None => {
+                tracing::error!("OPENCAGE_API_KEY not found in environment");
+                Err(ApiError::GeocodeKeyMissing.into())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for OpenCage {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn lookup(&self, lookup: &GeocodeLookup) -> Result<Geocoded, UpstreamError> {
+        let api_key = self.api_key()?;
+
+        let (q, limit, language) = match lookup {
+            GeocodeLookup::Forward { q, language } => (q.clone(), FORWARD_LIMIT, language),
+            GeocodeLookup::Reverse { lat, lng, language } => (format!("{},{}", lat, lng), 1, language),
+        };
+        let mut params = vec![("q", q), ("key", api_key.to_string()), ("limit", limit.to_string())];
+        if let Some(language) = language {
+            params.push(("language", language.clone()));
+        }
+
+        let url = Url::parse_with_params(&format!("{}/json", self.base_url), &params).map_err(|e| {
+            tracing::error!("Invalid OpenCage URL: {:?}", e);
+            UpstreamError::from(ApiError::GeocodeServiceError)
+        })?;
+
+        let raw = send_json(&self.http, Upstream::Geocode, &self.breaker, self.http.client().get(url), LABELS).await?;
+        Ok(Geocoded {
+            provider: NAME.to_string(),
+            results: normalize(&raw),
+            raw,
+        })
+    }
+}
+
+/// Normalizes OpenCage's `results`. Entries without coordinates are dropped.
+pub fn normalize(data: &Value) -> Vec<GeocodeResult> {
+    let Some(results) = data["results"].as_array() else {
+        return Vec::new();
+    };
+    results.iter().filter_map(result).collect()
+}
+
+fn result(result: &Value) -> Option<GeocodeResult> {
+    let components = &result["components"];
+    let annotations = &result["annotations"];
+    // The first of several OpenCage component names that is present
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| components[*key].as_str())
+            .map(str::to_string)
+    };
+
+    Some(GeocodeResult {
+        formatted_address: result["formatted"].as_str().unwrap_or_default().to_string(),
+        coordinates: coordinates(&result["geometry"])?,
+        components: AddressComponents {
+            country: first(&["country"]),
+            country_code: first(&["country_code"]).map(|code| code.to_uppercase()),
+            state: first(&["state", "province", "region", "state_code"]),
+            county: first(&["county", "state_district"]),
+            city: first(&["city", "town", "village", "hamlet", "municipality"]),
+            postcode: first(&["postcode"]),
+            suburb: first(&["suburb", "neighbourhood", "quarter", "city_district"]),
+            road: first(&["road"]),
+        },
+        timezone: annotations["timezone"]["name"].as_str().map(str::to_string),
+        currency: annotations["currency"]["iso_code"].as_str().map(str::to_string),
+        confidence: result["confidence"].as_u64().map(|c| c.min(10) as u8),
+        bounds: coordinates(&result["bounds"]["northeast"])
+            .zip(coordinates(&result["bounds"]["southwest"]))
+            .map(|(northeast, southwest)| Bounds { northeast, southwest }),
+        provider: NAME.to_string(),
+    })
+}
+
+fn coordinates(value: &Value) -> Option<Coordinates> {
+    Some(Coordinates {
+        lat: value["lat"].as_f64()?,
+        lng: value["lng"].as_f64()?,
+    })
+}
@@ -0,0 +1,357 @@
+use chrono::{DateTime, Utc};
+use reqwest::{header::{HeaderMap, RETRY_AFTER}, Method, RequestBuilder, Response, StatusCode};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::{sleep, timeout, Instant};
+
+use super::circuit::{CircuitBreaker, CircuitBreakers, CircuitSnapshot};
+use crate::config::{CircuitConfig, HttpConfig};
+
+// One pooled client for every upstream call. Each upstream gets its own
+// timeout, and calls that fail with a retryable status are retried with
+// jittered exponential backoff inside a per-request time budget. Every call
+// also goes through its provider's circuit breaker.
+
+const BACKOFF_BASE: Duration = Duration::from_millis(250);
+const BACKOFF_CAP: Duration = Duration::from_secs(4);
+
+/// The upstreams the backend calls, each with its own timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upstream {
+    Ai,
+    Geocode,
+    Maps,
+}
+
+/// Why a request produced no response.
+#[derive(Debug)]
+pub enum TransportError {
+    /// The upstream's timeout elapsed before the response (or the next
+    /// streamed chunk) arrived
+    Timeout(Duration),
+    /// No connection could be opened; the request was never sent
+    Connect(reqwest::Error),
+    Other(reqwest::Error),
+    /// The provider's circuit is open; nothing was sent. Holds the time
+    /// until the next probe may go through.
+    CircuitOpen(Duration),
+}
+
+impl TransportError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, TransportError::Timeout(_))
+    }
+
+    pub fn is_connect(&self) -> bool {
+        matches!(self, TransportError::Connect(_))
+    }
+
+    /// Classifies a reqwest failure on a call bounded by `limit`. The URL
+    /// is dropped from the error since several upstreams take their API
+    /// key as a query parameter, and these errors reach clients.
+    pub fn from_reqwest(e: reqwest::Error, limit: Duration) -> Self {
+        if e.is_timeout() {
+            TransportError::Timeout(limit)
+        } else if e.is_connect() {
+            TransportError::Connect(e.without_url())
+        } else {
+            TransportError::Other(e.without_url())
+        }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Timeout(limit) => write!(f, "no response within {}s", limit.as_secs()),
+            TransportError::Connect(e) | TransportError::Other(e) => write!(f, "{}", e),
+            TransportError::CircuitOpen(retry_in) => write!(
+                f,
+                "circuit open after repeated failures; next attempt allowed in {}s",
+                retry_in.as_secs()
+            ),
+        }
+    }
+}
+
+pub struct HttpClient {
+    client: reqwest::Client,
+    ai_timeout: Duration,
+    geocode_timeout: Duration,
+    maps_timeout: Duration,
+    max_retries: u32,
+    retry_budget: Duration,
+    breakers: CircuitBreakers,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpConfig, circuit: &CircuitConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self {
+            client,
+            ai_timeout: config.ai_timeout,
+            geocode_timeout: config.geocode_timeout,
+            maps_timeout: config.maps_timeout,
+            max_retries: config.max_retries,
+            retry_budget: config.retry_budget,
+            breakers: CircuitBreakers::new(circuit),
+        }
+    }
+
+    /// The pooled client, for building requests.
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// The circuit breaker for one provider, such as `gemini` or `opencage`.
+    pub fn breaker(&self, name: &'static str) -> Arc<CircuitBreaker> {
+        self.breakers.get(name)
+    }
+
+    /// The state of every provider's circuit breaker.
+    pub fn circuits(&self) -> Vec<CircuitSnapshot> {
+        self.breakers.snapshots()
+    }
+
+    pub fn timeout(&self, upstream: Upstream) -> Duration {
+        match upstream {
+            Upstream::Ai => self.ai_timeout,
+            Upstream::Geocode => self.geocode_timeout,
+            Upstream::Maps => self.maps_timeout,
+        }
+    }
+
+    /// Sends a request whose whole response, body included, must arrive
+    /// within the upstream's timeout. Retryable failures are retried.
+    pub async fn send(
+        &self,
+        upstream: Upstream,
+        breaker: &CircuitBreaker,
+        request: RequestBuilder,
+    ) -> Result<Response, TransportError> {
+        let limit = self.timeout(upstream);
+        self.send_through(breaker, request.timeout(limit), |request| async move {
+            request.send().await.map_err(|e| TransportError::from_reqwest(e, limit))
+        })
+        .await
+    }
+
+    /// Sends a request whose body is read as a stream. Only the wait for
+    /// the response headers is bounded here; the caller bounds the gap
+    /// between chunks with the same `timeout`.
+    pub async fn open_stream(
+        &self,
+        upstream: Upstream,
+        breaker: &CircuitBreaker,
+        request: RequestBuilder,
+    ) -> Result<Response, TransportError> {
+        let limit = self.timeout(upstream);
+        self.send_through(breaker, request, |request| async move {
+            match timeout(limit, request.send()).await {
+                Ok(result) => result.map_err(|e| TransportError::from_reqwest(e, limit)),
+                Err(_) => Err(TransportError::Timeout(limit)),
+            }
+        })
+        .await
+    }
+
+    /// Sends through the breaker, failing fast while it is open. A 5xx
+    /// or a transport failure left after retries counts against it.
+    async fn send_through<F, Fut>(
+        &self,
+        breaker: &CircuitBreaker,
+        request: RequestBuilder,
+        send: F,
+    ) -> Result<Response, TransportError>
+    where
+        F: Fn(RequestBuilder) -> Fut,
+        Fut: Future<Output = Result<Response, TransportError>>,
+    {
+        let permit = match breaker.try_acquire() {
+            Ok(permit) => permit,
+            Err(retry_in) => {
+                tracing::debug!("Circuit for {} is open; failing fast", breaker.name());
+                // Whole seconds, rounded up, so a client that waits that long gets through
+                return Err(TransportError::CircuitOpen(Duration::from_secs(retry_in.as_secs_f64().ceil() as u64)));
+            }
+        };
+
+        let result = self.send_with_retries(breaker.name(), request, send).await;
+        match &result {
+            Ok(response) if response.status().is_server_error() => permit.failure(),
+            Ok(_) => permit.success(),
+            Err(_) => permit.failure(),
+        }
+        result
+    }
+
+    async fn send_with_retries<F, Fut>(
+        &self,
+        provider: &str,
+        request: RequestBuilder,
+        send: F,
+    ) -> Result<Response, TransportError>
+    where
+        F: Fn(RequestBuilder) -> Fut,
+        Fut: Future<Output = Result<Response, TransportError>>,
+    {
+        let started = Instant::now();
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| is_idempotent(r.method()));
+
+        let mut attempt = 0;
+        let mut next = request;
+        loop {
+            // Bodies that can't be cloned (streams) are sent exactly once
+            let retry = next.try_clone();
+            let result = send(next).await;
+
+            let delay = match &result {
+                Ok(response) if is_retryable(response.status(), idempotent) => {
+                    retry_after(response.headers()).unwrap_or_else(|| backoff(attempt))
+                }
+                // The request never left, so it is always safe to resend
+                Err(TransportError::Connect(_)) => backoff(attempt),
+                _ => return result,
+            };
+
+            let Some(retry) = retry else { return result };
+            if attempt >= self.max_retries || started.elapsed() + delay > self.retry_budget {
+                return result;
+            }
+
+            attempt += 1;
+            match &result {
+                Ok(response) => tracing::warn!(
+                    "{} returned {}; retry {} of {} in {}ms",
+                    provider,
+                    response.status(),
+                    attempt,
+                    self.max_retries,
+                    delay.as_millis()
+                ),
+                Err(e) => tracing::warn!(
+                    "{} unreachable ({}); retry {} of {} in {}ms",
+                    provider,
+                    e,
+                    attempt,
+                    self.max_retries,
+                    delay.as_millis()
+                ),
+            }
+            drop(result);
+            sleep(delay).await;
+            next = retry;
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+/// Only idempotent requests are resent after a response. Even a 429 or
+/// 503 does not prove a proxy in front of the upstream did not pass a POST
+/// on, and a resent completion is paid for twice; those calls fail over to
+/// the next provider instead.
+fn is_retryable(status: StatusCode, idempotent: bool) -> bool {
+    idempotent
+        && matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+}
+
+/// `Retry-After` as either delta-seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Full-jitter exponential backoff: a random wait up to
+/// `BACKOFF_BASE * 2^attempt`, capped at `BACKOFF_CAP`.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BACKOFF_BASE.saturating_mul(1 << attempt.min(16)).min(BACKOFF_CAP);
+    ceiling.mul_f64(fastrand::f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn retry_after_header(value: &str) -> Option<Duration> {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        retry_after(&headers)
+    }
+
+    #[test]
+    fn reads_retry_after_in_seconds() {
+        assert_eq!(retry_after_header("7"), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after_header(" 0 "), Some(Duration::ZERO));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after_header("soon"), None);
+    }
+
+    #[test]
+    fn reads_retry_after_as_an_http_date() {
+        let at = Utc::now() + chrono::Duration::seconds(30);
+        let wait = retry_after_header(&at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).unwrap();
+        assert!(wait > Duration::from_secs(28) && wait <= Duration::from_secs(30), "{:?}", wait);
+
+        // A date already past means retry now
+        assert_eq!(retry_after_header("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn backoff_is_jittered_below_a_doubling_capped_ceiling() {
+        for attempt in 0..20 {
+            let ceiling = (BACKOFF_BASE * 2u32.pow(attempt.min(16))).min(BACKOFF_CAP);
+            for _ in 0..50 {
+                let delay = backoff(attempt);
+                assert!(delay <= ceiling, "attempt {}: {:?} over {:?}", attempt, delay, ceiling);
+            }
+        }
+        assert!(BACKOFF_CAP < BACKOFF_BASE * 2u32.pow(5));
+
+        // Jitter: the waits for one attempt are not all the same
+        let delays: Vec<Duration> = (0..20).map(|_| backoff(3)).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn retries_only_idempotent_requests_on_transient_statuses() {
+        for status in [429, 502, 503, 504] {
+            let status = StatusCode::from_u16(status).unwrap();
+            assert!(is_retryable(status, true), "{} should be retried", status);
+            assert!(!is_retryable(status, false), "POST after {} should not be retried", status);
+        }
+        for status in [400, 401, 404, 500] {
+            assert!(!is_retryable(StatusCode::from_u16(status).unwrap(), true));
+        }
+
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+}
@@ -0,0 +1,208 @@
+use axum::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::stream::{sse_stream, Chunk, CompletionStream};
+use super::{open_stream, read_key, Completion, CompletionRequest, Labels, LlmProvider};
+use crate::config::Config;
+use crate::services::circuit::CircuitBreaker;
+use crate::services::http::{HttpClient, Upstream};
+use crate::services::upstream::{send_json, UpstreamError};
+
+const LABELS: Labels = Labels {
+    prefix: "GEMINI",
+    service: "Gemini API",
+};
+
+/// Gemini reads the key from this header, which keeps it out of URLs and logs
+const API_KEY_HEADER: &str = "x-goog-api-key";
+
+/// Google's Gemini `generateContent` API.
+pub struct GeminiProvider {
+    http: Arc<HttpClient>,
+    breaker: Arc<CircuitBreaker>,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl GeminiProvider {
+    /// Authenticates with `GEMINI_API_KEY`; the model defaults to
+    /// `gemini-2.5-flash` and can be changed with `GEMINI_MODEL`, the host
+    /// with `GEMINI_BASE_URL`.
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        Self {
+            breaker: http.breaker("gemini"),
+            http,
+            base_url: config.upstreams.gemini_base_url.clone(),
+            model: config.upstreams.gemini_model.clone(),
+            api_key: config.keys.gemini.clone(),
+        }
+    }
+
+    /// Sends a native `generateContent` body and returns Gemini's response
+    /// unchanged.
+    pub async fn generate_content(&self, model: Option<&str>, payload: &Value) -> Result<Value, UpstreamError> {
+        let api_key = read_key(self.api_key.as_deref(), "GEMINI_API_KEY", LABELS)?;
+        let model = model_name(model.unwrap_or(&self.model))?;
+        let url = format!("{}/models/{}:generateContent", self.base_url, model);
+
+        tracing::info!("Proxying Gemini AI request");
+        let request = self
+            .http
+            .client()
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header(API_KEY_HEADER, api_key)
+            .json(payload);
+
+        send_json(&self.http, Upstream::Ai, &self.breaker, request, LABELS).await
+    }
+}
+
+#[async_trait]
+impl LlmProvider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<Completion, UpstreamError> {
+        let model = request.model.as_deref().unwrap_or(&self.model).to_string();
+        let raw = self.generate_content(Some(&model), &to_gemini(request)).await?;
+
+        let content = candidate_text(&raw);
+
+        if content.is_empty() {
+            tracing::error!("Gemini response contained no text: {}", raw);
+            return Err(LABELS.error("PARSE_ERROR", "The Gemini API response contained no text").into());
+        }
+
+        Ok(Completion {
+            provider: self.name(),
+            model,
+            content,
+            usage: usage(&raw),
+            citations: citations(&raw).unwrap_or_default(),
+            raw,
+        })
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, UpstreamError> {
+        let model = request.model.as_deref().unwrap_or(&self.model).to_string();
+        let api_key = read_key(self.api_key.as_deref(), "GEMINI_API_KEY", LABELS)?;
+        let url = format!("{}/models/{}:streamGenerateContent?alt=sse", self.base_url, model_name(&model)?);
+
+        tracing::info!("Streaming Gemini AI request");
+        let post = self
+            .http
+            .client()
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header(API_KEY_HEADER, api_key)
+            .json(&to_gemini(request));
+
+        let response = open_stream(&self.http, &self.breaker, post, LABELS).await?;
+        Ok(sse_stream(self.name(), model, response, self.http.timeout(Upstream::Ai), LABELS, parse_chunk))
+    }
+}
+
+/// The model name goes into the URL path, and callers may choose it, so
+/// only plain names like `gemini-2.5-flash` are accepted.
+fn model_name(model: &str) -> Result<&str, UpstreamError> {
+    let plain = !model.is_empty()
+        && model
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if plain {
+        Ok(model)
+    } else {
+        tracing::warn!("Rejected Gemini model name {:?}", model);
+        Err(LABELS
+            .error("BAD_REQUEST", "The model name may only contain letters, digits, '.', '_' and '-'")
+            .into())
+    }
+}
+
+fn candidate_text(value: &Value) -> String {
+    value["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|part| part["text"].as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// `usageMetadata` in the OpenAI usage shape.
+fn usage(value: &Value) -> Option<Value> {
+    let metadata = &value["usageMetadata"];
+    metadata.is_object().then(|| {
+        json!({
+            "prompt_tokens": metadata["promptTokenCount"],
+            "completion_tokens": metadata["candidatesTokenCount"],
+            "total_tokens": metadata["totalTokenCount"]
+        })
+    })
+}
+
+/// Source URLs from search grounding, when it was used.
+fn citations(value: &Value) -> Option<Vec<String>> {
+    value["candidates"][0]["groundingMetadata"]["groundingChunks"]
+        .as_array()
+        .map(|chunks| {
+            chunks
+                .iter()
+                .filter_map(|chunk| chunk["web"]["uri"].as_str())
+                .map(str::to_string)
+                .collect()
+        })
+}
+
+/// Each streamed event is a partial `GenerateContentResponse`.
+fn parse_chunk(value: &Value) -> Chunk {
+    Chunk {
+        delta: Some(candidate_text(value)),
+        model: value["modelVersion"].as_str().map(str::to_string),
+        finish_reason: value["candidates"][0]["finishReason"].as_str().map(str::to_string),
+        usage: usage(value),
+        citations: citations(value),
+    }
+}
+
+/// Converts chat messages to Gemini's `contents`: system messages become
+/// the `systemInstruction` and `assistant` turns become `model` turns.
+fn to_gemini(request: &CompletionRequest) -> Value {
+    let system: Vec<&str> = request
+        .messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| m.content.as_str())
+        .collect();
+
+    let contents: Vec<Value> = request
+        .messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            let role = if m.role == "assistant" { "model" } else { "user" };
+            json!({ "role": role, "parts": [{ "text": m.content }] })
+        })
+        .collect();
+
+    let mut config = json!({});
+    if let Some(max_tokens) = request.max_tokens {
+        config["maxOutputTokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = request.temperature {
+        config["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        config["topP"] = json!(top_p);
+    }
+    if request.json_output {
+        config["responseMimeType"] = json!("application/json");
+    }
+
+    let mut body = json!({ "contents": contents, "generationConfig": config });
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+    }
+    body
+}
@@ -0,0 +1,336 @@
+mod gemini;
+mod openai;
+mod perplexity;
+mod stream;
+
+use axum::{async_trait, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+pub use self::gemini::GeminiProvider;
+use self::openai::OpenAiCompatible;
+use self::perplexity::PerplexityProvider;
+pub use self::stream::{CompletionStream, StreamEvent};
+use super::circuit::CircuitBreaker;
+use super::http::{HttpClient, Upstream};
+use super::quota::add_tokens;
+use super::upstream::{check_status, transport_error, Labels, UpstreamError};
+use crate::config::{Config, LlmConfig};
+use crate::error::ApiError;
+
+// ============ Requests and Responses ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// A provider-neutral chat completion request. Serialized as the cache key
+/// request, so it must stay stable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub messages: Vec<Message>,
+    /// Only honoured by the first provider of a route; fallbacks use their
+    /// own configured model since model names are provider-specific.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Ask the provider for a JSON-only reply where it supports that
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub json_output: bool,
+}
+
+impl CompletionRequest {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            model: None,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            json_output: false,
+        }
+    }
+}
+
+/// A finished completion, normalized across providers.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub provider: &'static str,
+    pub model: String,
+    pub content: String,
+    /// OpenAI-style `{prompt_tokens, completion_tokens, total_tokens}`
+    pub usage: Option<Value>,
+    pub citations: Vec<String>,
+    /// The provider's own response body
+    pub raw: Value,
+}
+
+impl Completion {
+    /// The completion as an OpenAI-style `chat.completion` body, which is
+    /// what `/api/details` has always returned. OpenAI-format providers pass
+    /// their body through untouched apart from the `provider` field.
+    pub fn to_chat_response(&self) -> Value {
+        let mut body = if self.raw.get("choices").is_some() {
+            self.raw.clone()
+        } else {
+            json!({
+                "object": "chat.completion",
+                "model": self.model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": self.content },
+                    "finish_reason": "stop"
+                }],
+                "usage": self.usage,
+                "citations": self.citations,
+            })
+        };
+        body["provider"] = json!(self.provider);
+        body
+    }
+
+    /// `usage.total_tokens`, when the provider reported it.
+    pub fn total_tokens(&self) -> Option<i64> {
+        self.usage.as_ref()?["total_tokens"].as_i64()
+    }
+}
+
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<Completion, UpstreamError>;
+
+    /// Starts a streamed completion. Errors before the first byte (bad key,
+    /// 429, 5xx) are returned here so the router can still fail over.
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, UpstreamError>;
+}
+
+// ============ Routing ============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UseCase {
+    Analysis,
+    Chat,
+    Ocr,
+}
+
+impl UseCase {
+    pub const ALL: [UseCase; 3] = [UseCase::Analysis, UseCase::Chat, UseCase::Ocr];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UseCase::Analysis => "analysis",
+            UseCase::Chat => "chat",
+            UseCase::Ocr => "ocr",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn route_env(self) -> &'static str {
+        match self {
+            UseCase::Analysis => "LLM_ROUTE_ANALYSIS",
+            UseCase::Chat => "LLM_ROUTE_CHAT",
+            UseCase::Ocr => "LLM_ROUTE_OCR",
+        }
+    }
+
+    fn route(self, config: &LlmConfig) -> &[String] {
+        match self {
+            UseCase::Analysis => &config.route_analysis,
+            UseCase::Chat => &config.route_chat,
+            UseCase::Ocr => &config.route_ocr,
+        }
+    }
+}
+
+/// Holds every configured provider and the ordered provider list for each
+/// use case. A provider that answers 429 or 5xx hands over to the next one.
+pub struct LlmRouter {
+    gemini: Arc<GeminiProvider>,
+    routes: [Vec<Arc<dyn LlmProvider>>; 3],
+}
+
+impl LlmRouter {
+    /// Routes come from `LLM_ROUTE_ANALYSIS`, `LLM_ROUTE_CHAT` and
+    /// `LLM_ROUTE_OCR`, each a list of `perplexity`, `gemini` and `openai`
+    /// already checked by `Config::load`.
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        let gemini = Arc::new(GeminiProvider::new(config, http.clone()));
+        let mut providers: HashMap<&'static str, Arc<dyn LlmProvider>> = HashMap::new();
+        providers.insert("perplexity", Arc::new(PerplexityProvider::new(config, http.clone())));
+        providers.insert("gemini", gemini.clone());
+        if let Some(openai) = OpenAiCompatible::new(config, http) {
+            providers.insert("openai", Arc::new(openai));
+        }
+
+        let routes = UseCase::ALL.map(|use_case| {
+            let route: Vec<Arc<dyn LlmProvider>> = use_case
+                .route(&config.llm)
+                .iter()
+                .filter_map(|name| match providers.get(name.as_str()) {
+                    Some(provider) => Some(provider.clone()),
+                    None => {
+                        tracing::warn!(
+                            "Ignoring unknown or unconfigured LLM provider '{}' in {}",
+                            name,
+                            use_case.route_env()
+                        );
+                        None
+                    }
+                })
+                .collect();
+
+            let names: Vec<&str> = route.iter().map(|p| p.name()).collect();
+            tracing::info!("LLM route for {}: {}", use_case.as_str(), names.join(" -> "));
+            route
+        });
+
+        Self { gemini, routes }
+    }
+
+    /// The Gemini provider, for the raw `/api/gemini` passthrough.
+    pub fn gemini(&self) -> &GeminiProvider {
+        &self.gemini
+    }
+
+    /// Runs the request against the use case's providers in order, moving
+    /// on when one is rate limited or failing. The usage reported by the
+    /// provider that answers is added to the current request's token count.
+    pub async fn complete(&self, use_case: UseCase, request: &CompletionRequest) -> Result<Completion, UpstreamError> {
+        self.with_failover(use_case, request, |provider, request| async move {
+            let completion = provider.complete(&request).await?;
+            if let Some(tokens) = completion.total_tokens() {
+                add_tokens(tokens);
+            }
+            Ok(completion)
+        })
+        .await
+    }
+
+    /// Like `complete`, but streams the reply. Failover only happens while
+    /// opening the stream; once deltas flow, errors end the stream.
+    pub async fn stream(&self, use_case: UseCase, request: &CompletionRequest) -> Result<CompletionStream, UpstreamError> {
+        self.with_failover(use_case, request, |provider, request| async move {
+            provider.stream(&request).await
+        })
+        .await
+    }
+
+    async fn with_failover<T, F, Fut>(
+        &self,
+        use_case: UseCase,
+        request: &CompletionRequest,
+        call: F,
+    ) -> Result<T, UpstreamError>
+    where
+        F: Fn(Arc<dyn LlmProvider>, CompletionRequest) -> Fut,
+        Fut: Future<Output = Result<T, UpstreamError>>,
+    {
+        let route = &self.routes[use_case.index()];
+
+        let mut last_error = None;
+        for (i, provider) in route.iter().enumerate() {
+            let attempt = if i == 0 {
+                request.clone()
+            } else {
+                CompletionRequest {
+                    model: None,
+                    ..request.clone()
+                }
+            };
+
+            match call(provider.clone(), attempt).await {
+                Ok(result) => return Ok(result),
+                Err(e) if should_fail_over(e.status) && i + 1 < route.len() => {
+                    tracing::warn!(
+                        "LLM provider {} failed for {} ({}), failing over to {}",
+                        provider.name(),
+                        use_case.as_str(),
+                        e.status,
+                        route[i + 1].name()
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            tracing::error!("No LLM provider is configured for {}", use_case.as_str());
+            ApiError::LlmNoProvider
+                .with_message(format!(
+                    "No usable provider is listed in {}. Use perplexity, gemini or openai.",
+                    use_case.route_env()
+                ))
+                .into()
+        }))
+    }
+}
+
+fn should_fail_over(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// ============ Shared Upstream Handling ============
+
+/// Checks an API key at call time so a missing key fails the request with
+/// a clear code instead of failing the boot.
+fn read_key<'a>(key: Option<&'a str>, var: &str, labels: Labels) -> Result<&'a str, UpstreamError> {
+    match key {
+        Some(key) if !key.is_empty() => Ok(key),
+        Some(_) => {
+            tracing::error!("{} is empty", var);
+            Err(labels
+                .error(
+                    "KEY_EMPTY",
+                    format!("The {} environment variable is empty. Please add a valid API key to the backend .env file.", var),
+                )
+                .into())
+        }
+        None => {
+            tracing::error!("{} not found in environment", var);
+            Err(labels
+                .error(
+                    "KEY_MISSING",
+                    format!("The {} environment variable is not set. Please add it to the backend .env file.", var),
+                )
+                .into())
+        }
+    }
+}
+
+/// Opens a streamed response, returning it once the upstream has accepted
+/// the request.
+async fn open_stream(
+    http: &HttpClient,
+    breaker: &CircuitBreaker,
+    request: reqwest::RequestBuilder,
+    labels: Labels,
+) -> Result<reqwest::Response, UpstreamError> {
+    let response = http
+        .open_stream(Upstream::Ai, breaker, request)
+        .await
+        .map_err(|e| transport_error(labels, &e))?;
+    check_status(response, labels).await
+}
@@ -0,0 +1,138 @@
+use axum::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::stream::{sse_stream, Chunk, CompletionStream};
+use super::{open_stream, read_key, Completion, CompletionRequest, Labels, LlmProvider};
+use crate::config::Config;
+use crate::services::circuit::CircuitBreaker;
+use crate::services::http::{HttpClient, Upstream};
+use crate::services::upstream::{send_json, UpstreamError};
+
+const LABELS: Labels = Labels {
+    prefix: "LLM",
+    service: "OpenAI-compatible service",
+};
+
+/// Any server that speaks the OpenAI `/chat/completions` API, such as a
+/// local model server or a stand-in used during development.
+pub struct OpenAiCompatible {
+    http: Arc<HttpClient>,
+    breaker: Arc<CircuitBreaker>,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatible {
+    /// Configured by `OPENAI_COMPAT_BASE_URL` (e.g. `http://localhost:8080/v1`),
+    /// `OPENAI_COMPAT_MODEL` and an optional `OPENAI_COMPAT_API_KEY`.
+    /// Returns `None` when no base URL is set.
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Option<Self> {
+        let base_url = config.upstreams.openai_compat_base_url.clone()?;
+
+        Some(Self {
+            breaker: http.breaker("openai"),
+            http,
+            base_url,
+            model: config.upstreams.openai_compat_model.clone(),
+            api_key: config.keys.openai_compat.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<Completion, UpstreamError> {
+        let request = self.post(&chat_body(request, &self.model))?;
+
+        tracing::info!("Proxying request to OpenAI-compatible service at {}", self.base_url);
+        let raw = send_json(&self.http, Upstream::Ai, &self.breaker, request, LABELS).await?;
+        parse_chat(self.name(), raw, LABELS)
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, UpstreamError> {
+        let mut body = chat_body(request, &self.model);
+        body["stream"] = json!(true);
+        // Without this the OpenAI API leaves usage out of streamed replies
+        body["stream_options"] = json!({ "include_usage": true });
+        let model = body["model"].as_str().unwrap_or_default().to_string();
+
+        tracing::info!("Streaming request to OpenAI-compatible service at {}", self.base_url);
+        let response = open_stream(&self.http, &self.breaker, self.post(&body)?, LABELS).await?;
+        Ok(sse_stream(self.name(), model, response, self.http.timeout(Upstream::Ai), LABELS, parse_chat_chunk))
+    }
+}
+
+impl OpenAiCompatible {
+    fn post(&self, body: &Value) -> Result<reqwest::RequestBuilder, UpstreamError> {
+        let mut http = self
+            .http
+            .client()
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(body);
+        if self.api_key.as_deref().is_some_and(|key| !key.is_empty()) {
+            let key = read_key(self.api_key.as_deref(), "OPENAI_COMPAT_API_KEY", LABELS)?;
+            http = http.header("Authorization", format!("Bearer {}", key));
+        }
+        Ok(http)
+    }
+}
+
+/// The `/chat/completions` body for a request, shared with Perplexity.
+pub(super) fn chat_body(request: &CompletionRequest, default_model: &str) -> Value {
+    let mut body = json!({
+        "model": request.model.as_deref().unwrap_or(default_model),
+        "messages": request.messages,
+    });
+
+    if let Some(max_tokens) = request.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    body
+}
+
+/// Reads the first choice out of a `chat.completion` body.
+pub(super) fn parse_chat(provider: &'static str, raw: Value, labels: Labels) -> Result<Completion, UpstreamError> {
+    let Some(content) = raw["choices"][0]["message"]["content"].as_str() else {
+        tracing::error!("{} response contained no message content", labels.service);
+        return Err(labels
+            .error("PARSE_ERROR", format!("The {} response contained no message content", labels.service))
+            .into());
+    };
+
+    Ok(Completion {
+        provider,
+        model: raw["model"].as_str().unwrap_or_default().to_string(),
+        content: content.to_string(),
+        usage: raw.get("usage").cloned(),
+        citations: Vec::new(),
+        raw,
+    })
+}
+
+/// Reads one `chat.completion.chunk`, shared with Perplexity, which also
+/// sends `citations`.
+pub(super) fn parse_chat_chunk(value: &Value) -> Chunk {
+    let choice = &value["choices"][0];
+    Chunk {
+        delta: choice["delta"]["content"].as_str().map(str::to_string),
+        model: value["model"].as_str().map(str::to_string),
+        finish_reason: choice["finish_reason"].as_str().map(str::to_string),
+        usage: value.get("usage").filter(|usage| usage.is_object()).cloned(),
+        citations: value["citations"]
+            .as_array()
+            .map(|urls| urls.iter().filter_map(Value::as_str).map(str::to_string).collect()),
+    }
+}
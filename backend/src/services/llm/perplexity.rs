@@ -0,0 +1,85 @@
+use axum::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::openai::{chat_body, parse_chat, parse_chat_chunk};
+use super::stream::{sse_stream, CompletionStream};
+use super::{open_stream, read_key, Completion, CompletionRequest, Labels, LlmProvider};
+use crate::config::Config;
+use crate::services::circuit::CircuitBreaker;
+use crate::services::http::{HttpClient, Upstream};
+use crate::services::upstream::{send_json, UpstreamError};
+
+const LABELS: Labels = Labels {
+    prefix: "AI",
+    service: "AI service",
+};
+
+/// Perplexity's OpenAI-style API, which also returns the web sources it
+/// used as `citations`.
+pub struct PerplexityProvider {
+    http: Arc<HttpClient>,
+    breaker: Arc<CircuitBreaker>,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl PerplexityProvider {
+    /// Authenticates with `AI_SERVICE_API_KEY`; the model defaults to
+    /// `sonar-pro` and can be changed with `PERPLEXITY_MODEL`, the host with
+    /// `PERPLEXITY_BASE_URL`.
+    pub fn new(config: &Config, http: Arc<HttpClient>) -> Self {
+        Self {
+            breaker: http.breaker("perplexity"),
+            http,
+            base_url: config.upstreams.perplexity_base_url.clone(),
+            model: config.upstreams.perplexity_model.clone(),
+            api_key: config.keys.ai_service.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for PerplexityProvider {
+    fn name(&self) -> &'static str {
+        "perplexity"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<Completion, UpstreamError> {
+        let request = self.post(&chat_body(request, &self.model))?;
+
+        tracing::info!("Proxying request to AI service");
+        let raw = send_json(&self.http, Upstream::Ai, &self.breaker, request, LABELS).await?;
+
+        let mut completion = parse_chat(self.name(), raw, LABELS)?;
+        completion.citations = completion.raw["citations"]
+            .as_array()
+            .map(|urls| urls.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        Ok(completion)
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, UpstreamError> {
+        let mut body = chat_body(request, &self.model);
+        body["stream"] = json!(true);
+        let model = body["model"].as_str().unwrap_or_default().to_string();
+
+        tracing::info!("Streaming request to AI service");
+        let response = open_stream(&self.http, &self.breaker, self.post(&body)?, LABELS).await?;
+        Ok(sse_stream(self.name(), model, response, self.http.timeout(Upstream::Ai), LABELS, parse_chat_chunk))
+    }
+}
+
+impl PerplexityProvider {
+    fn post(&self, body: &Value) -> Result<reqwest::RequestBuilder, UpstreamError> {
+        let api_key = read_key(self.api_key.as_deref(), "AI_SERVICE_API_KEY", LABELS)?;
+        Ok(self
+            .http
+            .client()
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(body))
+    }
+}
@@ -0,0 +1,190 @@
+use axum::body::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::VecDeque, pin::Pin, time::Duration};
+use tokio::time::timeout;
+
+use super::Labels;
+use crate::services::http::TransportError;
+use crate::services::upstream::{transport_error, UpstreamError};
+
+// Incremental parsing of an upstream `text/event-stream` body into
+// provider-neutral events. Dropping the stream drops the upstream response,
+// which closes the connection and cancels the generation. An upstream that
+// goes quiet for longer than its timeout ends the stream with a timeout.
+
+/// One event of a streamed completion.
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// The next piece of the assistant's reply
+    Delta(String),
+    /// Sent once, after the last delta
+    Done(StreamSummary),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamSummary {
+    pub provider: &'static str,
+    pub model: String,
+    pub finish_reason: Option<String>,
+    /// OpenAI-style `{prompt_tokens, completion_tokens, total_tokens}`
+    pub usage: Option<Value>,
+    pub citations: Vec<String>,
+}
+
+pub type CompletionStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, UpstreamError>> + Send>>;
+
+/// What one upstream `data:` payload contributes to the stream. Providers
+/// repeat or omit metadata freely, so only fields that are present are kept.
+#[derive(Debug, Default)]
+pub(super) struct Chunk {
+    pub delta: Option<String>,
+    pub model: Option<String>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Value>,
+    pub citations: Option<Vec<String>>,
+}
+
+type ByteStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+struct SseState {
+    body: ByteStream,
+    buffer: Vec<u8>,
+    pending: VecDeque<Result<StreamEvent, UpstreamError>>,
+    summary: StreamSummary,
+    parse: fn(&Value) -> Chunk,
+    idle_timeout: Duration,
+    labels: Labels,
+    finished: bool,
+}
+
+impl SseState {
+    /// Handles every complete line in the buffer.
+    fn drain_lines(&mut self) {
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.handle_line(String::from_utf8_lossy(&line).trim_end());
+        }
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return;
+        };
+        if data.is_empty() {
+            return;
+        }
+        if data == "[DONE]" {
+            self.finish();
+            return;
+        }
+
+        let value: Value = match serde_json::from_str(data) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!("Skipping malformed {} stream chunk: {}", self.labels.service, e);
+                return;
+            }
+        };
+
+        let chunk = (self.parse)(&value);
+        if let Some(model) = chunk.model {
+            self.summary.model = model;
+        }
+        if chunk.finish_reason.is_some() {
+            self.summary.finish_reason = chunk.finish_reason;
+        }
+        if chunk.usage.is_some() {
+            self.summary.usage = chunk.usage;
+        }
+        if let Some(citations) = chunk.citations {
+            self.summary.citations = citations;
+        }
+        if let Some(delta) = chunk.delta.filter(|d| !d.is_empty()) {
+            self.pending.push_back(Ok(StreamEvent::Delta(delta)));
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            self.pending
+                .push_back(Ok(StreamEvent::Done(std::mem::take(&mut self.summary))));
+        }
+    }
+}
+
+impl Drop for SseState {
+    fn drop(&mut self) {
+        if !self.finished {
+            tracing::info!(
+                "{} stream dropped before completion; cancelling upstream request",
+                self.labels.service
+            );
+        }
+    }
+}
+
+/// Turns a successful streaming response into a `CompletionStream`.
+pub(super) fn sse_stream(
+    provider: &'static str,
+    model: String,
+    response: reqwest::Response,
+    idle_timeout: Duration,
+    labels: Labels,
+    parse: fn(&Value) -> Chunk,
+) -> CompletionStream {
+    let state = SseState {
+        body: Box::pin(response.bytes_stream()),
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        summary: StreamSummary {
+            provider,
+            model,
+            ..StreamSummary::default()
+        },
+        parse,
+        idle_timeout,
+        labels,
+        finished: false,
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            let next = match timeout(state.idle_timeout, state.body.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    state.finished = true;
+                    let e = TransportError::Timeout(state.idle_timeout);
+                    return Some((Err(transport_error(state.labels, &e)), state));
+                }
+            };
+
+            match next {
+                Some(Ok(bytes)) => {
+                    state.buffer.extend_from_slice(&bytes);
+                    state.drain_lines();
+                }
+                Some(Err(e)) => {
+                    state.finished = true;
+                    let e = TransportError::from_reqwest(e, state.idle_timeout);
+                    return Some((Err(transport_error(state.labels, &e)), state));
+                }
+                None => {
+                    // Some servers close without a trailing newline or [DONE]
+                    state.buffer.push(b'\n');
+                    state.drain_lines();
+                    state.finish();
+                }
+            }
+        }
+    }))
+}
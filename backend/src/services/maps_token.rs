@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+// Short-lived tokens for the map proxy. The browser never sees the Google
+// Maps key: a signed-in user asks for a token, and the token is only good
+// for a few minutes and only from the origin that asked for it. Tokens are
+// HS256 JWTs with their own audience, signed with MAPS_TOKEN_SECRET.
+
+const AUDIENCE: &str = "terratruce-maps";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MapsClaims {
+    pub sub: String,
+    pub origin: String,
+    aud: String,
+    iat: i64,
+    exp: i64,
+}
+
+/// A freshly signed token and when it stops working.
+#[derive(Debug, Serialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub origin: String,
+    pub expires_at: DateTime<Utc>,
+    pub expires_in: u64,
+}
+
+pub struct MapsTokens {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl: Duration,
+}
+
+impl MapsTokens {
+    /// Signs with `MAPS_TOKEN_SECRET`. Without one, a random secret is made
+    /// for this process, so tokens stop working across restarts and are
+    /// not shared between instances.
+    pub fn new(config: &Config) -> Self {
+        let secret = match &config.keys.maps_token_secret {
+            Some(secret) => secret.clone(),
+            None => {
+                tracing::warn!("MAPS_TOKEN_SECRET is not set; map tokens are signed with a per-process secret");
+                format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+            }
+        };
+
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            ttl: config.maps.token_ttl,
+        }
+    }
+
+    pub fn issue(&self, user_id: Uuid, origin: &str) -> IssuedToken {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::seconds(self.ttl.as_secs() as i64);
+        let claims = MapsClaims {
+            sub: user_id.to_string(),
+            origin: origin.to_string(),
+            aud: AUDIENCE.to_string(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let token = encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+            .expect("HS256 signing cannot fail");
+
+        IssuedToken {
+            token,
+            origin: origin.to_string(),
+            expires_at,
+            expires_in: self.ttl.as_secs(),
+        }
+    }
+
+    /// Checks the signature, audience and expiry, and that the token was
+    /// issued to `origin`.
+    pub fn verify(&self, token: &str, origin: Option<&str>) -> Result<MapsClaims, ApiError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[AUDIENCE]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud"]);
+        validation.leeway = 0;
+
+        let claims = decode::<MapsClaims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => ApiError::MapsTokenExpired,
+                _ => ApiError::MapsTokenInvalid,
+            })?;
+
+        if origin != Some(claims.origin.as_str()) {
+            tracing::warn!(
+                "Map token for {} used from {:?} (user {})",
+                claims.origin,
+                origin,
+                claims.sub
+            );
+            return Err(ApiError::MapsTokenOriginMismatch);
+        }
+        Ok(claims)
+    }
+}
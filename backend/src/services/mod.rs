@@ -0,0 +1,12 @@
+pub mod analysis_fallback;
+pub mod analysis_prompt;
+pub mod analysis_schema;
+pub mod cache;
+pub mod circuit;
+pub mod geocode;
+pub mod http;
+pub mod llm;
+pub mod maps_token;
+pub mod quota;
+pub mod rate_limit;
+pub mod upstream;
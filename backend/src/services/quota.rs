@@ -0,0 +1,242 @@
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::{cell::Cell, fmt, future::Future, net::IpAddr, str::FromStr};
+use uuid::Uuid;
+
+use crate::config::LimitsConfig;
+
+// Daily request and token quotas, one row per caller per UTC day in
+//
+//   CREATE TABLE usage_quotas (
+//       subject    TEXT        NOT NULL,
+//       day        DATE        NOT NULL,
+//       requests   BIGINT      NOT NULL DEFAULT 0,
+//       tokens     BIGINT      NOT NULL DEFAULT 0,
+//       updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+//       PRIMARY KEY (subject, day)
+//   );
+//
+// Old days are never read again and can be deleted at will.
+
+tokio::task_local! {
+    static SPENT: Cell<Spent>;
+}
+
+/// Upstream work one request caused, charged to its caller afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Spent {
+    /// Tokens the AI providers reported
+    pub tokens: i64,
+    /// Geocoder lookups that missed the cache; each counts as a request
+    pub lookups: i64,
+}
+
+impl Spent {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Runs a request handler while recording the upstream work it causes,
+/// and returns that with its output.
+pub async fn metered<F: Future>(handler: F) -> (F::Output, Spent) {
+    SPENT
+        .scope(Cell::new(Spent::default()), async move {
+            let output = handler.await;
+            (output, SPENT.with(Cell::get))
+        })
+        .await
+}
+
+/// Adds tokens an upstream reported to the current request. Does nothing
+/// outside `metered`, so unmetered routes can call it freely.
+pub fn add_tokens(tokens: i64) {
+    spend(|spent| spent.tokens += tokens);
+}
+
+/// Records a paid geocoder lookup for the current request, like `add_tokens`.
+pub fn add_lookup() {
+    spend(|spent| spent.lookups += 1);
+}
+
+fn spend(add: impl FnOnce(&mut Spent)) {
+    let _ = SPENT.try_with(|cell| {
+        let mut spent = cell.get();
+        add(&mut spent);
+        cell.set(spent);
+    });
+}
+
+/// Who a limit applies to: the signed-in user, or the client IP for
+/// anonymous callers. Stored as `user:<uuid>` / `ip:<address>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subject {
+    User(Uuid),
+    Ip(IpAddr),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::User(id) => write!(f, "user:{}", id),
+            Subject::Ip(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+impl FromStr for Subject {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("user", id)) => Uuid::parse_str(id).map(Subject::User).map_err(|_| ()),
+            Some(("ip", ip)) => ip.parse().map(Subject::Ip).map_err(|_| ()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A caller's usage for one day.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct QuotaUsage {
+    pub subject: String,
+    pub day: NaiveDate,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// The outcome of counting a request against the quota.
+#[derive(Debug)]
+pub enum QuotaCheck {
+    Allowed(QuotaUsage),
+    RequestsExceeded(QuotaUsage),
+    TokensExceeded(QuotaUsage),
+}
+
+pub struct QuotaService {
+    pool: PgPool,
+    daily_requests: i64,
+    daily_tokens: i64,
+}
+
+impl QuotaService {
+    pub fn new(pool: PgPool, config: &LimitsConfig) -> Self {
+        Self {
+            pool,
+            daily_requests: config.daily_requests,
+            daily_tokens: config.daily_tokens,
+        }
+    }
+
+    pub fn today() -> NaiveDate {
+        Utc::now().date_naive()
+    }
+
+    /// Requests left today, or `None` when the request quota is off.
+    pub fn requests_left(&self, usage: &QuotaUsage) -> Option<i64> {
+        (self.daily_requests > 0).then(|| (self.daily_requests - usage.requests).max(0))
+    }
+
+    /// Tokens left today, or `None` when the token quota is off.
+    pub fn tokens_left(&self, usage: &QuotaUsage) -> Option<i64> {
+        (self.daily_tokens > 0).then(|| (self.daily_tokens - usage.tokens).max(0))
+    }
+
+    /// Counts one request for `subject` today, unless either quota is
+    /// already used up. The check and the increment are one statement, so
+    /// concurrent requests cannot overshoot the request quota.
+    pub async fn record_request(&self, subject: &Subject) -> Result<QuotaCheck, sqlx::Error> {
+        let subject = subject.to_string();
+        let day = Self::today();
+
+        let counted = sqlx::query_as::<_, QuotaUsage>(
+            "INSERT INTO usage_quotas (subject, day, requests) VALUES ($1, $2, 1)
+             ON CONFLICT (subject, day) DO UPDATE
+                SET requests = usage_quotas.requests + 1, updated_at = NOW()
+                WHERE ($3 = 0 OR usage_quotas.requests < $3)
+                  AND ($4 = 0 OR usage_quotas.tokens < $4)
+             RETURNING subject, day, requests, tokens",
+        )
+        .bind(&subject)
+        .bind(day)
+        .bind(self.daily_requests)
+        .bind(self.daily_tokens)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(usage) = counted {
+            return Ok(QuotaCheck::Allowed(usage));
+        }
+
+        let usage = self.usage_on(&subject, day).await?;
+        if self.daily_requests > 0 && usage.requests >= self.daily_requests {
+            Ok(QuotaCheck::RequestsExceeded(usage))
+        } else {
+            Ok(QuotaCheck::TokensExceeded(usage))
+        }
+    }
+
+    /// Adds what a request spent upstream to the day it was counted on.
+    pub async fn charge(&self, subject: &Subject, day: NaiveDate, spent: Spent) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE usage_quotas SET requests = requests + $3, tokens = tokens + $4, updated_at = NOW()
+             WHERE subject = $1 AND day = $2",
+        )
+        .bind(subject.to_string())
+        .bind(day)
+        .bind(spent.lookups)
+        .bind(spent.tokens)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Today's usage for `subject`, zero if it has made no requests.
+    pub async fn usage(&self, subject: &Subject) -> Result<QuotaUsage, sqlx::Error> {
+        self.usage_on(&subject.to_string(), Self::today()).await
+    }
+
+    async fn usage_on(&self, subject: &str, day: NaiveDate) -> Result<QuotaUsage, sqlx::Error> {
+        let usage = sqlx::query_as::<_, QuotaUsage>(
+            "SELECT subject, day, requests, tokens FROM usage_quotas WHERE subject = $1 AND day = $2",
+        )
+        .bind(subject)
+        .bind(day)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(usage.unwrap_or_else(|| QuotaUsage {
+            subject: subject.to_string(),
+            day,
+            requests: 0,
+            tokens: 0,
+        }))
+    }
+
+    /// Today's heaviest callers, most requests first.
+    pub async fn top_usage(&self, limit: i64) -> Result<Vec<QuotaUsage>, sqlx::Error> {
+        sqlx::query_as::<_, QuotaUsage>(
+            "SELECT subject, day, requests, tokens FROM usage_quotas
+             WHERE day = $1 ORDER BY requests DESC, tokens DESC LIMIT $2",
+        )
+        .bind(Self::today())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Clears today's usage for `subject`. Returns whether it had any.
+    pub async fn reset(&self, subject: &Subject) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM usage_quotas WHERE subject = $1 AND day = $2")
+            .bind(subject.to_string())
+            .bind(Self::today())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub fn limits(&self) -> (i64, i64) {
+        (self.daily_requests, self.daily_tokens)
+    }
+}
@@ -0,0 +1,163 @@
+use lru::LruCache;
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep_until;
+
+use crate::config::LimitsConfig;
+
+/// Callers tracked at once. The least recently seen are forgotten first,
+/// which only hands them a full bucket again.
+const MAX_TRACKED: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// In-process token buckets, one per caller key. Each instance keeps its
+/// own buckets, so behind N instances a caller's burst is up to N times
+/// `RATE_LIMIT_BURST`; the daily quota in Postgres is shared.
+pub struct RateLimiter {
+    capacity: f64,
+    per_sec: f64,
+    buckets: Mutex<LruCache<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            capacity: config.burst as f64,
+            per_sec: config.per_minute as f64 / 60.0,
+            buckets: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_TRACKED).unwrap())),
+        }
+    }
+
+    /// Takes one token for `key`. Returns the whole tokens left, or how
+    /// long until the next one when the bucket is empty.
+    pub fn acquire(&self, key: &str) -> Result<u32, Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_or_insert_mut(key.to_string(), || Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(bucket.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_sec))
+        }
+    }
+
+    /// Refills `key`'s bucket, for admins resetting a caller.
+    pub fn reset(&self, key: &str) {
+        self.buckets.lock().unwrap().pop(key);
+    }
+}
+
+/// Spaces out calls to an upstream so they start no faster than a fixed
+/// rate, however many tasks share it.
+pub struct Pacer {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Pacer {
+    pub fn per_second(rate: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / rate.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for this caller's turn.
+    pub async fn wait(&self) {
+        let turn = {
+            let mut next = self.next.lock().unwrap();
+            let turn = (*next).max(Instant::now());
+            *next = turn + self.interval;
+            turn
+        };
+        sleep_until(turn.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(burst: u32, per_minute: u32) -> RateLimiter {
+        RateLimiter::new(&LimitsConfig {
+            burst,
+            per_minute,
+            daily_requests: 0,
+            daily_tokens: 0,
+            trust_forwarded_for: false,
+        })
+    }
+
+    #[test]
+    fn allows_a_burst_then_says_how_long_to_wait() {
+        let limiter = limiter(3, 60);
+
+        assert_eq!(limiter.acquire("ip:1"), Ok(2));
+        assert_eq!(limiter.acquire("ip:1"), Ok(1));
+        assert_eq!(limiter.acquire("ip:1"), Ok(0));
+
+        // One token a second, and the bucket was just emptied
+        let wait = limiter.acquire("ip:1").unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1), "{:?}", wait);
+    }
+
+    #[test]
+    fn keeps_one_bucket_per_caller() {
+        let limiter = limiter(1, 1);
+
+        assert!(limiter.acquire("ip:1").is_ok());
+        assert!(limiter.acquire("ip:1").is_err());
+        assert!(limiter.acquire("ip:2").is_ok());
+    }
+
+    #[test]
+    fn refills_over_time_up_to_the_burst() {
+        let limiter = limiter(2, 6000);
+
+        assert!(limiter.acquire("ip:1").is_ok());
+        assert!(limiter.acquire("ip:1").is_ok());
+        std::thread::sleep(Duration::from_millis(50));
+
+        // 100 tokens a second for 50 ms, but never more than the burst
+        assert_eq!(limiter.acquire("ip:1"), Ok(1));
+        assert_eq!(limiter.acquire("ip:1"), Ok(0));
+    }
+
+    #[test]
+    fn reset_refills_the_bucket() {
+        let limiter = limiter(1, 1);
+
+        assert!(limiter.acquire("ip:1").is_ok());
+        limiter.reset("ip:1");
+        assert!(limiter.acquire("ip:1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn pacer_spaces_out_callers() {
+        let pacer = Pacer::per_second(20);
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            pacer.wait().await;
+        }
+
+        // The first goes at once, the next two 50 ms apart
+        assert!(started.elapsed() >= Duration::from_millis(100), "{:?}", started.elapsed());
+    }
+}
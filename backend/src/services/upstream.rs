@@ -0,0 +1,133 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+
+use super::circuit::CircuitBreaker;
+use super::http::{HttpClient, TransportError, Upstream};
+use crate::error::{attach_request_id, current_request_id, ApiError, ErrorResponse};
+
+/// A failed upstream call, already mapped to the status and JSON body the
+/// client should see. Cloneable so one failure can be handed to every
+/// request that was waiting on the same call; the body carries no request
+/// id until it is turned into a response.
+#[derive(Debug, Clone)]
+pub struct UpstreamError {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl UpstreamError {
+    pub fn new(status: StatusCode, body: Value) -> Self {
+        Self { status, body }
+    }
+}
+
+impl IntoResponse for UpstreamError {
+    fn into_response(self) -> Response {
+        let mut body = self.body;
+        attach_request_id(&mut body, current_request_id().as_deref());
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// How one provider's failures are labelled: the error-code prefix
+/// (`AI_RATE_LIMIT`, `GEMINI_RATE_LIMIT`, ...) and a human-readable name.
+#[derive(Debug, Clone, Copy)]
+pub struct Labels {
+    pub prefix: &'static str,
+    pub service: &'static str,
+}
+
+impl Labels {
+    /// The provider's catalog entry for a failure, e.g. `TIMEOUT` becomes
+    /// `GEMINI_TIMEOUT`. Providers without a specific entry get their
+    /// `_ERROR` code.
+    pub fn error(&self, suffix: &str, message: impl Into<String>) -> ErrorResponse {
+        let code = format!("{}_{}", self.prefix, suffix);
+        let error = ApiError::from_code(&code)
+            .or_else(|| ApiError::from_code(&format!("{}_ERROR", self.prefix)))
+            .unwrap_or_else(|| {
+                tracing::error!("{} is missing from the error catalog", code);
+                ApiError::InternalError
+            });
+        error.with_message(message)
+    }
+}
+
+/// Sends a prepared request and parses the JSON body, mapping every
+/// failure to the provider's codes.
+pub async fn send_json(
+    http: &HttpClient,
+    upstream: Upstream,
+    breaker: &CircuitBreaker,
+    request: reqwest::RequestBuilder,
+    labels: Labels,
+) -> Result<Value, UpstreamError> {
+    let response = http
+        .send(upstream, breaker, request)
+        .await
+        .map_err(|e| transport_error(labels, &e))?;
+    let response = check_status(response, labels).await?;
+    response.json::<Value>().await.map_err(|e| {
+        if e.is_timeout() {
+            return transport_error(labels, &TransportError::Timeout(http.timeout(upstream)));
+        }
+        tracing::error!("Failed to parse {} response: {:?}", labels.service, e);
+        labels
+            .error("PARSE_ERROR", format!("The {} returned an invalid response format", labels.service))
+            .into()
+    })
+}
+
+/// Passes a successful response through and maps any other status to the
+/// provider's codes, keeping the upstream status.
+pub async fn check_status(response: reqwest::Response, labels: Labels) -> Result<reqwest::Response, UpstreamError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+    tracing::error!("{} error ({}): {}", labels.service, status, error_text);
+
+    let suffix = match status.as_u16() {
+        400 => "BAD_REQUEST",
+        401 => "UNAUTHORIZED",
+        402 => "QUOTA_EXCEEDED",
+        403 => "FORBIDDEN",
+        429 => "RATE_LIMIT",
+        500 => "SERVER_ERROR",
+        503 => "UNAVAILABLE",
+        _ => "ERROR",
+    };
+
+    Err(labels
+        .error(suffix, error_text)
+        .status(StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .into())
+}
+
+/// Maps a request that never got (or lost) its response.
+pub fn transport_error(labels: Labels, e: &TransportError) -> UpstreamError {
+    if let TransportError::CircuitOpen(retry_in) = e {
+        return labels
+            .error("CIRCUIT_OPEN", format!("Not calling {}: {}", labels.service, e))
+            .detail("retry_after", retry_in.as_secs())
+            .into();
+    }
+
+    tracing::error!("Failed to call {}: {:?}", labels.service, e);
+    let suffix = if e.is_timeout() {
+        "TIMEOUT"
+    } else if e.is_connect() {
+        "CONNECTION_ERROR"
+    } else {
+        "SERVICE_ERROR"
+    };
+    labels
+        .error(suffix, format!("Failed to reach {}: {}", labels.service, e))
+        .into()
+}
@@ -0,0 +1,108 @@
+#![allow(dead_code)]
+
+use backend::{auth::JwtVerifier, config::Config, routes::create_router};
+use axum::Router;
+use serde_json::json;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::{
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+/// The `mock_upstream` binary on a free local port, stopped on drop.
+pub struct MockUpstream {
+    child: Child,
+    pub url: String,
+}
+
+impl MockUpstream {
+    pub async fn start() -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_mock_upstream"))
+            .env("MOCK_UPSTREAM_ADDR", addr.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("start mock_upstream");
+        let mock = Self {
+            child,
+            url: format!("http://{}", addr),
+        };
+
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                return mock;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("mock_upstream did not start listening on {}", addr);
+    }
+
+    /// Makes `service` answer with `scenario` (success, 401, 429, timeout
+    /// or malformed) until changed again.
+    pub async fn scenario(&self, service: &str, scenario: &str) {
+        let response = reqwest::Client::new()
+            .put(format!("{}/_mock/scenario", self.url))
+            .json(&json!({ "service": service, "scenario": scenario }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "setting mock scenario failed");
+    }
+
+    /// Config with every upstream pointed at the mock, plus `extra` TOML.
+    pub fn config(&self, database_url: &str, extra: &str) -> Config {
+        let url = &self.url;
+        Config::from_toml(&format!(
+            r#"
+            [database]
+            url = "{database_url}"
+
+            [upstreams]
+            perplexity_base_url = "{url}"
+            gemini_base_url = "{url}/v1beta"
+            opencage_base_url = "{url}/geocode/v1"
+            nominatim_base_url = "{url}/nominatim"
+            google_maps_base_url = "{url}/maps/api"
+
+            [keys]
+            ai_service = "mock"
+            gemini = "mock"
+            opencage = "mock"
+            google_maps = "mock"
+
+            [http]
+            geocode_timeout_secs = 2
+            ai_timeout_secs = 5
+            max_retries = 0
+
+            {extra}
+            "#
+        ))
+        .expect("valid test config")
+    }
+}
+
+impl Drop for MockUpstream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn router(config: Config, pool: PgPool) -> Router {
+    let auth = JwtVerifier::new(&config.auth).expect("auth config");
+    create_router(config, pool, auth)
+}
+
+pub const UNREACHABLE_DATABASE_URL: &str = "postgres://terratruce@127.0.0.1:1/terratruce";
+
+/// A pool for a database that is never there. Every query fails quickly,
+/// so caches miss and quotas fail open while the in-memory parts still run.
+pub fn unreachable_pool() -> PgPool {
+    PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(100))
+        .connect_lazy(UNREACHABLE_DATABASE_URL)
+        .expect("lazy pool")
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use backend::config::Config;
+use common::{router, unreachable_pool, UNREACHABLE_DATABASE_URL};
+use serde_json::Value;
+use tower::ServiceExt;
+
+const ALLOWED: &str = "https://app.terratruce.test";
+const FOREIGN: &str = "https://evil.example";
+
+/// The full router with a fixed CORS policy. The pool never connects, so
+/// the requests below stop before any route touches the database.
+fn app() -> Router {
+    let config = Config::from_toml(&format!(
+        r#"
+        [database]
+        url = "{UNREACHABLE_DATABASE_URL}"
+
+        [cors]
+        allowed_origins = ["{ALLOWED}"]
+        public_origins = ["*"]
+        "#
+    ))
+    .expect("valid test config");
+    router(config, unreachable_pool())
+}
+
+async fn send(method: Method, uri: &str, origin: Option<&str>) -> Response {
+    let mut request = Request::builder().method(method).uri(uri).header(header::HOST, "api.terratruce.test");
+    if let Some(origin) = origin {
+        request = request.header(header::ORIGIN, origin);
+    }
+    app().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+async fn preflight(uri: &str, origin: &str) -> Response {
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+        .body(Body::empty())
+        .unwrap();
+    app().oneshot(request).await.unwrap()
+}
+
+fn allow_origin(response: &Response) -> Option<&str> {
+    response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|value| value.to_str().ok())
+}
+
+async fn error_code(response: Response) -> String {
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    body["code"].as_str().unwrap_or_default().to_string()
+}
+
+#[tokio::test]
+async fn foreign_origin_is_rejected_on_api_routes() {
+    let response = send(Method::GET, "/search", Some(FOREIGN)).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(allow_origin(&response), None);
+    assert_eq!(error_code(response).await, "CORS_ORIGIN_NOT_ALLOWED");
+}
+
+#[tokio::test]
+async fn foreign_origin_preflight_is_rejected() {
+    let response = preflight("/api/analyze", FOREIGN).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(allow_origin(&response), None);
+}
+
+#[tokio::test]
+async fn allowed_origin_preflight_lists_methods_and_headers() {
+    let response = preflight("/api/analyze", ALLOWED).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(allow_origin(&response), Some(ALLOWED));
+    let methods = response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+    assert!(methods.contains("POST"));
+    let headers = response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+    assert!(headers.contains("authorization"));
+    assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+}
+
+#[tokio::test]
+async fn allowed_origin_reaches_the_handler() {
+    let response = send(Method::GET, "/search", Some(ALLOWED)).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(allow_origin(&response), Some(ALLOWED));
+    assert_eq!(error_code(response).await, "AUTH_MISSING_TOKEN");
+}
+
+#[tokio::test]
+async fn requests_without_origin_are_not_cors_checked() {
+    let response = send(Method::GET, "/search", None).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn same_origin_requests_pass() {
+    let response = send(Method::GET, "/search", Some("https://api.terratruce.test")).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn public_routes_allow_any_origin() {
+    let response = send(Method::GET, "/health/live", Some(FOREIGN)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(allow_origin(&response), Some("*"));
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    response::Response,
+};
+use common::{router, unreachable_pool, MockUpstream, UNREACHABLE_DATABASE_URL};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+/// Both providers point at the mock. The pool never connects (and gives up
+/// quickly), so every lookup misses the cache and reaches a provider.
+async fn send(mock: &MockUpstream, extra: &str, request: Request<Body>) -> Response {
+    let config = mock.config(UNREACHABLE_DATABASE_URL, extra);
+    router(config, unreachable_pool()).oneshot(request).await.unwrap()
+}
+
+async fn geocode(mock: &MockUpstream, query: &str) -> Response {
+    let request = Request::builder()
+        .uri(format!("/api/geocode?q={}", query))
+        .body(Body::empty())
+        .unwrap();
+    send(mock, "", request).await
+}
+
+async fn json(response: Response) -> Value {
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn providers(body: &Value) -> Vec<&str> {
+    body["results"]
+        .as_array()
+        .expect("results")
+        .iter()
+        .map(|result| result["provider"].as_str().unwrap_or_default())
+        .collect()
+}
+
+#[tokio::test]
+async fn opencage_answers_first() {
+    let mock = MockUpstream::start().await;
+
+    let response = geocode(&mock, "12%20Main%20St").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json(response).await;
+    assert!(!providers(&body).is_empty());
+    assert!(providers(&body).iter().all(|provider| *provider == "opencage"));
+}
+
+#[tokio::test]
+async fn fails_over_to_nominatim_when_opencage_is_rate_limited() {
+    let mock = MockUpstream::start().await;
+    mock.scenario("geocode", "429").await;
+
+    let response = geocode(&mock, "12%20Main%20St").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json(response).await;
+    assert!(!providers(&body).is_empty());
+    assert!(providers(&body).iter().all(|provider| *provider == "nominatim"));
+}
+
+#[tokio::test]
+async fn returns_the_last_providers_error_when_all_fail() {
+    let mock = MockUpstream::start().await;
+    mock.scenario("geocode", "401").await;
+    mock.scenario("nominatim", "429").await;
+
+    let response = geocode(&mock, "12%20Main%20St").await;
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(json(response).await["code"], "NOMINATIM_RATE_LIMIT");
+}
+
+#[tokio::test]
+async fn batch_failovers_are_paced_at_nominatims_rate() {
+    let mock = MockUpstream::start().await;
+    mock.scenario("geocode", "429").await;
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/geocode/batch")
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "queries": ["1 Main St", "2 Main St", "3 Main St"] }).to_string()))
+        .unwrap();
+    let extra = "[geocode]\nbatch_rate_per_sec = 100\nnominatim_rate_per_sec = 2";
+    let started = Instant::now();
+    let response = send(&mock, extra, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json(response).await["ok"], 3);
+    // Three Nominatim calls at 2/s: the last starts a second after the first
+    assert!(started.elapsed() >= Duration::from_millis(1000), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn suggest_failovers_are_paced_at_nominatims_rate() {
+    let mock = MockUpstream::start().await;
+    mock.scenario("geocode", "429").await;
+    let config = mock.config(UNREACHABLE_DATABASE_URL, "[geocode]\nnominatim_rate_per_sec = 2");
+    let app = router(config, unreachable_pool());
+
+    let started = Instant::now();
+    for query in ["1%20Main", "2%20Main", "3%20Main"] {
+        let request = Request::builder()
+            .uri(format!("/api/geocode/suggest?q={}", query))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    assert!(started.elapsed() >= Duration::from_millis(1000), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn short_suggest_queries_bypass_the_cache() {
+    let mock = MockUpstream::start().await;
+
+    let request = Request::builder()
+        .uri("/api/geocode/suggest?q=ab")
+        .body(Body::empty())
+        .unwrap();
+    let response = send(&mock, "", request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-cache"], "BYPASS");
+    assert_eq!(json(response).await["suggestions"], json!([]));
+}
+
+#[tokio::test]
+async fn overlong_suggest_queries_are_rejected() {
+    let mock = MockUpstream::start().await;
+
+    let request = Request::builder()
+        .uri(format!("/api/geocode/suggest?q={}", "a".repeat(101)))
+        .body(Body::empty())
+        .unwrap();
+    let response = send(&mock, "", request).await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(json(response).await["code"], "INVALID_QUERY");
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+};
+use backend::services::quota::{QuotaService, Subject};
+use common::{router, unreachable_pool, MockUpstream, UNREACHABLE_DATABASE_URL};
+use serde_json::{json, Value};
+use sqlx::postgres::PgPoolOptions;
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    time::Duration,
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Runs against the database at `DATABASE_URL`, which needs the
+/// `usage_quotas` and cache tables. Run with `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn analyze_charges_upstream_tokens() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let mock = MockUpstream::start().await;
+    let config = mock.config(&database_url, "[limits]\ntrust_forwarded_for = true");
+    let pool = PgPoolOptions::new().connect(&database_url).await.expect("database");
+    let quotas = QuotaService::new(pool.clone(), &config.limits);
+
+    // A fresh caller and location, so neither earlier usage nor a cached
+    // report gets in the way
+    let ip = IpAddr::V6(Ipv6Addr::from(Uuid::new_v4().as_u128()));
+    let subject = Subject::Ip(ip);
+    let location = format!("{} Test Street, Springfield", Uuid::new_v4().simple());
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/api/analyze")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-forwarded-for", ip.to_string())
+        .body(Body::from(json!({ "location": location }).to_string()))
+        .unwrap();
+    let response = router(config, pool).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Tokens are recorded in the background once the response is built
+    let mut usage = quotas.usage(&subject).await.unwrap();
+    for _ in 0..50 {
+        if usage.tokens > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        usage = quotas.usage(&subject).await.unwrap();
+    }
+
+    assert_eq!(usage.requests, 1);
+    assert!(usage.tokens > 0, "analyze charged no tokens");
+    quotas.reset(&subject).await.unwrap();
+}
+
+/// A suggest query too short to geocode from `forwarded_for`, so only the
+/// limiter (in memory) and the quota (failing open) are involved.
+fn suggest_from(forwarded_for: &str) -> Request<Body> {
+    Request::builder()
+        .uri("/api/geocode/suggest?q=ab")
+        .header("x-forwarded-for", forwarded_for)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn bursts_past_the_limit_get_429_with_retry_after() {
+    let mock = MockUpstream::start().await;
+    let config = mock.config(
+        UNREACHABLE_DATABASE_URL,
+        "[limits]\ntrust_forwarded_for = true\nburst = 2\nper_minute = 6",
+    );
+    let app = router(config, unreachable_pool());
+
+    for _ in 0..2 {
+        let response = app.clone().oneshot(suggest_from("203.0.113.9")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = app.oneshot(suggest_from("203.0.113.9")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    // One token every 10 seconds
+    assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "RATE_LIMITED");
+    assert_eq!(body["retry_after"], 10);
+}
+
+#[tokio::test]
+async fn spoofed_forwarded_for_entries_are_ignored() {
+    let mock = MockUpstream::start().await;
+    let config = mock.config(
+        UNREACHABLE_DATABASE_URL,
+        "[limits]\ntrust_forwarded_for = true\nburst = 1\nper_minute = 1",
+    );
+    let app = router(config, unreachable_pool());
+
+    let first = app.clone().oneshot(suggest_from("10.0.0.1, 203.0.113.7")).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+
+    // A new leftmost address does not make a new caller
+    let second = app.clone().oneshot(suggest_from("10.0.0.2, 203.0.113.7")).await.unwrap();
+    assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let other = app.oneshot(suggest_from("10.0.0.2, 203.0.113.8")).await.unwrap();
+    assert_eq!(other.status(), StatusCode::OK);
+}
@@ -4,7 +4,7 @@ import { useGSAP } from '@gsap/react';
 import gsap from 'gsap';
 import { getSuggestions } from '../../services/geocoding';
 
-const LocationSearch = ({ onSearch, history = [], loading }) => {
+const LocationSearch = ({ onSearch, history = [], loading, near = null }) => {
   const [query, setQuery] = useState('');
   const [showSuggestions, setShowSuggestions] = useState(false);
   const [apiSuggestions, setApiSuggestions] = useState([]);
@@ -44,10 +44,10 @@ const LocationSearch = ({ onSearch, history = [], loading }) => {
 
     setIsSearchingSuggestions(true);
     debounceTimeout.current = setTimeout(async () => {
-      const results = await getSuggestions(val);
+      const results = await getSuggestions(val, near);
       setApiSuggestions(results);
       setIsSearchingSuggestions(false);
-    }, 500);
+    }, 250);
   };
 
   const handleSelectSuggestion = (suggestion, isApi = false) => {
@@ -55,8 +55,8 @@ const LocationSearch = ({ onSearch, history = [], loading }) => {
     const locationData = isApi
       ? {
           name: suggestion.label,
-          lat: suggestion.geometry.lat,
-          lng: suggestion.geometry.lng,
+          lat: suggestion.coordinates?.lat ?? null,
+          lng: suggestion.coordinates?.lng ?? null,
         }
       : suggestion;
 
@@ -159,7 +159,7 @@ const LocationSearch = ({ onSearch, history = [], loading }) => {
                   className="w-full flex items-center gap-3 px-4 py-3 hover:bg-brand-primary/5 transition-colors text-left group border-l-2 border-transparent hover:border-brand-primary"
                 >
                   <div className="p-2 bg-gray-100 dark:bg-gray-800 rounded-lg group-hover:bg-white text-text-secondary group-hover:text-brand-primary transition-colors">
-                    <MapPin className="h-4 w-4" />
+                    {item.fromHistory ? <Clock className="h-4 w-4" /> : <MapPin className="h-4 w-4" />}
                   </div>
                   <span className="text-sm font-medium text-text-primary group-hover:text-brand-primary transition-colors">
                     {item.label}
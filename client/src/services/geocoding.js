@@ -1,10 +1,11 @@
 /**
- * OpenCage Geocoding API Service
- * Provides location enrichment and coordinate conversion
+ * Geocoding Service
+ * Provides location enrichment and coordinate conversion through the backend
  */
 
-const OPENCAGE_API_KEY = import.meta.env.VITE_OPENCAGE_API_KEY;
-const OPENCAGE_BASE_URL = 'https://api.opencagedata.com/geocode/v1/json';
+import { supabase } from './supabase';
+
+const BACKEND_URL = import.meta.env.VITE_BACKEND_URL || '';
 
 /**
  * Geocode an address to get coordinates and location details
@@ -14,7 +15,7 @@ const OPENCAGE_BASE_URL = 'https://api.opencagedata.com/geocode/v1/json';
 export const geocodeAddress = async (address) => {
   try {
     const response = await fetch(
-      `${OPENCAGE_BASE_URL}?q=${encodeURIComponent(address)}&key=${OPENCAGE_API_KEY}&limit=1&no_annotations=0`
+      `${BACKEND_URL}/api/geocode?q=${encodeURIComponent(address)}&limit=1`
     );
 
     if (!response.ok) {
@@ -27,31 +28,26 @@ export const geocodeAddress = async (address) => {
       throw new Error('No results found for this location');
     }
 
+    // The backend returns a normalized GeocodeResult
     const result = data.results[0];
     const components = result.components;
-    const annotations = result.annotations;
 
     return {
-      formatted_address: result.formatted,
-      coordinates: {
-        lat: result.geometry.lat,
-        lng: result.geometry.lng,
-      },
+      formatted_address: result.formatted_address,
+      coordinates: result.coordinates,
       location_details: {
         country: components.country || 'Unknown',
-        country_code: components.country_code?.toUpperCase() || '',
-        state: components.state || components.state_code || '',
+        country_code: components.country_code || '',
+        state: components.state || '',
         county: components.county || '',
-        city: components.city || components.town || components.village || '',
+        city: components.city || '',
         postcode: components.postcode || '',
         road: components.road || '',
-        suburb: components.suburb || components.neighbourhood || '',
+        suburb: components.suburb || '',
       },
       metadata: {
-        timezone: annotations?.timezone?.name || 'UTC',
-        currency: annotations?.currency?.name || '',
-        flag: annotations?.flag || '',
-        continent: components.continent || '',
+        timezone: result.timezone || 'UTC',
+        currency: result.currency || '',
         confidence: result.confidence || 0,
       },
       bounds: result.bounds || null,
@@ -65,27 +61,34 @@ export const geocodeAddress = async (address) => {
 /**
  * Get location suggestions for autocomplete
  * @param {string} query - The search text
+ * @param {{lat: number, lng: number}} [near] - Optional point to rank nearby places first
  * @returns {Promise<Array>} List of suggestions
  */
-export const getSuggestions = async (query) => {
-  if (!query || query.length < 3) return [];
+export const getSuggestions = async (query, near = null) => {
+  if (!query || query.trim().length < 3) return [];
 
   try {
-    // Add fuzzy=1 to allow for typos
-    const response = await fetch(
-      `${OPENCAGE_BASE_URL}?q=${encodeURIComponent(query)}&key=${OPENCAGE_API_KEY}&limit=5&no_annotations=1`
-    );
+    const params = new URLSearchParams({ q: query });
+    if (near) params.set('near', `${near.lat},${near.lng}`);
+
+    // Signed-in users get their own past searches boosted
+    const { data: sessionData } = await supabase.auth.getSession();
+    const token = sessionData?.session?.access_token;
+
+    const response = await fetch(`${BACKEND_URL}/api/geocode/suggest?${params}`, {
+      headers: token ? { Authorization: `Bearer ${token}` } : {},
+    });
 
     if (!response.ok) return [];
 
     const data = await response.json();
-    if (!data.results) return [];
+    if (!data.suggestions) return [];
 
-    return data.results.map((r) => ({
-      label: r.formatted,
-      components: r.components,
-      geometry: r.geometry,
-      bounds: r.bounds,
+    return data.suggestions.map((s) => ({
+      label: s.label,
+      coordinates: s.coordinates,
+      fromHistory: s.from_history,
+      distanceKm: s.distance_km,
     }));
   } catch (error) {
     console.error('Suggestion error:', error);
@@ -101,23 +104,20 @@ export const getSuggestions = async (query) => {
  */
 export const reverseGeocode = async (lat, lng) => {
   try {
-    const response = await fetch(
-      `${OPENCAGE_BASE_URL}?q=${lat}+${lng}&key=${OPENCAGE_API_KEY}&limit=1`
-    );
+    const response = await fetch(`${BACKEND_URL}/api/reverse-geocode?lat=${lat}&lng=${lng}`);
 
+    if (response.status === 404) {
+      // NO_RESULTS: open water or an unaddressable point
+      return null;
+    }
     if (!response.ok) {
       throw new Error(`Reverse geocoding failed: ${response.status}`);
     }
 
     const data = await response.json();
-
-    if (!data.results || data.results.length === 0) {
-      throw new Error('No results found for these coordinates');
-    }
-
     const result = data.results[0];
     return {
-      formatted_address: result.formatted,
+      formatted_address: result.formatted_address,
       components: result.components,
     };
   } catch (error) {